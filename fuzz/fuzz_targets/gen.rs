@@ -0,0 +1,9 @@
+// the original one-shot api, with the corpus, seed and length all up for grabs
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (&str, &str, i8)| {
+    let (corpus, seed, length) = input;
+    let _ = markov::gen(corpus, seed, length as i32);
+});
@@ -0,0 +1,17 @@
+// model files get passed around, so reading one can't trust a single byte of it
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use markov::{Chain, FrozenChain};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(chain) = Chain::read_from(&mut &bytes[..]) {
+        let _ = chain.generate(&Default::default());
+    }
+    if let Ok(chain) = FrozenChain::from_bytes(bytes.to_vec()) {
+        for w in ["a", "the", ""] {
+            let _ = chain.successors(w);
+            let _ = chain.generate(w, 20);
+        }
+    }
+});
@@ -0,0 +1,12 @@
+// splits and trains on arbitrary text, then asks the chain about it
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use markov::{Chain, GenConfig};
+
+fuzz_target!(|text: &str| {
+    let mut chain = Chain::new();
+    let _ = chain.train(text);
+    let _ = chain.coverage(text);
+    let _ = chain.reply(text, &GenConfig::default());
+});
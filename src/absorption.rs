@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use Chain;
+
+// value iteration stops once no word's expectation moves by more than this in a round
+const TOLERANCE: f64 = 1e-9;
+const MAX_ROUNDS: usize = 100_000;
+
+impl Chain {
+    /*
+    for every word, how many words we'd expect a text to have from that one on, itself included,
+    going by how often each word ended a training text rather than going on to another. this is
+    the expected time to absorption in the chain with the ends of texts as absorbing states. a
+    word that can never reach the end of a text (a loop with no way out) gets infinity.
+    */
+    pub fn steps_to_end(&self) -> BTreeMap<String, f64> {
+        let words = self.vocab();
+        // a word's outgoing edges, and how many times it went anywhere or ended a text
+        let mut out: HashMap<&String, Vec<(&String, f64)>> = HashMap::new();
+        for ((a, b), &weight) in &self.edges {
+            out.entry(a).or_default().push((b, weight as f64));
+        }
+        let seen = |w: &String| (*self.nodes.get(w).unwrap_or(&0) + *self.ends.get(w).unwrap_or(&0)) as f64;
+
+        // anything that never goes on, or ever ends a text, can finish one. so can anything that
+        // reaches one of those.
+        let mut finishes: HashSet<&String> = words.iter().filter(|w| !self.nodes.contains_key(*w) || self.ends.contains_key(*w)).collect();
+        let mut into: HashMap<&String, Vec<&String>> = HashMap::new();
+        for (a, b) in self.edges.keys() {
+            into.entry(b).or_default().push(a);
+        }
+        let mut queue: VecDeque<&String> = finishes.iter().cloned().collect();
+        while let Some(w) = queue.pop_front() {
+            for &before in into.get(w).into_iter().flatten() {
+                if finishes.insert(before) {
+                    queue.push_back(before);
+                }
+            }
+        }
+
+        // t(w) = 1 + the sum over w's successors v of P(w -> v) * t(v), solved by iterating from
+        // 0, which converges for every word that's sure to finish eventually
+        let mut expected: HashMap<&String, f64> = words.iter().filter(|w| finishes.contains(w)).map(|w| (w, 0.0)).collect();
+        for _ in 0..MAX_ROUNDS {
+            let mut moved: f64 = 0.0;
+            for w in words.iter().filter(|w| finishes.contains(w)) {
+                let total = seen(w);
+                let onward: f64 = out.get(w).into_iter().flatten()
+                    .map(|&(next, weight)| weight / total * expected.get(next).cloned().unwrap_or(f64::INFINITY))
+                    .sum();
+                let t = 1.0 + onward;
+                moved = moved.max((t - expected[w]).abs());
+                expected.insert(w, t);
+            }
+            if moved < TOLERANCE {
+                break;
+            }
+        }
+
+        words.iter().map(|w| (w.clone(), expected.get(w).cloned().unwrap_or(f64::INFINITY))).collect()
+    }
+
+    /*
+    how many words we'd expect a generated text to have, if it starts where training texts did and
+    stops at the end of one: steps_to_end averaged over the words texts started with. None if we
+    haven't seen any texts start, infinity if one of their start words can never reach an end.
+    */
+    pub fn expected_length(&self) -> Option<f64> {
+        let total: i32 = self.starts.values().sum();
+        if total == 0 {
+            return None;
+        }
+        let steps = self.steps_to_end();
+        Some(self.starts.iter().map(|(w, &count)| count as f64 / total as f64 * steps[w]).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_to_end() {
+        let mut chain = Chain::new();
+        chain.train("a b c").unwrap();
+        chain.train("a c").unwrap();
+        let steps = chain.steps_to_end();
+        assert_eq!(steps["c"], 1.0);
+        assert_eq!(steps["b"], 2.0);
+        assert!((steps["a"] - 2.5).abs() < 1e-6);
+        assert!((chain.expected_length().unwrap() - 2.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_steps_to_end_geometric() {
+        // "a" ends one text in four, otherwise goes round again, so a text is 4 words on average
+        let mut chain = Chain::new();
+        chain.see_weighted("a", "a", 3);
+        *chain.ends.entry("a".to_string()).or_insert(0) += 1;
+        *chain.starts.entry("a".to_string()).or_insert(0) += 1;
+        assert!((chain.steps_to_end()["a"] - 4.0).abs() < 1e-6);
+        assert!((chain.expected_length().unwrap() - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_steps_to_end_forever() {
+        let mut chain = Chain::new();
+        chain.see("round", "and");
+        chain.see("and", "round");
+        chain.see("out", "round");
+        chain.see("exit", "done");
+        let steps = chain.steps_to_end();
+        assert_eq!(steps["round"], f64::INFINITY);
+        assert_eq!(steps["out"], f64::INFINITY);
+        assert_eq!(steps["exit"], 2.0);
+        assert_eq!(chain.expected_length(), None);
+    }
+}
@@ -0,0 +1,167 @@
+use rand::{thread_rng, Rng};
+
+use MarkovErr;
+
+// the most counts an AlphabetChain will allocate: 4^12 contexts of DNA, or 20^5 of protein
+const MAX_TABLE: usize = 1 << 26;
+
+/*
+a chain over the characters of a small, fixed alphabet (the four bases of DNA, the twenty amino
+acids), for simulating sequences. with so few symbols every context of order characters gets a
+row in one flat table, indexed by reading the context as a number in base alphabet-size, so a
+step is a bit of arithmetic rather than a map lookup.
+*/
+#[derive(Debug, Clone)]
+pub struct AlphabetChain {
+    alphabet: Vec<u8>,
+    // each ASCII character's place in alphabet, or NONE if it isn't in it
+    lookup: [u8; 128],
+    order: usize,
+    // contexts.len() rows of alphabet.len() counts each
+    counts: Vec<u32>
+}
+
+const NONE: u8 = u8::MAX;
+
+impl AlphabetChain {
+    /*
+    a chain over the characters in alphabet (e.g. "ACGT"), where each depends on the order (at
+    least one) before it. None if the alphabet isn't distinct ASCII characters, or if there'd be
+    too many contexts to keep a table for (more than 2^26 counts).
+    */
+    pub fn new(alphabet: &str, order: usize) -> Option<AlphabetChain> {
+        let order = order.max(1);
+        let mut lookup = [NONE; 128];
+        for (i, c) in alphabet.bytes().enumerate() {
+            if c >= 128 || lookup[c as usize] != NONE || i >= NONE as usize {
+                return None;
+            }
+            lookup[c as usize] = i as u8;
+        }
+        let n = alphabet.len();
+        let size = (0..=order).try_fold(1usize, |size, _| size.checked_mul(n)).filter(|&size| size > 0 && size <= MAX_TABLE)?;
+        Some(AlphabetChain { alphabet: alphabet.as_bytes().to_vec(), lookup, order, counts: vec![0; size] })
+    }
+
+    /*
+    counts every run of order + 1 characters in sequence. a character outside the alphabet (like
+    N for an unknown base) breaks the sequence there, so nothing's counted across it.
+    EmptyCorpus if no run was long enough.
+    */
+    pub fn train(&mut self, sequence: &str) -> Result<(), MarkovErr> {
+        let n = self.alphabet.len();
+        let rows = self.counts.len() / n;
+        let mut trained = false;
+        // the context so far as a row number, and how many of its characters are real
+        let mut row = 0;
+        let mut filled = 0;
+        for c in sequence.bytes() {
+            let symbol = match self.symbol(c) {
+                Some(symbol) => symbol,
+                None => {
+                    filled = 0;
+                    continue;
+                }
+            };
+            if filled == self.order {
+                let count = &mut self.counts[row * n + symbol];
+                *count = count.saturating_add(1);
+                trained = true;
+            }
+            row = (row * n + symbol) % rows;
+            filled = (filled + 1).min(self.order);
+        }
+        if trained { Ok(()) } else { Err(MarkovErr::EmptyCorpus) }
+    }
+
+    /*
+    how many times each character of the alphabet followed context, in alphabet order. None if
+    context isn't order characters of the alphabet.
+    */
+    pub fn successors(&self, context: &str) -> Option<Vec<(char, u32)>> {
+        let row = self.row(context.as_bytes())?;
+        let n = self.alphabet.len();
+        Some(self.alphabet.iter().zip(&self.counts[row * n..(row + 1) * n]).map(|(&c, &count)| (c as char, count)).collect())
+    }
+
+    /*
+    carries on from seed until the output is len characters long (seed included), stopping early
+    at a dead end. the seed's last order characters have to be a context we've seen.
+    */
+    pub fn generate(&self, seed: &str, len: usize) -> Result<String, MarkovErr> {
+        let not_seen = || MarkovErr::NotSeen{w: seed.to_string()};
+        if seed.len() < self.order {
+            return Err(not_seen());
+        }
+        let n = self.alphabet.len();
+        let rows = self.counts.len() / n;
+        let mut row = self.row(&seed.as_bytes()[seed.len() - self.order..]).ok_or_else(not_seen)?;
+        if self.counts[row * n..(row + 1) * n].iter().all(|&count| count == 0) {
+            return Err(not_seen());
+        }
+
+        let mut rng = thread_rng();
+        let mut out = seed.as_bytes().to_vec();
+        while out.len() < len {
+            let counts = &self.counts[row * n..(row + 1) * n];
+            let total: u64 = counts.iter().map(|&count| count as u64).sum();
+            if total == 0 {
+                break;
+            }
+            let mut index = rng.gen_range(0, total);
+            let symbol = counts.iter().position(|&count| {
+                let hit = index < count as u64;
+                index = index.saturating_sub(count as u64);
+                hit
+            }).unwrap_or(n - 1);
+            out.push(self.alphabet[symbol]);
+            row = (row * n + symbol) % rows;
+        }
+        out.truncate(len);
+        // everything we added is ASCII, so this only fails if cutting the seed short split a character
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    fn symbol(&self, c: u8) -> Option<usize> {
+        match self.lookup.get(c as usize) {
+            Some(&i) if i != NONE => Some(i as usize),
+            _ => None
+        }
+    }
+
+    // the row for a context of exactly order characters
+    fn row(&self, context: &[u8]) -> Option<usize> {
+        if context.len() != self.order {
+            return None;
+        }
+        context.iter().try_fold(0, |row, &c| Some(row * self.alphabet.len() + self.symbol(c)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alphabet_chain() {
+        let mut chain = AlphabetChain::new("ACGT", 2).unwrap();
+        chain.train("ACGTNACGA").unwrap();
+        assert_eq!(chain.successors("AC"), Some(vec![('A', 0), ('C', 0), ('G', 2), ('T', 0)]));
+        assert_eq!(chain.successors("CG"), Some(vec![('A', 1), ('C', 0), ('G', 0), ('T', 1)]));
+        // N broke the sequence, so T never led to A
+        assert_eq!(chain.successors("TN"), None);
+        assert_eq!(chain.successors("GT"), Some(vec![('A', 0), ('C', 0), ('G', 0), ('T', 0)]));
+
+        assert_eq!(chain.generate("AC", 3), Ok("ACG".to_string()));
+        assert_eq!(chain.generate("TT", 3), Err(MarkovErr::NotSeen{w: "TT".to_string()}));
+        assert_eq!(chain.train("AC"), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_bad_alphabets() {
+        assert!(AlphabetChain::new("", 1).is_none());
+        assert!(AlphabetChain::new("AAC", 1).is_none());
+        assert!(AlphabetChain::new("ACGTé", 1).is_none());
+        assert!(AlphabetChain::new("ACGT", 40).is_none());
+    }
+}
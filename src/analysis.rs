@@ -0,0 +1,535 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rand::Rng;
+
+use {Chain, MarkovErr};
+
+impl Chain {
+    /*
+    returns the words we've seen something lead to but have never seen lead anywhere. a walk that
+    reaches one of these has nowhere left to go.
+    */
+    pub fn dead_ends(&self) -> Vec<String> {
+        let mut out: Vec<String> = self.edges.keys()
+            .map(|key| &key.1)
+            .filter(|w| !self.nodes.contains_key(*w))
+            .collect::<HashSet<&String>>()
+            .into_iter()
+            .cloned()
+            .collect();
+        out.sort();
+        out
+    }
+
+    /*
+    returns the cycles of at most max_len words where every word has exactly one possible successor.
+    once a walk enters one of these it repeats the same words forever. each loop starts at its
+    alphabetically-first word. a max_len of 0 means any length.
+    */
+    pub fn loops(&self, max_len: usize) -> Vec<Vec<String>> {
+        let adjacency = self.adjacency();
+        let only_successor = |w: &String| -> Option<&String> {
+            match adjacency.get(w) {
+                Some(next) if next.len() == 1 => Some(next[0]),
+                _ => None
+            }
+        };
+
+        let mut out = vec![];
+        for start in adjacency.keys() {
+            let mut cycle = vec![(*start).clone()];
+            let mut w = *start;
+            while let Some(next) = only_successor(w) {
+                if next == *start {
+                    out.push(cycle);
+                    break;
+                }
+                // only report each loop once, from its smallest word. a word we've already passed
+                // means we've fallen into a loop that start isn't part of
+                if next < *start || cycle.len() == max_len || cycle.contains(next) {
+                    break;
+                }
+                cycle.push(next.clone());
+                w = next;
+            }
+        }
+        out.sort();
+        out
+    }
+
+    /*
+    returns the strongly connected components of the transition graph: groups of words that can all
+    reach each other. a chain trained on one long text is usually a single component; several big
+    components mean a walk that starts in one island can never reach the others. biggest first.
+    */
+    pub fn components(&self) -> Vec<Vec<String>> {
+        let adjacency = self.adjacency();
+        let (words, ids) = self.numbered();
+        let graph: Vec<Vec<usize>> = words.iter()
+            .map(|w| adjacency.get(w).map(|next| next.iter().map(|n| ids[n]).collect()).unwrap_or_default())
+            .collect();
+
+        let mut out: Vec<Vec<String>> = tarjan(&graph).into_iter()
+            .map(|component| {
+                let mut component: Vec<String> = component.into_iter().map(|i| words[i].clone()).collect();
+                component.sort();
+                component
+            })
+            .collect();
+        out.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        out
+    }
+
+    /*
+    ranks every word by its PageRank: how often a surfer who follows edges with probability damping
+    (and jumps to a random word otherwise) ends up on it in the long run. the top of the list is a
+    fair summary of what the corpus is "about". most important first.
+    */
+    pub fn pagerank(&self, damping: f64, iters: usize) -> Vec<(String, f64)> {
+        let (words, ids) = self.numbered();
+        let n = words.len();
+        if n == 0 {
+            return vec![];
+        }
+
+        let mut rank = vec![1.0 / n as f64; n];
+        for _ in 0..iters {
+            let mut next = vec![(1.0 - damping) / n as f64; n];
+            for (key, weight) in &self.edges {
+                let total = self.nodes[&key.0];
+                next[ids[&key.1]] += damping * rank[ids[&key.0]] * *weight as f64 / total as f64;
+            }
+            // dead ends have nowhere to send their rank, so they share it with everyone
+            let dangling: f64 = words.iter()
+                .filter(|w| !self.nodes.contains_key(**w))
+                .map(|w| rank[ids[*w]])
+                .sum();
+            for r in next.iter_mut() {
+                *r += damping * dangling / n as f64;
+            }
+            rank = next;
+        }
+
+        let mut out: Vec<(String, f64)> = words.into_iter().cloned().zip(rank).collect();
+        out.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+
+    /*
+    the probability that a walk starting at a is on b after exactly k steps. we push the whole
+    probability distribution forward one step at a time rather than multiplying matrices, since
+    most words only lead to a handful of others.
+    */
+    pub fn probability_after(&self, a: &str, b: &str, k: usize) -> f64 {
+        let mut dist: HashMap<&str, f64> = HashMap::new();
+        dist.insert(a, 1.0);
+        for _ in 0..k {
+            let mut next: HashMap<&str, f64> = HashMap::new();
+            for (key, weight) in &self.edges {
+                if let Some(p) = dist.get(key.0.as_str()) {
+                    let total = self.nodes[&key.0] as f64;
+                    *next.entry(&key.1).or_insert(0.0) += p * *weight as f64 / total;
+                }
+            }
+            dist = next;
+        }
+        *dist.get(b).unwrap_or(&0.0)
+    }
+
+    /*
+    runs trials independent random walks of steps steps each from start, and counts how many times
+    each word was visited along the way (start included). walks that hit a dead end just stop. the
+    walks are drawn from rng, so a seeded one always gives the same counts.
+    */
+    pub fn simulate<R: Rng>(&self, start: &str, steps: usize, trials: usize, rng: &mut R) -> Result<HashMap<String, usize>, MarkovErr> {
+        if !self.contains(start) {
+            return Err(MarkovErr::NotSeen{w: start.to_string()});
+        }
+
+        let mut visits: HashMap<String, usize> = HashMap::new();
+        for _ in 0..trials {
+            let mut w = start.to_string();
+            *visits.entry(w.clone()).or_insert(0) += 1;
+            for _ in 0..steps {
+                w = match self.next_with(&w, rng) {
+                    Ok(next) => next,
+                    Err(_) => break
+                };
+                *visits.entry(w.clone()).or_insert(0) += 1;
+            }
+        }
+        Ok(visits)
+    }
+
+    /*
+    how different other's transitions are from ours: the Jensen-Shannon divergence between the
+    two chains' next-word distributions, averaged over every word both of them know how to
+    continue from. 0.0 means identical, 1.0 means they never agree on anything. None if the
+    chains don't share any words to compare.
+    */
+    pub fn divergence(&self, other: &Chain) -> Option<f64> {
+        let shared: Vec<&String> = self.nodes.keys().filter(|w| other.nodes.contains_key(*w)).collect();
+        if shared.is_empty() {
+            return None;
+        }
+
+        let total: f64 = shared.iter()
+            .map(|w| {
+                let p = self.distribution(w);
+                let q = other.distribution(w);
+                let mut js = 0.0;
+                for next in p.keys().chain(q.keys()).collect::<HashSet<&&String>>() {
+                    let pi = *p.get(*next).unwrap_or(&0.0);
+                    let qi = *q.get(*next).unwrap_or(&0.0);
+                    let m = (pi + qi) / 2.0;
+                    if pi > 0.0 {
+                        js += 0.5 * pi * (pi / m).log2();
+                    }
+                    if qi > 0.0 {
+                        js += 0.5 * qi * (qi / m).log2();
+                    }
+                }
+                js
+            })
+            .sum();
+        Some(total / shared.len() as f64)
+    }
+
+    /*
+    maps each word we've seen follow w to the probability that it does
+    */
+    fn distribution(&self, w: &str) -> HashMap<&String, f64> {
+        let total = *self.nodes.get(w).unwrap_or(&0) as f64;
+        self.successors(w).into_iter().map(|(next, weight)| (next, weight as f64 / total)).collect()
+    }
+
+    /*
+    whether a walk starting at a could ever produce b
+    */
+    pub fn can_reach(&self, a: &str, b: &str) -> bool {
+        self.shortest_path(a, b).is_some()
+    }
+
+    /*
+    returns the fewest words a walk needs to get from a to b, including both ends. None if b can't
+    be reached from a at all.
+    */
+    pub fn shortest_path(&self, a: &str, b: &str) -> Option<Vec<String>> {
+        if a == b && self.edges.keys().any(|key| key.0 == a || key.1 == a) {
+            return Some(vec![a.to_string()]);
+        }
+        let adjacency = self.adjacency();
+        let start = self.nodes.get_key_value(a).map(|(w, _)| w)?;
+
+        // breadth-first, remembering how we got to each word so we can walk the path back
+        let mut came_from: HashMap<&String, &String> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(w) = queue.pop_front() {
+            for next in adjacency.get(w).map(|n| n.as_slice()).unwrap_or(&[]) {
+                if *next == start || came_from.contains_key(next) {
+                    continue;
+                }
+                came_from.insert(next, w);
+                if *next == b {
+                    let mut path = vec![(*next).clone()];
+                    let mut cursor = *next;
+                    while let Some(prev) = came_from.get(cursor) {
+                        path.push((*prev).clone());
+                        cursor = prev;
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back(next);
+            }
+        }
+        None
+    }
+
+    /*
+    returns every word in the graph, sorted, along with the position of each one in that list
+    */
+    pub(crate) fn numbered(&self) -> (Vec<&String>, HashMap<&String, usize>) {
+        let mut words: Vec<&String> = self.edges.keys()
+            .flat_map(|key| vec![&key.0, &key.1])
+            .collect::<HashSet<&String>>()
+            .into_iter()
+            .collect();
+        words.sort();
+        let ids = words.iter().enumerate().map(|(i, w)| (*w, i)).collect();
+        (words, ids)
+    }
+
+    /*
+    maps each word to the distinct words we've seen follow it
+    */
+    pub(crate) fn adjacency(&self) -> HashMap<&String, Vec<&String>> {
+        let mut out: HashMap<&String, Vec<&String>> = HashMap::new();
+        for key in self.edges.keys() {
+            out.entry(&key.0).or_default().push(&key.1);
+        }
+        out
+    }
+}
+
+/*
+Tarjan's algorithm, unrolled into an explicit stack so a long chain of words can't blow the call
+stack. returns each component as a list of node indices.
+*/
+fn tarjan(graph: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = graph.len();
+    let mut index = vec![usize::MAX; n];
+    let mut lowlink = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut next_index = 0;
+    let mut out = vec![];
+
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        // each frame is a node and how many of its edges we've already followed
+        let mut frames = vec![(root, 0)];
+        while let Some(&mut (v, ref mut edge)) = frames.last_mut() {
+            if *edge == 0 && index[v] == usize::MAX {
+                index[v] = next_index;
+                lowlink[v] = next_index;
+                next_index += 1;
+                stack.push(v);
+                on_stack[v] = true;
+            }
+
+            if *edge < graph[v].len() {
+                let w = graph[v][*edge];
+                *edge += 1;
+                if index[w] == usize::MAX {
+                    frames.push((w, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[v]);
+            }
+            if lowlink[v] == index[v] {
+                let mut component = vec![];
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                out.push(component);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use TrainConfig;
+
+    fn wrapped() -> Chain {
+        Chain::with_config(TrainConfig { wrap_around: true, ..TrainConfig::default() })
+    }
+
+    #[test]
+    fn test_dead_ends() {
+        let mut chain = Chain::new();
+        chain.see("the", "end");
+        chain.see("the", "start");
+        chain.see("start", "the");
+        assert_eq!(chain.dead_ends(), vec!["end".to_string()]);
+    }
+
+    #[test]
+    fn test_no_dead_ends_when_wrapped() {
+        let mut chain = wrapped();
+        chain.train("round and round we go").unwrap();
+        assert_eq!(chain.dead_ends(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_text_ends_in_dead_end() {
+        let mut chain = Chain::new();
+        chain.train("round and round we go").unwrap();
+        assert_eq!(chain.dead_ends(), vec!["go".to_string()]);
+    }
+
+    #[test]
+    fn test_loops() {
+        let mut chain = Chain::new();
+        chain.see("very", "very");
+        chain.see("ping", "pong");
+        chain.see("pong", "ping");
+        chain.see("a", "b");
+        chain.see("b", "a");
+        chain.see("b", "c");
+        let expected = vec![
+            vec!["ping".to_string(), "pong".to_string()],
+            vec!["very".to_string()]
+        ];
+        assert_eq!(chain.loops(2), expected);
+    }
+
+    #[test]
+    fn test_components_single() {
+        let mut chain = wrapped();
+        chain.train("round and round we go").unwrap();
+        assert_eq!(chain.components().len(), 1);
+    }
+
+    #[test]
+    fn test_components_islands() {
+        let mut chain = wrapped();
+        chain.train("cats chase mice").unwrap();
+        chain.train("dogs bark").unwrap();
+        chain.see("mice", "dogs");
+        let expected = vec![
+            vec!["cats".to_string(), "chase".to_string(), "mice".to_string()],
+            vec!["bark".to_string(), "dogs".to_string()]
+        ];
+        assert_eq!(chain.components(), expected);
+    }
+
+    #[test]
+    fn test_components_dead_end() {
+        let mut chain = Chain::new();
+        chain.see("the", "end");
+        let expected = vec![vec!["end".to_string()], vec!["the".to_string()]];
+        assert_eq!(chain.components(), expected);
+    }
+
+    #[test]
+    fn test_pagerank() {
+        let mut chain = Chain::new();
+        chain.see("a", "hub");
+        chain.see("b", "hub");
+        chain.see("c", "hub");
+        chain.see("hub", "a");
+        let ranks = chain.pagerank(0.85, 50);
+        assert_eq!(ranks.len(), 4);
+        assert_eq!(ranks[0].0, "hub");
+        assert_eq!(ranks[1].0, "a");
+        let total: f64 = ranks.iter().map(|&(_, r)| r).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pagerank_empty() {
+        assert_eq!(Chain::new().pagerank(0.85, 10), vec![]);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        let expected = vec!["cat".to_string(), "sat".to_string(), "on".to_string(), "the".to_string(), "mat".to_string()];
+        assert_eq!(chain.shortest_path("cat", "mat"), Some(expected));
+        assert_eq!(chain.shortest_path("the", "mat"), Some(vec!["the".to_string(), "mat".to_string()]));
+        assert_eq!(chain.shortest_path("mat", "mat"), Some(vec!["mat".to_string()]));
+    }
+
+    #[test]
+    fn test_probability_after() {
+        let mut chain = Chain::new();
+        chain.see("a", "b");
+        chain.see("a", "c");
+        chain.see("b", "a");
+        chain.see("c", "a");
+        chain.see("c", "c");
+        assert_eq!(chain.probability_after("a", "a", 0), 1.0);
+        assert_eq!(chain.probability_after("a", "b", 1), 0.5);
+        assert!((chain.probability_after("a", "a", 2) - 0.75).abs() < 1e-9);
+        assert!((chain.probability_after("a", "c", 2) - 0.25).abs() < 1e-9);
+        assert_eq!(chain.probability_after("b", "b", 1), 0.0);
+        assert_eq!(chain.probability_after("x", "a", 1), 0.0);
+    }
+
+    #[test]
+    fn test_simulate() {
+        let mut chain = wrapped();
+        chain.train("tick tock").unwrap();
+        let visits = chain.simulate("tick", 3, 10, &mut thread_rng()).unwrap();
+        assert_eq!(visits.get("tick"), Some(&20));
+        assert_eq!(visits.get("tock"), Some(&20));
+    }
+
+    #[test]
+    fn test_simulate_dead_end() {
+        let mut chain = Chain::new();
+        chain.train("the end").unwrap();
+        let visits = chain.simulate("the", 100, 5, &mut thread_rng()).unwrap();
+        assert_eq!(visits.get("the"), Some(&5));
+        assert_eq!(visits.get("end"), Some(&5));
+        assert_eq!(chain.simulate("nope", 1, 1, &mut thread_rng()), Err(MarkovErr::NotSeen{w: "nope".to_string()}));
+    }
+
+    #[test]
+    fn test_simulate_seeded() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat and the dog sat on the cat").unwrap();
+        let visits = |seed| chain.simulate("the", 20, 50, &mut StdRng::seed_from_u64(seed)).unwrap();
+        assert_eq!(visits(7), visits(7));
+        assert_ne!(visits(7), visits(8));
+    }
+
+    #[test]
+    fn test_divergence() {
+        let mut a = Chain::new();
+        a.train("the cat sat").unwrap();
+        let mut b = Chain::new();
+        b.train("the cat sat").unwrap();
+        assert_eq!(a.divergence(&b), Some(0.0));
+
+        b.train("the dog ran").unwrap();
+        // "the" goes to cat or dog half and half in b, while "cat" agrees completely
+        let js = 0.5 * (1.0 / 0.75f64).log2() + 0.25 * (0.5 / 0.75f64).log2() + 0.25 * 2.0f64.log2();
+        let expected = js / 2.0;
+        assert!((a.divergence(&b).unwrap() - expected).abs() < 1e-9);
+
+        let mut c = Chain::new();
+        c.train("a dog ran").unwrap();
+        assert_eq!(a.divergence(&c), None);
+    }
+
+    #[test]
+    fn test_can_reach() {
+        let mut chain = Chain::new();
+        chain.see("the", "end");
+        assert!(chain.can_reach("the", "end"));
+        assert!(!chain.can_reach("end", "the"));
+        assert!(chain.can_reach("end", "end"));
+        assert!(!chain.can_reach("nowhere", "the"));
+    }
+
+    #[test]
+    fn test_loops_max_len() {
+        let mut chain = wrapped();
+        chain.train("one two three").unwrap();
+        assert_eq!(chain.loops(2), Vec::<Vec<String>>::new());
+        assert_eq!(chain.loops(3).len(), 1);
+    }
+
+    #[test]
+    fn test_loops_unbounded() {
+        let mut chain = Chain::new();
+        chain.see("a", "b");
+        chain.see("b", "c");
+        chain.see("c", "b");
+        let expected = vec![vec!["b".to_string(), "c".to_string()]];
+        assert_eq!(chain.loops(0), expected);
+        assert_eq!(chain.loops(5), expected);
+    }
+}
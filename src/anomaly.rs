@@ -0,0 +1,86 @@
+use {Chain, MarkovErr};
+
+impl Chain {
+    /*
+    how unlike the training text line is: the mean, per word, of minus the log probability of
+    walking through it (smoothed, so words and steps we've never seen are unlikely rather than
+    impossible). lines the chain has seen plenty like score low; garbled or novel ones score
+    high. 0 for a line with no words.
+    */
+    pub fn surprise(&self, line: &str) -> f64 {
+        self.surprise_with(line, self.vocab().len())
+    }
+
+    fn surprise_with(&self, line: &str, vocab: usize) -> f64 {
+        let words = self.tokenize(line);
+        if words.is_empty() {
+            return 0.0;
+        }
+        -self.smoothed_log_likelihood(&words, vocab) / words.len() as f64
+    }
+
+    /*
+    for a chain trained on normal logs, the lines whose surprise is above threshold, as (their
+    place in lines, surprise) pairs, most surprising first. see calibrate for picking a
+    threshold.
+    */
+    pub fn anomalies(&self, lines: impl IntoIterator<Item = impl AsRef<str>>, threshold: f64) -> Vec<(usize, f64)> {
+        let vocab = self.vocab().len();
+        let mut out: Vec<(usize, f64)> = lines.into_iter()
+            .map(|line| self.surprise_with(line.as_ref(), vocab))
+            .enumerate()
+            .filter(|&(_, surprise)| surprise > threshold)
+            .collect();
+        out.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+
+    /*
+    a threshold for anomalies that quantile (between 0 and 1) of normal lines fall at or under,
+    e.g. 0.99 to flag what's stranger than all but one in a hundred normal lines. best with lines
+    held out of training, since the ones we trained on look more normal than new ones will.
+    EmptyCorpus if none of the lines have words.
+    */
+    pub fn calibrate(&self, normal: impl IntoIterator<Item = impl AsRef<str>>, quantile: f64) -> Result<f64, MarkovErr> {
+        let vocab = self.vocab().len();
+        let mut surprises: Vec<f64> = normal.into_iter()
+            .filter(|line| !self.tokenize(line.as_ref()).is_empty())
+            .map(|line| self.surprise_with(line.as_ref(), vocab))
+            .collect();
+        if surprises.is_empty() {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+        surprises.sort_by(|a, b| a.total_cmp(b));
+        let i = (quantile.clamp(0.0, 1.0) * (surprises.len() - 1) as f64).round() as usize;
+        Ok(surprises[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NORMAL: &[&str] = &[
+        "connection accepted from client",
+        "request served from cache",
+        "connection closed by client",
+        "request served from disk",
+        "connection accepted from proxy",
+        "connection closed by proxy"
+    ];
+
+    #[test]
+    fn test_anomalies() {
+        let mut chain = Chain::new();
+        chain.train_sequences(NORMAL).unwrap();
+
+        let threshold = chain.calibrate(NORMAL, 1.0).unwrap();
+        let lines = ["connection accepted from client", "kernel panic not syncing", "request served from proxy"];
+        let anomalies = chain.anomalies(lines, threshold);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].0, 1);
+        assert!(chain.surprise(lines[2]) > chain.surprise(lines[0]));
+        assert_eq!(chain.surprise("!!"), 0.0);
+        assert_eq!(chain.calibrate(["", "42"], 0.5), Err(MarkovErr::EmptyCorpus));
+    }
+}
@@ -0,0 +1,136 @@
+use serde_json::Value;
+
+use {Chain, MarkovErr};
+
+/*
+what to keep of each post when reading a social media archive
+*/
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    // drop links, which are never the same twice and just make noise
+    pub strip_urls: bool,
+    // drop @mentions, so the output doesn't ping people
+    pub strip_mentions: bool,
+    // drop retweets and boosts, which someone else wrote
+    pub skip_reposts: bool
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> ArchiveConfig {
+        ArchiveConfig {
+            strip_urls: true,
+            strip_mentions: true,
+            skip_reposts: true
+        }
+    }
+}
+
+impl Chain {
+    /*
+    trains on every post in a Twitter archive's tweets.js or a Mastodon archive's outbox.json,
+    each post its own text. returns how many posts that was.
+    */
+    pub fn train_archive(&mut self, json: &str, config: &ArchiveConfig) -> Result<usize, MarkovErr> {
+        let posts = archive_posts(json, config)?;
+        self.train_sequences(&posts)?;
+        Ok(posts.len())
+    }
+}
+
+/*
+the text of every post in an archive (see Chain::train_archive), cleaned up according to config.
+posts with nothing left in them are left out. anything that isn't one of the two formats is Corrupt.
+*/
+pub fn archive_posts(json: &str, config: &ArchiveConfig) -> Result<Vec<String>, MarkovErr> {
+    // tweets.js is really a script: "window.YTD.tweets.part0 = [...]"
+    let start = json.find(['[', '{']).ok_or(MarkovErr::Corrupt)?;
+    let archive: Value = serde_json::from_str(&json[start..]).map_err(|_| MarkovErr::Corrupt)?;
+
+    let posts: Vec<String> = match archive {
+        Value::Array(tweets) => tweets.iter()
+            .map(|item| item.get("tweet").unwrap_or(item))
+            .filter_map(|tweet| tweet.get("full_text").or_else(|| tweet.get("text")).and_then(Value::as_str))
+            .filter(|text| !(config.skip_reposts && text.starts_with("RT @")))
+            .map(unescape)
+            .collect(),
+        Value::Object(ref outbox) => outbox.get("orderedItems").and_then(Value::as_array).ok_or(MarkovErr::Corrupt)?
+            .iter()
+            .filter(|activity| !(config.skip_reposts && activity.get("type").and_then(Value::as_str) == Some("Announce")))
+            .filter_map(|activity| activity.get("object")?.get("content")?.as_str())
+            .map(strip_html)
+            .collect(),
+        _ => return Err(MarkovErr::Corrupt)
+    };
+
+    Ok(posts.iter()
+        .map(|post| {
+            post.split_whitespace()
+                .filter(|w| !(config.strip_urls && (w.starts_with("http://") || w.starts_with("https://"))))
+                .filter(|w| !(config.strip_mentions && w.starts_with('@')))
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+        .filter(|post| !post.is_empty())
+        .collect())
+}
+
+/*
+the text of a Mastodon post, which is HTML. paragraphs and line breaks become spaces.
+*/
+fn strip_html(html: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                out.push(' ');
+            },
+            '>' => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    // a mention is <a>@<span>bob</span></a>, which would come out "@ bob"
+    unescape(&out.replace("@ ", "@"))
+}
+
+// the HTML entities posts actually use
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&#39;", "'").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_twitter_archive() {
+        let json = r#"window.YTD.tweets.part0 = [
+            {"tweet": {"full_text": "hello there @bob https://t.co/abc"}},
+            {"tweet": {"full_text": "RT @alice: not mine"}},
+            {"tweet": {"full_text": "fish &amp; chips"}}
+        ]"#;
+        let posts = archive_posts(json, &ArchiveConfig::default()).unwrap();
+        assert_eq!(posts, vec!["hello there".to_string(), "fish & chips".to_string()]);
+
+        let config = ArchiveConfig { strip_urls: false, strip_mentions: false, skip_reposts: false };
+        assert_eq!(archive_posts(json, &config).unwrap()[0], "hello there @bob https://t.co/abc");
+        assert_eq!(archive_posts(json, &config).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_mastodon_archive() {
+        let json = r#"{"orderedItems": [
+            {"type": "Create", "object": {"content": "<p>hi <span class=\"h-card\"><a href=\"x\">@<span>bob</span></a></span> how&#39;s it going</p>"}},
+            {"type": "Announce", "object": "https://example.com/1"}
+        ]}"#;
+        let posts = archive_posts(json, &ArchiveConfig::default()).unwrap();
+        assert_eq!(posts, vec!["hi how's it going".to_string()]);
+
+        let mut chain = Chain::new();
+        assert_eq!(chain.train_archive(json, &ArchiveConfig::default()), Ok(1));
+        assert!(chain.has_transition("hi", "hows"));
+        assert_eq!(archive_posts("not json", &ArchiveConfig::default()), Err(MarkovErr::Corrupt));
+    }
+}
@@ -0,0 +1,266 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use rand::thread_rng;
+
+use {novelty, rng, Chain, GenConfig, MarkovErr};
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    // how to generate each candidate
+    pub gen: GenConfig,
+    // throw away candidates we've already generated in this batch
+    pub dedup: bool,
+    // throw away candidates that appear word for word in the training texts. needs a chain
+    // trained with index_corpus.
+    pub novel: bool,
+    // give up after generating this many candidates, even if we haven't kept enough of them
+    pub attempts: usize
+}
+
+impl Default for BatchConfig {
+    fn default() -> BatchConfig {
+        BatchConfig {
+            gen: GenConfig::default(),
+            dedup: false,
+            novel: false,
+            attempts: 100
+        }
+    }
+}
+
+/*
+which candidate best_of keeps, judged by the chain's own average log-likelihood per step
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    // the one the model finds most plausible. tends to be safe and a bit boring.
+    Likeliest,
+    // the one in the middle of the pack, which is usually more interesting without being word salad
+    Median
+}
+
+/*
+one output of generate_batch, and what we know about it
+*/
+#[derive(Debug, PartialEq)]
+pub struct GenResult {
+    // what generate would have returned for the same config. one config failing doesn't stop the
+    // rest of the batch.
+    pub words: Result<Vec<String>, MarkovErr>,
+    // the chain's average log-likelihood per step of the output, as best_of judges it. negative
+    // infinity if it took a step training never did (a teleport, say), 0.0 without an output.
+    pub likelihood: f64,
+    // the most words in a row it copied from one training text (see novelty). None if the chain
+    // wasn't trained with index_corpus, or there's no output.
+    pub copied: Option<usize>,
+    // how long generating it took
+    pub elapsed: Duration
+}
+
+impl Chain {
+    /*
+    generates one output for each config, in order, for a server answering many requests at once.
+    the whole batch shares each word's successor list, so a list is only built once however many
+    walks pass through it, even with the successor cache off. configs without an rng_seed share
+    one random generator rather than setting up their own, and ones with a seed get the same
+    output they would from generate.
+    */
+    pub fn generate_batch(&self, configs: &[GenConfig]) -> Vec<GenResult> {
+        let _held = self.hold_cache();
+        let mut shared = thread_rng();
+        configs.iter().map(|config| {
+            let started = Instant::now();
+            let prompt = config.seed.as_ref().map(|seed| self.tokenize(seed));
+            let keys = match config.rng_seed {
+                Some(_) => self.generate_from(prompt, config, &mut rng(config)),
+                None => self.generate_from(prompt, config, &mut shared)
+            };
+            let elapsed = started.elapsed();
+            match keys {
+                Ok(keys) => GenResult {
+                    likelihood: self.average_log_likelihood(&keys),
+                    copied: self.corpus.as_ref().map(|corpus| novelty(&keys, corpus).longest),
+                    words: Ok(self.surface(keys)),
+                    elapsed
+                },
+                Err(e) => GenResult { words: Err(e), likelihood: 0.0, copied: None, elapsed }
+            }
+        }).collect()
+    }
+
+    /*
+    generates up to n outputs that pass the batch config's filters, so a bot can pick amongst
+    candidates. can return fewer than n if we run out of attempts.
+    */
+    pub fn generate_many(&self, n: usize, config: &BatchConfig) -> Result<Vec<Vec<String>>, MarkovErr> {
+        Ok(self.candidates(n, config)?.into_iter().map(|words| self.surface(words)).collect())
+    }
+
+    /*
+    generate_many, but leaving the output as keys so it can still be checked against the chain
+    */
+    fn candidates(&self, n: usize, config: &BatchConfig) -> Result<Vec<Vec<String>>, MarkovErr> {
+        let corpus = match self.corpus {
+            Some(ref corpus) => Some(corpus),
+            None if config.novel => return Err(MarkovErr::NotIndexed),
+            None => None
+        };
+
+        let mut out = vec![];
+        let mut seen = HashSet::new();
+        // one generator for the whole batch, or a seeded one would give the same candidate every time
+        let mut rng = rng(&config.gen);
+        let started = Instant::now();
+        for _ in 0..config.attempts {
+            if out.len() == n || config.gen.timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                break;
+            }
+            let prompt = config.gen.seed.as_ref().map(|seed| self.tokenize(seed));
+            let candidate = self.generate_from(prompt, &config.gen, &mut rng)?;
+            if config.novel && corpus.is_some_and(|corpus| corpus.contains(&candidate)) {
+                continue;
+            }
+            if config.dedup && !seen.insert(candidate.clone()) {
+                continue;
+            }
+            out.push(candidate);
+        }
+        Ok(out)
+    }
+
+    /*
+    generates up to n candidates and keeps the best one according to selection. None if the
+    batch config's filters rejected everything.
+    */
+    pub fn best_of(&self, n: usize, config: &BatchConfig, selection: Selection) -> Result<Option<Vec<String>>, MarkovErr> {
+        let mut scored: Vec<(f64, Vec<String>)> = self.candidates(n, config)?.into_iter()
+            .map(|candidate| (self.average_log_likelihood(&candidate), candidate))
+            .collect();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+        let i = match selection {
+            Selection::Likeliest => 0,
+            Selection::Median => scored.len() / 2
+        };
+        Ok(if i < scored.len() { Some(self.surface(scored.swap_remove(i).1)) } else { None })
+    }
+
+    /*
+    log-likelihood per step, so longer candidates aren't punished just for being longer
+    */
+    fn average_log_likelihood(&self, words: &[String]) -> f64 {
+        if words.len() < 2 {
+            return 0.0;
+        }
+        self.log_likelihood(words) / (words.len() - 1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Stop, TrainConfig};
+
+    #[test]
+    fn test_generate_many() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        let config = BatchConfig {
+            gen: GenConfig { seed: Some("hello".to_string()), ..GenConfig::default() },
+            ..BatchConfig::default()
+        };
+        let out = chain.generate_many(3, &config).unwrap();
+        assert_eq!(out, vec![vec!["hello".to_string(), "bob".to_string()]; 3]);
+    }
+
+    #[test]
+    fn test_generate_many_dedup() {
+        let mut chain = Chain::new();
+        chain.train("a b a c").unwrap();
+        let config = BatchConfig {
+            gen: GenConfig { seed: Some("a".to_string()), stop: Stop::Words(2), ..GenConfig::default() },
+            dedup: true,
+            attempts: 1000,
+            ..BatchConfig::default()
+        };
+        let mut out = chain.generate_many(3, &config).unwrap();
+        out.sort();
+        let expected = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["a".to_string(), "c".to_string()]
+        ];
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_generate_many_novel() {
+        let mut chain = Chain::with_config(TrainConfig { index_corpus: true, ..TrainConfig::default() });
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("a dog sat still").unwrap();
+        let config = BatchConfig {
+            gen: GenConfig { seed: Some("the".to_string()), stop: Stop::Words(4), ..GenConfig::default() },
+            novel: true,
+            attempts: 1000,
+            ..BatchConfig::default()
+        };
+        let out = chain.generate_many(1, &config).unwrap();
+        let expected: Vec<String> = ["the", "cat", "sat", "still"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(out, vec![expected]);
+    }
+
+    #[test]
+    fn test_best_of() {
+        let mut chain = Chain::new();
+        chain.train("a b a b a b a c").unwrap();
+        let config = BatchConfig {
+            gen: GenConfig { seed: Some("a".to_string()), stop: Stop::Words(2), ..GenConfig::default() },
+            dedup: true,
+            attempts: 1000,
+            ..BatchConfig::default()
+        };
+        let best = chain.best_of(2, &config, Selection::Likeliest).unwrap();
+        assert_eq!(best, Some(vec!["a".to_string(), "b".to_string()]));
+        let median = chain.best_of(2, &config, Selection::Median).unwrap();
+        assert_eq!(median, Some(vec!["a".to_string(), "c".to_string()]));
+    }
+
+    #[test]
+    fn test_best_of_nothing() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        assert_eq!(chain.best_of(0, &BatchConfig::default(), Selection::Likeliest), Ok(None));
+    }
+
+    #[test]
+    fn test_generate_many_novel_needs_index() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        let config = BatchConfig { novel: true, ..BatchConfig::default() };
+        assert_eq!(chain.generate_many(1, &config), Err(MarkovErr::NotIndexed));
+    }
+
+    #[test]
+    fn test_generate_batch() {
+        let mut chain = Chain::with_config(TrainConfig { index_corpus: true, ..TrainConfig::default() });
+        chain.train("the cat sat on the mat").unwrap();
+        let configs = [
+            GenConfig { seed: Some("cat".to_string()), stop: Stop::Words(3), ..GenConfig::default() },
+            GenConfig { seed: Some("bird".to_string()), ..GenConfig::default() },
+            GenConfig { stop: Stop::Words(4), rng_seed: Some(3), ..GenConfig::default() }
+        ];
+        let results = chain.generate_batch(&configs);
+        assert_eq!(results.len(), 3);
+
+        let words: Vec<String> = ["cat", "sat", "on"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(results[0].words, Ok(words));
+        assert_eq!(results[0].likelihood, 0.0);
+        assert_eq!(results[0].copied, Some(3));
+
+        assert_eq!(results[1].words, Err(MarkovErr::NotSeen{w: "bird".to_string()}));
+        assert_eq!(results[1].copied, None);
+
+        assert_eq!(results[2].words, chain.generate(&configs[2]));
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use rand::{thread_rng, Rng};
+
+use MarkovErr;
+
+/*
+a chain over raw bytes rather than words, for binary formats and text that isn't UTF-8, which
+Chain can't take. it's the byte counterpart of Chain: each next byte depends on the order bytes
+before it, so an order of 3 or 4 picks up a lot more of a file format's structure than 1 does.
+*/
+#[derive(Debug, Clone)]
+pub struct ByteChain {
+    order: usize,
+    // how many times each byte followed each context of order bytes
+    edges: BTreeMap<Vec<u8>, BTreeMap<u8, u64>>
+}
+
+impl ByteChain {
+    /*
+    a chain where each byte depends on the order (at least one) bytes before it
+    */
+    pub fn new(order: usize) -> ByteChain {
+        ByteChain { order: order.max(1), edges: BTreeMap::new() }
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /*
+    counts every run of order + 1 bytes in input. EmptyCorpus if it's too short to have any.
+    */
+    pub fn train_bytes(&mut self, input: &[u8]) -> Result<(), MarkovErr> {
+        if input.len() <= self.order {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+        for window in input.windows(self.order + 1) {
+            let (context, next) = window.split_at(self.order);
+            let count = self.edges.entry(context.to_vec()).or_default().entry(next[0]).or_insert(0);
+            *count = count.saturating_add(1);
+        }
+        Ok(())
+    }
+
+    /*
+    every byte we've seen follow context, with how many times we saw it. empty if context isn't
+    order bytes long or we've never seen it.
+    */
+    pub fn successors(&self, context: &[u8]) -> Vec<(u8, u64)> {
+        self.edges.get(context).map_or(vec![], |next| next.iter().map(|(&b, &count)| (b, count)).collect())
+    }
+
+    /*
+    carries on from seed until the output is len bytes long (seed included), stopping early at a
+    dead end. the seed needs at least order bytes, and we have to have seen its last order bytes.
+    */
+    pub fn generate(&self, seed: &[u8], len: usize) -> Result<Vec<u8>, MarkovErr> {
+        if seed.len() < self.order || !self.edges.contains_key(&seed[seed.len() - self.order..]) {
+            return Err(MarkovErr::NotSeen{w: String::from_utf8_lossy(seed).into_owned()});
+        }
+        let mut rng = thread_rng();
+        let mut out = seed.to_vec();
+        while out.len() < len {
+            let next = match self.edges.get(&out[out.len() - self.order..]) {
+                Some(next) => next,
+                None => break
+            };
+            let total: u64 = next.values().sum();
+            let mut index = rng.gen_range(0, total);
+            for (&b, &count) in next {
+                if index < count {
+                    out.push(b);
+                    break;
+                }
+                index -= count;
+            }
+        }
+        out.truncate(len);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_chain() {
+        let mut chain = ByteChain::new(2);
+        chain.train_bytes(&[0xff, 0x00, 0xfe, 0xff, 0x00, 0xfd]).unwrap();
+        assert_eq!(chain.successors(&[0xff, 0x00]), vec![(0xfd, 1), (0xfe, 1)]);
+        assert_eq!(chain.successors(&[0x00]), vec![]);
+        assert_eq!(chain.generate(&[0x00, 0xfe], 4), Ok(vec![0x00, 0xfe, 0xff, 0x00]));
+        assert_eq!(chain.generate(&[0x00, 0xfd], 4), Err(MarkovErr::NotSeen{w: "\0\u{fffd}".to_string()}));
+        assert_eq!(chain.train_bytes(&[1, 2]), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(ByteChain::new(0).order(), 1);
+    }
+}
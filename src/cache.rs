@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use Chain;
+
+type Table = Arc<Vec<(String, f32)>>;
+
+/*
+the successor lists of the words generation visited most recently. walks keep coming back to the
+same few common words, and those have the longest lists, so remembering them saves most of the
+work of building lists without precomputing every word's.
+*/
+#[derive(Debug, Default)]
+pub(crate) struct SuccessorCache {
+    tables: HashMap<String, (Table, u64)>,
+    // which word was used at each tick, oldest first, so we know what to throw out
+    recency: BTreeMap<u64, String>,
+    tick: u64
+}
+
+impl SuccessorCache {
+    fn get(&mut self, w: &str) -> Option<Table> {
+        let tick = self.tick + 1;
+        let entry = self.tables.get_mut(w)?;
+        self.recency.remove(&entry.1);
+        self.recency.insert(tick, w.to_string());
+        entry.1 = tick;
+        self.tick = tick;
+        Some(entry.0.clone())
+    }
+
+    fn insert(&mut self, w: &str, table: Table, capacity: usize) {
+        // another thread may have got here first while we were building ours
+        if self.tables.contains_key(w) {
+            return;
+        }
+        self.shrink(capacity.saturating_sub(1));
+        self.tick += 1;
+        self.recency.insert(self.tick, w.to_string());
+        self.tables.insert(w.to_string(), (table, self.tick));
+    }
+
+    // throws out the least recently used lists until there are at most capacity left
+    fn shrink(&mut self, capacity: usize) {
+        while self.tables.len() > capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(&tick) => tick,
+                None => break
+            };
+            if let Some(evicted) = self.recency.remove(&oldest) {
+                self.tables.remove(&evicted);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        if !self.tables.is_empty() {
+            self.tables.clear();
+            self.recency.clear();
+        }
+    }
+}
+
+impl Chain {
+    /*
+    whether generation should go through the cache: if the config turns it on, or a batch is
+    holding on to it
+    */
+    pub(crate) fn caching(&self) -> bool {
+        self.config.successor_cache > 0 || self.batches.load(Ordering::SeqCst) > 0
+    }
+
+    /*
+    the same as successors, but through the cache. only worth it when caching.
+    */
+    pub(crate) fn cached_successors(&self, w: &str) -> Table {
+        if let Some(table) = lock(&self.cache).get(w) {
+            return table;
+        }
+        // build it without holding the lock, so other threads can keep hitting the cache meanwhile
+        let table: Table = Arc::new(self.successors(w).into_iter().map(|(next, weight)| (next.clone(), weight)).collect());
+        let capacity = match self.batches.load(Ordering::SeqCst) {
+            0 => self.config.successor_cache,
+            _ => usize::MAX
+        };
+        lock(&self.cache).insert(w, table.clone(), capacity);
+        table
+    }
+
+    /*
+    keeps every successor list generation builds, however small the configured cache is, until
+    the returned guard is dropped. a batch of generations then builds each word's list once
+    between them rather than once per walk.
+    */
+    pub(crate) fn hold_cache(&self) -> Held<'_> {
+        self.batches.fetch_add(1, Ordering::SeqCst);
+        Held { chain: self }
+    }
+
+    pub(crate) fn clear_cache(&mut self) {
+        self.cache.get_mut().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+pub(crate) struct Held<'a> {
+    chain: &'a Chain
+}
+
+impl<'a> Drop for Held<'a> {
+    fn drop(&mut self) {
+        // the last batch out cuts the cache back down to the size the config asked for
+        if self.chain.batches.fetch_sub(1, Ordering::SeqCst) == 1 {
+            lock(&self.chain.cache).shrink(self.chain.config.successor_cache);
+        }
+    }
+}
+
+// the cache is only ever a copy of what's in the chain, so whatever state a panicking thread
+// left it in is still usable
+fn lock(cache: &Mutex<SuccessorCache>) -> MutexGuard<'_, SuccessorCache> {
+    cache.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {GenConfig, Stop, TrainConfig};
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = SuccessorCache::default();
+        let table: Table = Arc::new(vec![]);
+        cache.insert("a", table.clone(), 2);
+        cache.insert("b", table.clone(), 2);
+        assert!(cache.get("a").is_some());
+        cache.insert("c", table.clone(), 2);
+        assert_eq!(cache.tables.len(), 2);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_cached_generation() {
+        let mut chain = Chain::with_config(TrainConfig { successor_cache: 2, ..TrainConfig::default() });
+        chain.train("the cat sat on the mat").unwrap();
+        let config = GenConfig { seed: Some("sat".to_string()), stop: Stop::Words(3), ..GenConfig::default() };
+        let expected: Vec<String> = ["sat", "on", "the"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+        assert_eq!(lock(&chain.cache).tables.len(), 2);
+
+        // training again throws the cache out, so we don't walk stale lists
+        chain.train("sat down").unwrap();
+        assert_eq!(lock(&chain.cache).tables.len(), 0);
+        assert_eq!(chain.cached_successors("sat").len(), 2);
+    }
+
+    #[test]
+    fn test_held_cache() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        assert!(!chain.caching());
+        {
+            let _held = chain.hold_cache();
+            assert!(chain.caching());
+            chain.cached_successors("the");
+            chain.cached_successors("cat");
+            chain.cached_successors("sat");
+            assert_eq!(lock(&chain.cache).tables.len(), 3);
+        }
+        // let go of, so back down to nothing
+        assert!(!chain.caching());
+        assert_eq!(lock(&chain.cache).tables.len(), 0);
+    }
+}
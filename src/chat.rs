@@ -0,0 +1,146 @@
+use {Chain, MarkovErr};
+
+/*
+which messages to keep when reading a chat log
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ChatConfig {
+    // only keep what this nick said (ignoring case, and any #1234 on the end of a Discord name),
+    // for a bot that talks like one person. None keeps everyone.
+    pub user: Option<String>
+}
+
+impl Chain {
+    /*
+    trains on a chat log, each message its own text. returns how many messages that was (see
+    chat_messages for the formats we understand).
+    */
+    pub fn train_chat_log(&mut self, log: &str, config: &ChatConfig) -> Result<usize, MarkovErr> {
+        let messages = chat_messages(log, config);
+        self.train_sequences(&messages)?;
+        Ok(messages.len())
+    }
+}
+
+/*
+the messages in a chat log, with the timestamps and nicks taken off. we understand the usual IRC
+client logs ("[12:34] <nick> hi", "2024-01-02 12:34:56\tnick\thi") and DiscordChatExporter's
+plain text ("[02-Jan-24 12:34 PM] nick" followed by the message's lines). joins, parts, actions
+and anything else that isn't somebody talking are left out.
+*/
+pub fn chat_messages(log: &str, config: &ChatConfig) -> Vec<String> {
+    let mut messages: Vec<(String, String)> = vec![];
+    // the Discord message we're in the middle of, if any
+    let mut current: Option<(String, String)> = None;
+    for line in log.lines() {
+        if line.trim().is_empty() {
+            messages.extend(current.take());
+            continue;
+        }
+        if let Some(message) = irc_message(line) {
+            messages.extend(current.take());
+            messages.push(message);
+        } else if let Some(nick) = discord_header(line) {
+            messages.extend(current.take());
+            current = Some((nick.to_string(), String::new()));
+        } else if let Some((_, ref mut text)) = current {
+            text.push(' ');
+            text.push_str(line.trim());
+        }
+    }
+    messages.extend(current);
+
+    messages.into_iter()
+        .filter(|(nick, _)| config.user.as_ref().is_none_or(|user| same_user(nick, user)))
+        .map(|(_, text)| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+// who said what on an IRC log line, if it's someone saying something
+fn irc_message(line: &str) -> Option<(String, String)> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() == 3 && skip_timestamp(fields[0]).is_empty() {
+        // weechat puts its own notices where the nick would be
+        let nick = fields[1].trim_start_matches(['@', '+', '%', '~', '&']);
+        let notice = nick.is_empty() || nick.chars().all(|c| !c.is_alphanumeric());
+        return if notice { None } else { Some((nick.to_string(), fields[2].to_string())) };
+    }
+
+    let rest = skip_timestamp(line).strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let nick = rest[..end].trim().trim_start_matches(['@', '+', '%', '~', '&']);
+    Some((nick.to_string(), rest[end + 1..].trim().to_string()))
+}
+
+// the author of a DiscordChatExporter message, if this line starts one
+fn discord_header(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    if !rest[..end].chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let nick = rest[end + 1..].trim();
+    // "[12:34] * bob waves" and "[12:34] -!- bob has joined" are IRC, not a header
+    if nick.is_empty() || nick.starts_with(['<', '*', '-']) { None } else { Some(nick) }
+}
+
+// the line after any timestamp at the start of it, bracketed or not
+fn skip_timestamp(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix('[') {
+        return rest.find(']').map_or(line, |end| rest[end + 1..].trim_start());
+    }
+    let mut rest = line.trim_start();
+    while let Some(token) = rest.split_whitespace().next() {
+        let timestamp = token.chars().any(|c| c.is_ascii_digit())
+            && token.chars().all(|c| c.is_ascii_digit() || ":-/.T".contains(c));
+        if !timestamp {
+            break;
+        }
+        rest = rest[token.len()..].trim_start();
+    }
+    rest
+}
+
+fn same_user(nick: &str, user: &str) -> bool {
+    let name = |n: &str| n.split('#').next().unwrap_or(n).to_lowercase();
+    name(nick) == name(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_irc_log() {
+        let log = "[12:00] -!- bob has joined #rust\n\
+            [12:01] <@bob> hello there\n\
+            12:02 <+alice> hi bob\n\
+            [12:03] * bob waves\n\
+            2024-01-02 12:04:00\tAlice\thow are you\n\
+            2024-01-02 12:05:00\t-->\tcarol has joined\n";
+        let all = chat_messages(log, &ChatConfig::default());
+        assert_eq!(all, vec!["hello there".to_string(), "hi bob".to_string(), "how are you".to_string()]);
+
+        let alice = ChatConfig { user: Some("alice".to_string()) };
+        assert_eq!(chat_messages(log, &alice), vec!["hi bob".to_string(), "how are you".to_string()]);
+    }
+
+    #[test]
+    fn test_discord_log() {
+        let log = "[02-Jan-24 12:34 PM] Bob#1234\n\
+            first line\n\
+            second line\n\
+            \n\
+            [02-Jan-24 12:35 PM] Alice Smith\n\
+            hey\n";
+        let all = chat_messages(log, &ChatConfig::default());
+        assert_eq!(all, vec!["first line second line".to_string(), "hey".to_string()]);
+
+        let mut chain = Chain::new();
+        let bob = ChatConfig { user: Some("bob".to_string()) };
+        assert_eq!(chain.train_chat_log(log, &bob), Ok(1));
+        assert!(chain.has_transition("line", "second"));
+        assert!(!chain.contains("bob"));
+    }
+}
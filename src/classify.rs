@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, HashSet};
+
+use {Chain, MarkovErr, TrainConfig};
+
+/*
+sorts texts into classes (spam and ham, one author or another) by keeping a chain for each and
+asking which one a new text reads most like. the scores are smoothed (see
+Chain::smoothed_log_likelihood), so a text with words a class never used still gets compared rather
+than ruled out.
+*/
+pub struct Classifier {
+    config: TrainConfig,
+    classes: BTreeMap<String, Chain>,
+    // how many texts each class was trained on, for how likely the class is before we've read anything
+    texts: BTreeMap<String, usize>
+}
+
+impl Default for Classifier {
+    fn default() -> Classifier {
+        Classifier::new()
+    }
+}
+
+impl Classifier {
+    pub fn new() -> Classifier {
+        Classifier::with_config(TrainConfig::default())
+    }
+
+    /*
+    a classifier whose chains all split and count text the way config says
+    */
+    pub fn with_config(config: TrainConfig) -> Classifier {
+        Classifier { config, classes: BTreeMap::new(), texts: BTreeMap::new() }
+    }
+
+    /*
+    trains class's chain on one of its texts. EmptyCorpus if the text has no words.
+    */
+    pub fn train(&mut self, class: &str, text: &str) -> Result<(), MarkovErr> {
+        let config = &self.config;
+        let chain = self.classes.entry(class.to_string()).or_insert_with(|| Chain::with_config(config.clone()));
+        chain.train(text)?;
+        *self.texts.entry(class.to_string()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /*
+    the same as train, but for a text already split into tokens (see Chain::train_tokens)
+    */
+    pub fn train_tokens(&mut self, class: &str, tokens: impl IntoIterator<Item = impl AsRef<str>>) -> Result<(), MarkovErr> {
+        let config = &self.config;
+        let chain = self.classes.entry(class.to_string()).or_insert_with(|| Chain::with_config(config.clone()));
+        chain.train_tokens(tokens)?;
+        *self.texts.entry(class.to_string()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /*
+    the classes we've trained, sorted
+    */
+    pub fn classes(&self) -> Vec<String> {
+        self.texts.keys().cloned().collect()
+    }
+
+    /*
+    every class with the natural log of how likely it is to have produced text: the class's share
+    of the training texts times the chance of its chain walking text's words. best first.
+    */
+    pub fn scores(&self, text: &str) -> Vec<(String, f64)> {
+        self.ranked(|chain| chain.tokenize(text))
+    }
+
+    /*
+    scores, for a text already split into tokens
+    */
+    pub fn scores_tokens(&self, tokens: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<(String, f64)> {
+        let tokens: Vec<String> = tokens.into_iter().map(|t| t.as_ref().to_string()).collect();
+        self.ranked(|chain| chain.normalize(&tokens))
+    }
+
+    // scores every class on the words each one's chain makes of the text
+    fn ranked(&self, words: impl Fn(&Chain) -> Vec<String>) -> Vec<(String, f64)> {
+        let all: usize = self.texts.values().sum();
+        // every class smooths over the same vocabulary, or one with fewer words would be favoured
+        let vocab = self.classes.values()
+            .flat_map(|chain| chain.vocab())
+            .collect::<HashSet<String>>()
+            .len();
+
+        let mut out: Vec<(String, f64)> = self.classes.iter()
+            .filter(|&(class, _)| self.texts.contains_key(class))
+            .map(|(class, chain)| {
+                let prior = (self.texts[class] as f64 / all as f64).ln();
+                (class.clone(), prior + chain.smoothed_log_likelihood(&words(chain), vocab))
+            })
+            .collect();
+        out.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+
+    /*
+    the class text most likely came from. EmptyCorpus if we haven't trained any.
+    */
+    pub fn classify(&self, text: &str) -> Result<String, MarkovErr> {
+        self.scores(text).into_iter().next().map(|(class, _)| class).ok_or(MarkovErr::EmptyCorpus)
+    }
+
+    /*
+    classify, for a text already split into tokens
+    */
+    pub fn classify_tokens(&self, tokens: impl IntoIterator<Item = impl AsRef<str>>) -> Result<String, MarkovErr> {
+        self.scores_tokens(tokens).into_iter().next().map(|(class, _)| class).ok_or(MarkovErr::EmptyCorpus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let mut classifier = Classifier::new();
+        for text in &["win a free prize now", "claim your free prize today", "you win cash now"] {
+            classifier.train("spam", text).unwrap();
+        }
+        for text in &["are we still on for lunch today", "see you at lunch", "can you send me the notes"] {
+            classifier.train("ham", text).unwrap();
+        }
+        assert_eq!(classifier.classes(), vec!["ham".to_string(), "spam".to_string()]);
+
+        assert_eq!(classifier.classify("claim a free prize"), Ok("spam".to_string()));
+        assert_eq!(classifier.classify("lunch with you today"), Ok("ham".to_string()));
+        // nothing in common with either, but it still gets a score from both
+        let scores = classifier.scores("zebras quietly graze");
+        assert_eq!(scores.len(), 2);
+        assert!(scores.iter().all(|(_, score)| score.is_finite()));
+
+        assert_eq!(classifier.train("spam", "!!!"), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(Classifier::new().classify("hello"), Err(MarkovErr::EmptyCorpus));
+    }
+}
@@ -0,0 +1,376 @@
+/*
+a chain that can still learn, stored for size rather than convenience. every word is interned once
+and after that is just a u32, and each word's successors are a short list of (id, count) pairs
+kept inline until a word has more than a few of them. a Chain keeps both words of every pair as
+their own strings in a tree, which costs several times as much and scatters a word's successors
+all over memory, so sampling here touches far fewer cache lines.
+*/
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::mem::size_of;
+
+use rand::{thread_rng, Rng};
+use smallvec::SmallVec;
+
+use {Chain, MarkovErr};
+
+// most words are followed by only a handful of others, so rows this short never hit the heap
+type Row = SmallVec<[(u32, u32); 4]>;
+
+// how many bytes of words the arena grabs at a time
+const CHUNK: usize = 64 * 1024;
+
+/*
+hands out a u32 for every distinct word, and back. the words themselves are packed end to end
+into a few big chunks (a bump arena) rather than each having an allocation of its own, so a
+vocabulary of millions of words is a few dozen allocations, and freeing it is just as few. the
+lookup table only holds hashes and ids, not copies of the words.
+*/
+#[derive(Default)]
+struct Interner {
+    // every chunk is allocated with its capacity up front and never grows past it, so a word's
+    // bytes never move once they're in
+    chunks: Vec<String>,
+    // where word i is: its chunk, and its start and end in that chunk
+    spans: Vec<(u32, u32, u32)>,
+    // the ids of the words with each hash. almost always just the one.
+    ids: HashMap<u64, SmallVec<[u32; 1]>>,
+    hasher: RandomState
+}
+
+impl Interner {
+    fn intern(&mut self, w: &str) -> u32 {
+        if let Some(id) = self.get(w) {
+            return id;
+        }
+        let fits = self.chunks.last().is_some_and(|chunk| chunk.capacity() - chunk.len() >= w.len());
+        if !fits {
+            // a word longer than a chunk gets a chunk to itself
+            self.chunks.push(String::with_capacity(CHUNK.max(w.len())));
+        }
+        let chunk = self.chunks.len() - 1;
+        let start = self.chunks[chunk].len();
+        self.chunks[chunk].push_str(w);
+
+        let id = self.spans.len() as u32;
+        self.spans.push((chunk as u32, start as u32, (start + w.len()) as u32));
+        self.ids.entry(self.hash(w)).or_default().push(id);
+        id
+    }
+
+    fn get(&self, w: &str) -> Option<u32> {
+        self.ids.get(&self.hash(w))?.iter().cloned().find(|&id| self.resolve(id) == w)
+    }
+
+    fn resolve(&self, id: u32) -> &str {
+        let (chunk, start, end) = self.spans[id as usize];
+        &self.chunks[chunk as usize][start as usize..end as usize]
+    }
+
+    fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /*
+    forgets every word, but keeps the first chunk to refill
+    */
+    fn clear(&mut self) {
+        self.chunks.truncate(1);
+        if let Some(chunk) = self.chunks.first_mut() {
+            chunk.clear();
+        }
+        self.spans.clear();
+        self.ids.clear();
+    }
+
+    fn memory(&self) -> usize {
+        let chunks: usize = self.chunks.iter().map(|chunk| chunk.capacity() + size_of::<String>()).sum();
+        chunks + self.spans.len() * size_of::<(u32, u32, u32)>()
+            + self.ids.len() * (size_of::<(u64, SmallVec<[u32; 1]>)>() + 8)
+    }
+
+    fn hash(&self, w: &str) -> u64 {
+        self.hasher.hash_one(w)
+    }
+}
+
+#[derive(Default)]
+pub struct CompactChain {
+    words: Interner,
+    // word i's successors, in the order we first saw each follow it
+    rows: Vec<Row>,
+    // how often each word started a text
+    starts: Vec<u32>
+}
+
+impl Chain {
+    /*
+    copies the chain's counts into a CompactChain. as with freeze, only the transitions and start
+    counts come along, so its words are the keys we counted.
+    */
+    pub fn compact(&self) -> CompactChain {
+        let mut compact = CompactChain::new();
+        for ((a, b), &count) in &self.edges {
+            compact.see_weighted(a, b, count.max(0) as u32);
+        }
+        for (w, &count) in &self.starts {
+            let id = compact.id(w);
+            compact.starts[id as usize] += count.max(0) as u32;
+        }
+        compact
+    }
+}
+
+impl CompactChain {
+    pub fn new() -> CompactChain {
+        CompactChain::default()
+    }
+
+    /*
+    learns text the way a chain with the default config would (whitespace separated words,
+    lowercased, letters only), without a String for every word it reads: words that are
+    already clean are borrowed straight out of text, and the only ones copied are those we've
+    never seen before, into the interner. on a big corpus, where nearly every word is one we've
+    seen, that saves an allocation per word. EmptyCorpus if it has no words.
+    */
+    pub fn train(&mut self, text: &str) -> Result<(), MarkovErr> {
+        let mut words = text.split_whitespace().map(clean).filter(|w| !w.is_empty());
+        let first = match words.next() {
+            Some(first) => self.id(&first),
+            None => return Err(MarkovErr::EmptyCorpus)
+        };
+        self.starts[first as usize] += 1;
+        let mut prev = first;
+        for w in words {
+            let id = self.id(&w);
+            self.see_ids(prev, id, 1);
+            prev = id;
+        }
+        Ok(())
+    }
+
+    /*
+    marks b following a once
+    */
+    pub fn see(&mut self, a: &str, b: &str) {
+        self.see_weighted(a, b, 1);
+    }
+
+    /*
+    marks b following a count times at once
+    */
+    pub fn see_weighted(&mut self, a: &str, b: &str, count: u32) {
+        let (a, b) = (self.id(a), self.id(b));
+        self.see_ids(a, b, count);
+    }
+
+    fn see_ids(&mut self, a: u32, b: u32, count: u32) {
+        let row = &mut self.rows[a as usize];
+        match row.iter_mut().find(|(next, _)| *next == b) {
+            Some(edge) => edge.1 += count,
+            None => row.push((b, count))
+        }
+    }
+
+    /*
+    forgets everything it's learned, keeping a little of its memory to learn the next corpus into
+    */
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.rows.clear();
+        self.starts.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.len() == 0
+    }
+
+    pub fn contains(&self, w: &str) -> bool {
+        self.words.get(w).is_some()
+    }
+
+    /*
+    the words that have followed w, and how many times each did, in alphabetical order
+    */
+    pub fn successors(&self, w: &str) -> Vec<(&str, u32)> {
+        let mut out: Vec<(&str, u32)> = self.row(w).iter().map(|&(next, count)| (self.words.resolve(next), count)).collect();
+        out.sort();
+        out
+    }
+
+    /*
+    picks a word to follow w, weighted by how often each did. None at a dead end or for a word
+    we've never seen.
+    */
+    pub fn next<R: Rng>(&self, w: &str, rng: &mut R) -> Option<&str> {
+        let row = self.row(w);
+        let total: u64 = row.iter().map(|&(_, count)| count as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut cursor = rng.gen_range(0, total);
+        for &(next, count) in row {
+            if cursor < count as u64 {
+                return Some(self.words.resolve(next));
+            }
+            cursor -= count as u64;
+        }
+        None
+    }
+
+    /*
+    walks len words from seed (seed included), stopping early at a dead end, like gen does
+    */
+    pub fn generate(&self, seed: &str, len: usize) -> Result<Vec<String>, MarkovErr> {
+        if !self.contains(seed) {
+            return Err(MarkovErr::NotSeen{w: seed.to_string()});
+        }
+        let mut rng = thread_rng();
+        let mut out = vec![seed.to_string()];
+        while out.len() < len {
+            match self.next(&out[out.len() - 1], &mut rng) {
+                Some(next) => out.push(next.to_string()),
+                None => break
+            }
+        }
+        out.truncate(len);
+        Ok(out)
+    }
+
+    pub fn start_count(&self, w: &str) -> u32 {
+        self.words.get(w).map_or(0, |id| self.starts[id as usize])
+    }
+
+    /*
+    a rough count of the bytes behind the words and rows, to set against Stats::memory
+    */
+    pub fn memory(&self) -> usize {
+        let rows: usize = self.rows.iter()
+            .map(|row| size_of::<Row>() + if row.spilled() { row.capacity() * size_of::<(u32, u32)>() } else { 0 })
+            .sum();
+        size_of::<CompactChain>() + self.words.memory() + rows + self.starts.len() * size_of::<u32>()
+    }
+
+    // w's id, interning it (and giving it an empty row) if it's new
+    fn id(&mut self, w: &str) -> u32 {
+        let id = self.words.intern(w);
+        if id as usize == self.rows.len() {
+            self.rows.push(Row::new());
+            self.starts.push(0);
+        }
+        id
+    }
+
+    fn row(&self, w: &str) -> &[(u32, u32)] {
+        self.words.get(w).map_or(&[], |id| &self.rows[id as usize])
+    }
+}
+
+/*
+word as the default config keys it: lowercase ascii letters only. borrowed when it's that already.
+*/
+fn clean(word: &str) -> Cow<'_, str> {
+    if word.bytes().all(|b| b.is_ascii_lowercase()) {
+        Cow::Borrowed(word)
+    } else {
+        Cow::Owned(word.chars().filter(char::is_ascii_alphabetic).map(|c| c.to_ascii_lowercase()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn compact() -> CompactChain {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("the dog sat").unwrap();
+        chain.compact()
+    }
+
+    #[test]
+    fn test_compact() {
+        let chain = compact();
+        assert_eq!(chain.len(), 6);
+        assert!(chain.contains("mat"));
+        assert!(!chain.contains("bird"));
+        assert_eq!(chain.successors("the"), vec![("cat", 1), ("dog", 1), ("mat", 1)]);
+        assert_eq!(chain.successors("mat"), vec![]);
+        assert_eq!(chain.start_count("the"), 2);
+        assert_eq!(chain.next("on", &mut thread_rng()), Some("the"));
+
+        let out = chain.generate("sat", 3).unwrap();
+        assert_eq!(&out[..2], &["sat".to_string(), "on".to_string()]);
+        assert_eq!(chain.generate("bird", 5), Err(MarkovErr::NotSeen{w: "bird".to_string()}));
+    }
+
+    #[test]
+    fn test_compact_memory() {
+        // 20000 of 600 two letter words, in random order
+        let mut rng = StdRng::seed_from_u64(7);
+        let text: String = (0..20000)
+            .map(|_| rng.gen_range(0, 600))
+            .map(|n: u32| format!("{}{} ", (b'a' + (n % 26) as u8) as char, (b'a' + (n / 26) as u8) as char))
+            .collect();
+        let mut chain = Chain::new();
+        chain.train(&text).unwrap();
+        let compact = chain.compact();
+        assert_eq!(compact.successors("ha"), chain.freeze().successors("ha"));
+        assert!(compact.memory() * 2 < chain.stats(0).memory);
+
+        let mut built = CompactChain::new();
+        built.see("a", "b");
+        built.see_weighted("a", "b", 2);
+        assert_eq!(built.successors("a"), vec![("b", 3)]);
+        assert!(!built.is_empty());
+    }
+
+    #[test]
+    fn test_train() {
+        let text = "The cat sat on the mat. The dog, the cat and I sat";
+        let mut chain = Chain::new();
+        chain.train(text).unwrap();
+        let mut compact = CompactChain::new();
+        compact.train(text).unwrap();
+        for w in chain.vocab() {
+            assert_eq!(compact.successors(&w), chain.freeze().successors(&w));
+        }
+        assert_eq!(compact.start_count("the"), 1);
+        assert_eq!(compact.train("-- !!"), Err(MarkovErr::EmptyCorpus));
+        assert!(matches!(clean("cat"), Cow::Borrowed("cat")));
+        assert_eq!(clean("Dog,"), "dog");
+    }
+
+    #[test]
+    fn test_interner() {
+        let mut words = Interner::default();
+        let long = "x".repeat(CHUNK + 10);
+        let ids: Vec<u32> = ["cat", "dog", &long, "cat", "bird"].iter().map(|w| words.intern(w)).collect();
+        assert_eq!(ids, vec![0, 1, 2, 0, 3]);
+        assert_eq!(words.len(), 4);
+        assert_eq!(words.resolve(2), long);
+        assert_eq!(words.resolve(3), "bird");
+        assert_eq!(words.get("dog"), Some(1));
+        assert_eq!(words.get("do"), None);
+        assert_eq!(words.chunks.len(), 3);
+
+        words.clear();
+        assert_eq!(words.len(), 0);
+        assert_eq!(words.chunks.len(), 1);
+        assert_eq!(words.intern("dog"), 0);
+
+        let mut chain = compact();
+        chain.clear();
+        assert!(chain.is_empty());
+        chain.train("a b").unwrap();
+        assert_eq!(chain.successors("a"), vec![("b", 1)]);
+    }
+}
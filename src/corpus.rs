@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+/*
+remembers the training texts word for word, so we can tell when generated output is just a quote
+*/
+#[derive(Debug, Default)]
+pub struct CorpusIndex {
+    texts: Vec<Vec<String>>,
+    // every place each word occurs, as (text, position within it)
+    positions: HashMap<String, Vec<(usize, usize)>>
+}
+
+impl CorpusIndex {
+    pub fn new() -> CorpusIndex {
+        CorpusIndex::default()
+    }
+
+    pub fn add(&mut self, words: &[String]) {
+        let text = self.texts.len();
+        for (i, word) in words.iter().enumerate() {
+            self.positions.entry(word.clone()).or_default().push((text, i));
+        }
+        self.texts.push(words.to_vec());
+    }
+
+    /*
+    swaps every word for whatever rename says it's called now
+    */
+    pub(crate) fn rename<F: Fn(&String) -> String>(&mut self, rename: &F) {
+        let texts: Vec<Vec<String>> = self.texts.iter()
+            .map(|text| text.iter().map(rename).collect())
+            .collect();
+        *self = CorpusIndex::new();
+        for text in &texts {
+            self.add(text);
+        }
+    }
+
+    /*
+    the texts, in the order we saw them
+    */
+    pub fn texts(&self) -> &[Vec<String>] {
+        &self.texts
+    }
+
+    /*
+    the most words from the start of words that occur, in order and back to back, somewhere in
+    one of the texts
+    */
+    fn longest_prefix(&self, words: &[String]) -> usize {
+        let first = match words.first() {
+            Some(first) => first,
+            None => return 0
+        };
+        self.positions.get(first).map_or(0, |positions| {
+            positions.iter()
+                .map(|&(text, i)| self.texts[text][i..].iter().zip(words).take_while(|(a, b)| a == b).count())
+                .max()
+                .unwrap_or(0)
+        })
+    }
+
+    /*
+    whether words occur, in order and back to back, somewhere in one of the texts
+    */
+    pub fn contains(&self, words: &[String]) -> bool {
+        if words.is_empty() {
+            return true;
+        }
+        self.positions.get(&words[0]).is_some_and(|positions| {
+            positions.iter().any(|&(text, i)| self.texts[text][i..].starts_with(words))
+        })
+    }
+}
+
+/*
+how much of a generated output was copied word for word from the training texts
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Novelty {
+    // the most words in a row it shares with one of the texts
+    pub longest: usize,
+    // how many words in a row it shares with one of the texts from each of its words on, on
+    // average. 1.0 means every word is in the texts, but no two are ever next to each other there.
+    pub average: f64
+}
+
+/*
+measures how much of output is lifted straight from the texts in corpus, so a bot can throw away
+outputs that are just memorized quotes: a longest run of more than a handful of words usually
+is one. output has to be words as the chain counts them, like generate's output for a chain
+that folds case. an empty output has nothing copied.
+*/
+pub fn novelty(output: &[String], corpus: &CorpusIndex) -> Novelty {
+    let runs: Vec<usize> = (0..output.len()).map(|i| corpus.longest_prefix(&output[i..])).collect();
+    Novelty {
+        longest: runs.iter().cloned().max().unwrap_or(0),
+        average: if runs.is_empty() { 0.0 } else { runs.iter().sum::<usize>() as f64 / runs.len() as f64 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_contains() {
+        let mut index = CorpusIndex::new();
+        index.add(&words("the cat sat on the mat"));
+        index.add(&words("the dog ate"));
+        assert!(index.contains(&words("the mat")));
+        assert!(index.contains(&words("dog ate")));
+        assert!(!index.contains(&words("the cat ate")));
+        assert!(!index.contains(&words("mat the")));
+        assert!(!index.contains(&words("ate more")));
+    }
+
+    #[test]
+    fn test_novelty() {
+        let mut index = CorpusIndex::new();
+        index.add(&words("the cat sat on the mat"));
+        index.add(&words("the dog ate"));
+        assert_eq!(novelty(&words("the cat sat on the dog"), &index), Novelty { longest: 5, average: 17.0 / 6.0 });
+        assert_eq!(novelty(&words("dog the zebra"), &index), Novelty { longest: 1, average: 2.0 / 3.0 });
+        assert_eq!(novelty(&[], &index), Novelty { longest: 0, average: 0.0 });
+    }
+}
@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use Chain;
+
+impl Chain {
+    /*
+    the chain as a Graphviz digraph, for rendering with dot. each word keeps only its top most
+    likely successors (0 keeps all of them), since a whole corpus is an unreadable hairball.
+    edges are labelled with the probability of following them.
+    */
+    pub fn to_dot(&self, top: usize) -> String {
+        let mut out = String::from("digraph markov {\n");
+        for (w, next, p) in self.top_edges(top) {
+            // writing to a String can't fail
+            let _ = writeln!(out, "    {} -> {} [label=\"{:.2}\"];", quote(w), quote(next), p);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /*
+    the same as to_dot, but as a Mermaid flowchart, which GitHub and plenty of Markdown renderers
+    draw straight from a ```mermaid block. words get ids in alphabetical order, since Mermaid can't
+    use arbitrary text as one.
+    */
+    pub fn to_mermaid(&self, top: usize) -> String {
+        let edges = self.top_edges(top);
+        let mut ids: BTreeMap<&String, usize> = BTreeMap::new();
+        for &(w, next, _) in &edges {
+            ids.insert(w, 0);
+            ids.insert(next, 0);
+        }
+        for (i, id) in ids.values_mut().enumerate() {
+            *id = i;
+        }
+
+        let mut out = String::from("flowchart LR\n");
+        for (w, id) in &ids {
+            let _ = writeln!(out, "    n{}[\"{}\"]", id, escape(w));
+        }
+        for (w, next, p) in edges {
+            let _ = writeln!(out, "    n{} -->|{:.2}| n{}", ids[w], p, ids[next]);
+        }
+        out
+    }
+
+    /*
+    every edge we draw: each word's top most likely successors (0 keeps all of them), likeliest
+    first, with the probability of following each
+    */
+    fn top_edges(&self, top: usize) -> Vec<(&String, &String, f32)> {
+        let mut out = vec![];
+        for (w, &total) in &self.nodes {
+            let mut successors = self.successors(w);
+            successors.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal).then_with(|| a.0.cmp(b.0)));
+            if top > 0 {
+                successors.truncate(top);
+            }
+            out.extend(successors.into_iter().map(|(next, weight)| (w, next, weight / total as f32)));
+        }
+        out
+    }
+}
+
+fn quote(w: &str) -> String {
+    format!("\"{}\"", w.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/*
+makes w safe inside a quoted Mermaid label, which takes HTML-style entity codes
+*/
+fn escape(w: &str) -> String {
+    w.replace('#', "#35;").replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use {Hook, TrainConfig};
+
+    #[test]
+    fn test_to_dot() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat the cat").unwrap();
+        let expected = "digraph markov {
+    \"cat\" -> \"sat\" [label=\"1.00\"];
+    \"mat\" -> \"the\" [label=\"1.00\"];
+    \"on\" -> \"the\" [label=\"1.00\"];
+    \"sat\" -> \"on\" [label=\"1.00\"];
+    \"the\" -> \"cat\" [label=\"0.67\"];
+}
+";
+        assert_eq!(chain.to_dot(1), expected);
+        assert!(chain.to_dot(0).contains("\"the\" -> \"mat\" [label=\"0.33\"];"));
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        let expected = "flowchart LR
+    n0[\"cat\"]
+    n1[\"on\"]
+    n2[\"sat\"]
+    n3[\"the\"]
+    n0 -->|1.00| n2
+    n1 -->|1.00| n3
+    n2 -->|1.00| n1
+    n3 -->|0.50| n0
+";
+        assert_eq!(chain.to_mermaid(1), expected);
+        assert!(chain.to_mermaid(0).contains("n4[\"the\"]"));
+
+        let mut chain = Chain::with_config(TrainConfig { keep_chars: Some(Hook(Arc::new(|_| true))), ..TrainConfig::default() });
+        chain.train("say \"#1\"").unwrap();
+        assert!(chain.to_mermaid(0).contains("[\"#quot;#35;1#quot;\"]"));
+    }
+
+    #[test]
+    fn test_to_dot_quoting() {
+        let mut chain = Chain::with_config(TrainConfig { keep_chars: Some(Hook(Arc::new(|_| true))), ..TrainConfig::default() });
+        chain.train("say \"hi\"").unwrap();
+        assert!(chain.to_dot(0).contains("\"say\" -> \"\\\"hi\\\"\""));
+    }
+}
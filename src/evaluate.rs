@@ -0,0 +1,127 @@
+use {Chain, MarkovErr, NgramModel, TrainConfig};
+
+// the share of the corpus fit_order holds out to score each order on
+const HELD_OUT: f64 = 0.2;
+
+/*
+how an n-gram model of one order did in fit_order
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderScore {
+    pub order: usize,
+    // on the held out texts. lower is better.
+    pub perplexity: f64,
+    // how big the model was (see NgramModel::ngrams)
+    pub ngrams: usize
+}
+
+/*
+splits texts into the ones to train on and the ones to hold out for checking a model against,
+holding out about fraction of them (between 0.0 and 1.0). the held out texts are spread evenly
+through the list rather than taken from the end, so a corpus sorted by date or by author doesn't
+leave its last few completely unseen. the same texts and fraction always split the same way.
+*/
+pub fn split_held_out<T: AsRef<str>>(texts: &[T], fraction: f64) -> (Vec<&str>, Vec<&str>) {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let mut train = vec![];
+    let mut held_out = vec![];
+    for (i, text) in texts.iter().enumerate() {
+        // text i is held out whenever it takes the running share held out up to the next whole text
+        if ((i + 1) as f64 * fraction).floor() > (i as f64 * fraction).floor() {
+            held_out.push(text.as_ref());
+        } else {
+            train.push(text.as_ref());
+        }
+    }
+    (train, held_out)
+}
+
+/*
+how well an n-gram model of order n, splitting text as config says, predicts texts it wasn't
+trained on: trains on the texts split_held_out keeps, and reports the perplexity of the ones it
+holds out. lower is better, and the numbers are only comparable between runs on the same texts
+and fraction, so use it to pick between tokenizers, normalizations or orders. EmptyCorpus if
+nothing is held out, or nothing left to train on has any words.
+*/
+pub fn held_out_perplexity<T: AsRef<str>>(texts: &[T], fraction: f64, n: usize, config: TrainConfig) -> Result<f64, MarkovErr> {
+    let (train, held_out) = split_held_out(texts, fraction);
+    let mut model = NgramModel::with_config(n, config);
+    model.train_sequences(train)?;
+    if held_out.is_empty() {
+        return Err(MarkovErr::EmptyCorpus);
+    }
+    Ok(model.perplexity_of(held_out))
+}
+
+impl Chain {
+    /*
+    works out how many words of context text like corpus is best predicted with, splitting it
+    the way this chain would: trains an n-gram model of every order from 1 to max_order on most of
+    corpus, and scores each on the rest (see held_out_perplexity). returns the one with the lowest
+    perplexity, retrained on all of corpus, along with the score of every order so a smaller model
+    that's nearly as good can be chosen instead. ties go to the lower order. EmptyCorpus if corpus
+    is too small to hold any of it out, or what's left has no words.
+    */
+    pub fn fit_order<T: AsRef<str>>(&self, corpus: &[T], max_order: usize) -> Result<(NgramModel, Vec<OrderScore>), MarkovErr> {
+        let (train, held_out) = split_held_out(corpus, HELD_OUT);
+        if held_out.is_empty() {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+        let mut scores = vec![];
+        for order in 1..=max_order.max(1) {
+            let mut model = NgramModel::with_config(order, self.config.clone());
+            model.train_sequences(&train)?;
+            scores.push(OrderScore { order, perplexity: model.perplexity_of(&held_out), ngrams: model.ngrams() });
+        }
+        let best = scores.iter()
+            .min_by(|a, b| a.perplexity.total_cmp(&b.perplexity).then_with(|| a.order.cmp(&b.order)))
+            .map_or(1, |score| score.order);
+        let mut model = NgramModel::with_config(best, self.config.clone());
+        model.train_sequences(corpus)?;
+        Ok((model, scores))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Case;
+
+    #[test]
+    fn test_split_held_out() {
+        let texts = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"];
+        assert_eq!(split_held_out(&texts, 0.2), (vec!["a", "b", "c", "d", "f", "g", "h", "i"], vec!["e", "j"]));
+        assert_eq!(split_held_out(&texts, 0.0).1, Vec::<&str>::new());
+        assert_eq!(split_held_out(&texts, 1.0).0, Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_held_out_perplexity() {
+        let texts = [
+            "The cat sat on the mat", "the cat sat on the hat", "the dog sat on the mat",
+            "The dog sat on the hat", "the cat ran to the mat", "The dog ran to the hat"
+        ];
+        let folded = held_out_perplexity(&texts, 0.34, 2, TrainConfig { case: Case::Fold, ..TrainConfig::default() }).unwrap();
+        let kept = held_out_perplexity(&texts, 0.34, 2, TrainConfig { case: Case::Preserve, ..TrainConfig::default() }).unwrap();
+        assert!(folded < kept);
+        assert_eq!(held_out_perplexity(&texts, 0.0, 2, TrainConfig::default()), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(held_out_perplexity(&["!!", "the cat"], 0.5, 2, TrainConfig::default()), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_fit_order() {
+        // the word after "sat on the" depends on what sat, which only a 4-gram model can see
+        let texts: Vec<String> = (0..20)
+            .map(|i| if i % 2 == 0 { "the cat sat on the mat" } else { "the dog sat on the rug" })
+            .map(String::from)
+            .collect();
+        let (model, scores) = Chain::new().fit_order(&texts, 5).unwrap();
+        assert_eq!(scores.iter().map(|score| score.order).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert!(scores[3].perplexity < scores[2].perplexity);
+        assert!(scores[4].ngrams > scores[3].ngrams);
+        assert!(model.order() >= 4);
+        assert_eq!(model.next_word_distribution("the cat sat on the")[0].0, "mat");
+
+        assert!(Chain::new().fit_order(&["the cat"], 3).is_err());
+    }
+}
@@ -0,0 +1,579 @@
+/*
+the model file format. a file is MAGIC, then the format version as a little-endian u32, then the
+chain in whatever layout that version uses.
+
+every time the layout changes, FORMAT_VERSION goes up by one and the old version's reader stays
+behind as a migration: it reads the old layout and fills in whatever the newer one added, so files
+saved by older versions of the crate keep loading. files from newer versions than we know about
+are rejected with UnsupportedVersion rather than read as garbage.
+
+with the gzip feature, files can also be gzipped as a whole. load tells them apart by gzip's own
+magic number, so it reads both.
+*/
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+#[cfg(feature = "gzip")]
+use std::io::BufRead;
+#[cfg(feature = "gzip")]
+use flate2::{Compression, bufread::GzDecoder, write::GzEncoder};
+
+#[cfg(feature = "stem")]
+use Algorithm;
+#[cfg(feature = "unicode")]
+use UnicodeForm;
+use {Case, Chain, CorpusIndex, MarkovErr, Tokenizer, TrainConfig};
+
+pub const MAGIC: &[u8; 4] = b"MRKV";
+pub const FORMAT_VERSION: u32 = 4;
+#[cfg(feature = "gzip")]
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[cfg(feature = "stem")]
+const ALGORITHMS: [Algorithm; 18] = [
+    Algorithm::Arabic, Algorithm::Danish, Algorithm::Dutch, Algorithm::English, Algorithm::Finnish,
+    Algorithm::French, Algorithm::German, Algorithm::Greek, Algorithm::Hungarian, Algorithm::Italian,
+    Algorithm::Norwegian, Algorithm::Portuguese, Algorithm::Romanian, Algorithm::Russian,
+    Algorithm::Spanish, Algorithm::Swedish, Algorithm::Tamil, Algorithm::Turkish
+];
+
+impl From<io::Error> for MarkovErr {
+    fn from(e: io::Error) -> MarkovErr {
+        match e.kind() {
+            // a file that stops partway through something is a broken file, not a broken disk
+            io::ErrorKind::UnexpectedEof => MarkovErr::Corrupt,
+            _ => MarkovErr::Io{e: e.to_string()}
+        }
+    }
+}
+
+impl Chain {
+    /*
+    writes the chain to a file that load can read back. the config's hooks (normalize, keep_chars,
+    filter) are closures, so they can't be saved; use load_with to put them back.
+    */
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MarkovErr> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /*
+    the same as save, but gzipped. transition tables are mostly the same few words over and over,
+    so this usually comes out 5-10x smaller.
+    */
+    #[cfg(feature = "gzip")]
+    pub fn save_gz(&self, path: impl AsRef<Path>) -> Result<(), MarkovErr> {
+        let mut out = GzEncoder::new(BufWriter::new(File::create(path)?), Compression::default());
+        self.write_to(&mut out)?;
+        out.finish()?.flush()?;
+        Ok(())
+    }
+
+    /*
+    reads a file written by save (or save_gz, with the gzip feature). without the feature, a gzipped
+    file is Corrupt.
+    */
+    pub fn load(path: impl AsRef<Path>) -> Result<Chain, MarkovErr> {
+        let mut input = BufReader::new(File::open(path)?);
+        #[cfg(feature = "gzip")]
+        {
+            if input.fill_buf()?.starts_with(&GZIP_MAGIC) {
+                return Chain::read_from(&mut GzDecoder::new(input));
+            }
+        }
+        Chain::read_from(&mut input)
+    }
+
+    /*
+    loads a chain, taking the config's hooks from hooks since the file can't have them, along with
+    successor_cache, which is only about how we run rather than what we learned. everything else
+    about the config comes from the file.
+    */
+    pub fn load_with(path: impl AsRef<Path>, hooks: &TrainConfig) -> Result<Chain, MarkovErr> {
+        let mut chain = Chain::load(path)?;
+        chain.config.normalize = hooks.normalize.clone();
+        chain.config.keep_chars = hooks.keep_chars.clone();
+        chain.config.filter = hooks.filter.clone();
+        #[cfg(feature = "pos")]
+        {
+            chain.config.tagger = hooks.tagger.clone();
+        }
+        chain.config.successor_cache = hooks.successor_cache;
+        Ok(chain)
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<(), MarkovErr> {
+        out.write_all(MAGIC)?;
+        write_u32(out, FORMAT_VERSION)?;
+        write_chain(out, self)
+    }
+
+    pub fn read_from<R: Read>(input: &mut R) -> Result<Chain, MarkovErr> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(MarkovErr::Corrupt);
+        }
+        match read_u32(input)? {
+            // version 1 didn't have the tokenizer, which was always Words back then, versions
+            // 1 and 2 didn't have the Unicode settings, since text was never normalized, and
+            // nothing before version 4 had sources
+            v @ 1..=4 => read_chain(input, v),
+            v => Err(MarkovErr::UnsupportedVersion{v})
+        }
+    }
+}
+
+fn write_chain<W: Write>(out: &mut W, chain: &Chain) -> Result<(), MarkovErr> {
+    write_config(out, &chain.config)?;
+    write_counts(out, &chain.nodes)?;
+    write_counts(out, &chain.incoming)?;
+    write_counts(out, &chain.starts)?;
+    write_counts(out, &chain.ends)?;
+
+    let mut edges: Vec<_> = chain.edges.iter().collect();
+    edges.sort();
+    write_u32(out, edges.len() as u32)?;
+    for ((a, b), &count) in edges {
+        write_str(out, a)?;
+        write_str(out, b)?;
+        write_i32(out, count)?;
+    }
+
+    let mut surfaces: Vec<_> = chain.surfaces.iter().collect();
+    surfaces.sort_by(|a, b| a.0.cmp(b.0));
+    write_u32(out, surfaces.len() as u32)?;
+    for (key, forms) in surfaces {
+        write_str(out, key)?;
+        write_counts(out, forms)?;
+    }
+
+    match chain.corpus {
+        Some(ref corpus) => {
+            write_u8(out, 1)?;
+            write_u32(out, corpus.texts().len() as u32)?;
+            for text in corpus.texts() {
+                write_words(out, text.iter())?;
+            }
+        },
+        None => write_u8(out, 0)?
+    }
+
+    match chain.known {
+        Some(ref known) => {
+            write_u8(out, 1)?;
+            let mut known: Vec<&String> = known.iter().collect();
+            known.sort();
+            write_words(out, known.into_iter())?;
+        },
+        None => write_u8(out, 0)?
+    }
+
+    match chain.reverse {
+        Some(ref reverse) => {
+            write_u8(out, 1)?;
+            write_chain(out, reverse)?;
+        },
+        None => write_u8(out, 0)?
+    }
+
+    write_u32(out, chain.sources.len() as u32)?;
+    for ((a, b), sources) in &chain.sources {
+        write_str(out, a)?;
+        write_str(out, b)?;
+        write_words(out, sources.iter())?;
+    }
+    Ok(())
+}
+
+fn read_chain<R: Read>(input: &mut R, version: u32) -> Result<Chain, MarkovErr> {
+    let mut chain = Chain::new();
+    chain.config = read_config(input, version)?;
+    chain.nodes = read_counts(input)?;
+    chain.incoming = read_counts(input)?;
+    chain.starts = read_counts(input)?;
+    chain.ends = read_counts(input)?;
+
+    for _ in 0..read_u32(input)? {
+        let a = read_str(input)?;
+        let b = read_str(input)?;
+        chain.edges.insert((a, b), read_i32(input)?);
+    }
+
+    for _ in 0..read_u32(input)? {
+        let key = read_str(input)?;
+        chain.surfaces.insert(key, read_counts(input)?);
+    }
+
+    if read_flag(input)? {
+        let mut corpus = CorpusIndex::new();
+        for _ in 0..read_u32(input)? {
+            corpus.add(&read_words(input)?);
+        }
+        chain.corpus = Some(corpus);
+    }
+
+    if read_flag(input)? {
+        chain.known = Some(read_words(input)?.into_iter().collect::<HashSet<String>>());
+    }
+
+    if read_flag(input)? {
+        chain.reverse = Some(Box::new(read_chain(input, version)?));
+    }
+
+    if version >= 4 {
+        for _ in 0..read_u32(input)? {
+            let a = read_str(input)?;
+            let b = read_str(input)?;
+            chain.sources.insert((a, b), read_words(input)?.into_iter().collect());
+        }
+    }
+    Ok(chain)
+}
+
+fn write_config<W: Write>(out: &mut W, config: &TrainConfig) -> io::Result<()> {
+    write_u8(out, config.self_loops as u8)?;
+    write_u8(out, config.wrap_around as u8)?;
+    write_u8(out, config.index_corpus as u8)?;
+    write_u8(out, config.reverse as u8)?;
+    write_u32(out, config.window as u32)?;
+    write_u8(out, match config.case {
+        Case::Fold => 0,
+        Case::Preserve => 1,
+        Case::FoldKeys => 2
+    })?;
+    // 0 for no stemming, otherwise one more than the algorithm's place in ALGORITHMS
+    #[cfg(feature = "stem")]
+    let stem = match config.stem {
+        Some(algorithm) => match ALGORITHMS.iter().position(|&a| a == algorithm) {
+            Some(i) => i as u8 + 1,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "unknown stemming algorithm"))
+        },
+        None => 0
+    };
+    #[cfg(not(feature = "stem"))]
+    let stem = 0;
+    write_u8(out, stem)?;
+    write_u8(out, match config.tokenizer {
+        Tokenizer::Words => 0,
+        Tokenizer::Code => 1,
+        Tokenizer::Chars => 2,
+        #[cfg(feature = "graphemes")]
+        Tokenizer::Graphemes => 3
+    })?;
+    // 0 for no normal form, then 1 for NFC and 2 for NFKC, and whether diacritics are folded
+    #[cfg(feature = "unicode")]
+    let unicode = (match config.unicode {
+        None => 0,
+        Some(UnicodeForm::Nfc) => 1,
+        Some(UnicodeForm::Nfkc) => 2
+    }, config.fold_diacritics as u8);
+    #[cfg(not(feature = "unicode"))]
+    let unicode = (0, 0);
+    write_u8(out, unicode.0)?;
+    write_u8(out, unicode.1)
+}
+
+fn read_config<R: Read>(input: &mut R, version: u32) -> Result<TrainConfig, MarkovErr> {
+    let mut config = TrainConfig {
+        self_loops: read_flag(input)?,
+        wrap_around: read_flag(input)?,
+        index_corpus: read_flag(input)?,
+        reverse: read_flag(input)?,
+        window: read_u32(input)? as usize,
+        ..TrainConfig::default()
+    };
+    config.case = match read_u8(input)? {
+        0 => Case::Fold,
+        1 => Case::Preserve,
+        2 => Case::FoldKeys,
+        _ => return Err(MarkovErr::Corrupt)
+    };
+    match read_u8(input)? {
+        0 => {},
+        #[cfg(feature = "stem")]
+        n if (n as usize) <= ALGORITHMS.len() => config.stem = Some(ALGORITHMS[n as usize - 1]),
+        // either garbage, or a stemmed model loaded without the stem feature, which would mean
+        // seeds and training texts never get stemmed to match
+        _ => return Err(MarkovErr::Corrupt)
+    }
+    if version >= 2 {
+        config.tokenizer = match read_u8(input)? {
+            0 => Tokenizer::Words,
+            1 => Tokenizer::Code,
+            2 => Tokenizer::Chars,
+            #[cfg(feature = "graphemes")]
+            3 => Tokenizer::Graphemes,
+            // either garbage, or a grapheme model loaded without the graphemes feature
+            _ => return Err(MarkovErr::Corrupt)
+        };
+    }
+    if version >= 3 {
+        match (read_u8(input)?, read_u8(input)?) {
+            (0, 0) => {},
+            #[cfg(feature = "unicode")]
+            (form @ 0..=2, fold @ 0..=1) => {
+                config.unicode = match form {
+                    1 => Some(UnicodeForm::Nfc),
+                    2 => Some(UnicodeForm::Nfkc),
+                    _ => None
+                };
+                config.fold_diacritics = fold == 1;
+            },
+            // either garbage, or a normalized model loaded without the unicode feature, which
+            // would mean seeds and training texts never get normalized to match
+            _ => return Err(MarkovErr::Corrupt)
+        }
+    }
+    Ok(config)
+}
+
+fn write_counts<W: Write>(out: &mut W, counts: &BTreeMap<String, i32>) -> io::Result<()> {
+    let mut counts: Vec<_> = counts.iter().collect();
+    counts.sort();
+    write_u32(out, counts.len() as u32)?;
+    for (w, &count) in counts {
+        write_str(out, w)?;
+        write_i32(out, count)?;
+    }
+    Ok(())
+}
+
+fn read_counts<R: Read>(input: &mut R) -> Result<BTreeMap<String, i32>, MarkovErr> {
+    let mut counts = BTreeMap::new();
+    for _ in 0..read_u32(input)? {
+        let w = read_str(input)?;
+        counts.insert(w, read_i32(input)?);
+    }
+    Ok(counts)
+}
+
+fn write_words<'a, W: Write, I: ExactSizeIterator<Item = &'a String>>(out: &mut W, words: I) -> io::Result<()> {
+    write_u32(out, words.len() as u32)?;
+    for w in words {
+        write_str(out, w)?;
+    }
+    Ok(())
+}
+
+fn read_words<R: Read>(input: &mut R) -> Result<Vec<String>, MarkovErr> {
+    (0..read_u32(input)?).map(|_| read_str(input)).collect()
+}
+
+pub(crate) fn write_str<W: Write>(out: &mut W, s: &str) -> io::Result<()> {
+    write_u32(out, s.len() as u32)?;
+    out.write_all(s.as_bytes())
+}
+
+pub(crate) fn read_str<R: Read>(input: &mut R) -> Result<String, MarkovErr> {
+    let len = read_u32(input)? as u64;
+    // read through take rather than allocating len bytes up front, so a garbage length can't ask
+    // for gigabytes
+    let mut bytes = vec![];
+    input.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(MarkovErr::Corrupt);
+    }
+    String::from_utf8(bytes).map_err(|_| MarkovErr::Corrupt)
+}
+
+pub(crate) fn write_u8<W: Write>(out: &mut W, n: u8) -> io::Result<()> {
+    out.write_all(&[n])
+}
+
+pub(crate) fn read_u8<R: Read>(input: &mut R) -> Result<u8, MarkovErr> {
+    let mut buf = [0; 1];
+    input.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_flag<R: Read>(input: &mut R) -> Result<bool, MarkovErr> {
+    match read_u8(input)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(MarkovErr::Corrupt)
+    }
+}
+
+pub(crate) fn write_u32<W: Write>(out: &mut W, n: u32) -> io::Result<()> {
+    out.write_all(&n.to_le_bytes())
+}
+
+pub(crate) fn read_u32<R: Read>(input: &mut R) -> Result<u32, MarkovErr> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn write_i32<W: Write>(out: &mut W, n: i32) -> io::Result<()> {
+    out.write_all(&n.to_le_bytes())
+}
+
+fn read_i32<R: Read>(input: &mut R) -> Result<i32, MarkovErr> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> ::std::path::PathBuf {
+        ::std::env::temp_dir().join(format!("markov-test-{}-{}.mrkv", ::std::process::id(), name))
+    }
+
+    fn round_trip(chain: &Chain) -> Chain {
+        let mut bytes = vec![];
+        chain.write_to(&mut bytes).unwrap();
+        Chain::read_from(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut chain = Chain::with_config(TrainConfig {
+            reverse: true,
+            index_corpus: true,
+            case: Case::FoldKeys,
+            window: 2,
+            ..TrainConfig::default()
+        });
+        chain.train("The cat sat on the mat").unwrap();
+        chain.train_from("dogs.txt", "the dog sat").unwrap();
+        chain.cap_vocab(4);
+
+        let loaded = round_trip(&chain);
+        assert_eq!(loaded.nodes, chain.nodes);
+        assert_eq!(loaded.edges, chain.edges);
+        assert_eq!(loaded.incoming, chain.incoming);
+        assert_eq!(loaded.starts, chain.starts);
+        assert_eq!(loaded.ends, chain.ends);
+        assert_eq!(loaded.surfaces, chain.surfaces);
+        assert_eq!(loaded.sources, chain.sources);
+        assert_eq!(loaded.known, chain.known);
+        assert_eq!(loaded.config.window, 2);
+        assert_eq!(loaded.config.case, Case::FoldKeys);
+        assert_eq!(loaded.corpus().unwrap().texts(), chain.corpus().unwrap().texts());
+        assert_eq!(loaded.predecessors("sat").unwrap(), chain.predecessors("sat").unwrap());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut chain = Chain::new();
+        chain.train("hello there world").unwrap();
+        let path = temp_path("plain");
+        chain.save(&path).unwrap();
+        let loaded = Chain::load(&path);
+        ::std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().edges, chain.edges);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_save_load_gz() {
+        let mut chain = Chain::new();
+        for _ in 0..50 {
+            chain.train("the cat sat on the mat and the dog sat on the cat").unwrap();
+        }
+        let plain = temp_path("plain-gz");
+        let gz = temp_path("gz");
+        chain.save(&plain).unwrap();
+        chain.save_gz(&gz).unwrap();
+        let sizes = (::std::fs::metadata(&plain).unwrap().len(), ::std::fs::metadata(&gz).unwrap().len());
+        let loaded = Chain::load(&gz);
+        ::std::fs::remove_file(&plain).unwrap();
+        ::std::fs::remove_file(&gz).unwrap();
+        assert!(sizes.1 < sizes.0);
+        assert_eq!(loaded.unwrap().edges, chain.edges);
+    }
+
+    #[test]
+    fn test_reads_version_1() {
+        let mut chain = Chain::with_config(TrainConfig::code());
+        chain.train("let x = y;").unwrap();
+        let loaded = round_trip(&chain);
+        assert_eq!(loaded.config.tokenizer, Tokenizer::Code);
+
+        // version 1 was the same, minus the tokenizer and Unicode bytes at the end of the config
+        let mut bytes = vec![];
+        chain.write_to(&mut bytes).unwrap();
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+        bytes.drain(18..21);
+        let loaded = Chain::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(loaded.config.tokenizer, Tokenizer::Words);
+        assert_eq!(loaded.edges, chain.edges);
+    }
+
+    #[test]
+    fn test_reads_version_2() {
+        let mut chain = Chain::new();
+        chain.train("hello there world").unwrap();
+
+        // version 2 was the same, minus the Unicode bytes at the end of the config
+        let mut bytes = vec![];
+        chain.write_to(&mut bytes).unwrap();
+        bytes[4..8].copy_from_slice(&2u32.to_le_bytes());
+        bytes.drain(19..21);
+        assert_eq!(Chain::read_from(&mut &bytes[..]).unwrap().edges, chain.edges);
+    }
+
+    #[test]
+    fn test_reads_version_3() {
+        let mut chain = Chain::new();
+        chain.train("hello there world").unwrap();
+
+        // version 3 was the same, minus the count of sources at the very end
+        let mut bytes = vec![];
+        chain.write_to(&mut bytes).unwrap();
+        bytes[4..8].copy_from_slice(&3u32.to_le_bytes());
+        bytes.truncate(bytes.len() - 4);
+        let loaded = Chain::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(loaded.edges, chain.edges);
+        assert!(loaded.sources.is_empty());
+    }
+
+    #[test]
+    fn test_save_load_sources() {
+        let mut chain = Chain::new();
+        chain.train_from("diary.txt", "the cat sat on the mat").unwrap();
+        chain.train_from("notes.txt", "the cat ran").unwrap();
+        let path = temp_path("sources");
+        chain.save(&path).unwrap();
+        let loaded = Chain::load(&path);
+        ::std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.sources, chain.sources);
+        assert_eq!(loaded.provenance(&["the", "cat", "sat"]), vec![("diary.txt".to_string(), 2), ("notes.txt".to_string(), 1)]);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_round_trip_unicode() {
+        let chain = Chain::with_config(TrainConfig { unicode: Some(UnicodeForm::Nfkc), fold_diacritics: true, ..TrainConfig::default() });
+        let loaded = round_trip(&chain);
+        assert_eq!(loaded.config.unicode, Some(UnicodeForm::Nfkc));
+        assert!(loaded.config.fold_diacritics);
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        assert_eq!(Chain::read_from(&mut &b"not a model"[..]).err(), Some(MarkovErr::Corrupt));
+        assert_eq!(Chain::read_from(&mut &b"MR"[..]).err(), Some(MarkovErr::Corrupt));
+
+        let mut bytes = vec![];
+        Chain::new().write_to(&mut bytes).unwrap();
+        bytes[4..8].copy_from_slice(&99u32.to_le_bytes());
+        assert_eq!(Chain::read_from(&mut &bytes[..]).err(), Some(MarkovErr::UnsupportedVersion{v: 99}));
+
+        let mut bytes = vec![];
+        let mut chain = Chain::new();
+        chain.train("a b c").unwrap();
+        chain.write_to(&mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 3);
+        assert_eq!(Chain::read_from(&mut &bytes[..]).err(), Some(MarkovErr::Corrupt));
+    }
+}
@@ -0,0 +1,463 @@
+/*
+a read-only copy of a chain packed into one flat buffer, so it can be written to disk as-is and
+memory-mapped back (with the mmap feature) instead of being parsed into hash maps. opening one only
+reads the header, so even a huge model is ready straight away and pages in as it's used.
+
+the buffer is all little-endian u32s, then the words' bytes:
+
+    header         FROZEN_MAGIC, FROZEN_VERSION, words (W), edges (E), string bytes (S)
+    word_offsets   W + 1 offsets into the string bytes, the words in sorted order
+    row_starts     W + 1 offsets into targets/weights, so word i's edges are row_starts[i]..row_starts[i + 1]
+    targets        E word ids
+    weights        E running totals of the counts along each word's row, so the last one in a row
+                   is the row's total (in version 1 files, the counts themselves)
+    starts         W counts of how often each word started a text
+    strings        S bytes of utf-8
+
+everything's bounds-checked when it's read, so a damaged file gives wrong answers or Corrupt rather
+than a panic.
+*/
+use std::fs::File;
+use std::io::{Read, Write};
+use std::ops::Deref;
+use std::path::Path;
+
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use rand::{thread_rng, Rng};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use {Chain, MarkovErr};
+
+pub const FROZEN_MAGIC: &[u8; 4] = b"MRKF";
+pub const FROZEN_VERSION: u32 = 2;
+const HEADER_LEN: usize = 5;
+
+enum Bytes {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap)
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match *self {
+            Bytes::Owned(ref bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Bytes::Mapped(ref map) => map
+        }
+    }
+}
+
+pub struct FrozenChain {
+    bytes: Bytes,
+    words: usize,
+    edges: usize,
+    // whether weights are running totals. they're plain counts in version 1 files.
+    cumulative: bool
+}
+
+impl Chain {
+    /*
+    packs the chain's transitions into a FrozenChain. only the counts come along: no corpus index,
+    reverse chain or surface forms, so the frozen chain's words are the keys we counted.
+    */
+    pub fn freeze(&self) -> FrozenChain {
+        let words = self.vocab();
+        let id = |w: &String| words.binary_search(w).unwrap() as u32;
+
+        let mut rows: Vec<Vec<(u32, u32)>> = vec![vec![]; words.len()];
+        for ((a, b), &count) in &self.edges {
+            rows[id(a) as usize].push((id(b), count as u32));
+        }
+        let strings: usize = words.iter().map(|w| w.len()).sum();
+        let edges: usize = rows.iter().map(|row| row.len()).sum();
+
+        let mut out = vec![];
+        out.extend_from_slice(FROZEN_MAGIC);
+        for n in &[FROZEN_VERSION, words.len() as u32, edges as u32, strings as u32] {
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        let mut offset = 0;
+        push(&mut out, 0);
+        for w in &words {
+            offset += w.len() as u32;
+            push(&mut out, offset);
+        }
+        let mut start = 0;
+        push(&mut out, 0);
+        for row in rows.iter_mut() {
+            row.sort();
+            start += row.len() as u32;
+            push(&mut out, start);
+        }
+        for row in &rows {
+            for &(target, _) in row {
+                push(&mut out, target);
+            }
+        }
+        for row in &rows {
+            let mut total: u32 = 0;
+            for &(_, weight) in row {
+                total = total.saturating_add(weight);
+                push(&mut out, total);
+            }
+        }
+        for w in &words {
+            push(&mut out, *self.starts.get(w).unwrap_or(&0) as u32);
+        }
+        for w in &words {
+            out.extend_from_slice(w.as_bytes());
+        }
+        FrozenChain { bytes: Bytes::Owned(out), words: words.len(), edges, cumulative: true }
+    }
+}
+
+impl FrozenChain {
+    /*
+    checks the header and that the buffer's the size it says, but nothing past that
+    */
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<FrozenChain, MarkovErr> {
+        FrozenChain::open(Bytes::Owned(bytes))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<FrozenChain, MarkovErr> {
+        let mut bytes = vec![];
+        File::open(path)?.read_to_end(&mut bytes)?;
+        FrozenChain::from_bytes(bytes)
+    }
+
+    /*
+    maps the file into memory rather than reading it. the file mustn't change while the chain's
+    around; if something truncates it underneath us, the OS kills the process.
+    */
+    #[cfg(feature = "mmap")]
+    pub fn load_mmap(path: impl AsRef<Path>) -> Result<FrozenChain, MarkovErr> {
+        let file = File::open(path)?;
+        let map = unsafe { Mmap::map(&file)? };
+        FrozenChain::open(Bytes::Mapped(map))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MarkovErr> {
+        File::create(path)?.write_all(&self.bytes)?;
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn open(bytes: Bytes) -> Result<FrozenChain, MarkovErr> {
+        if bytes.len() < 4 * HEADER_LEN || &bytes[..4] != FROZEN_MAGIC {
+            return Err(MarkovErr::Corrupt);
+        }
+        let header = |i: usize| read(&bytes, i).unwrap_or(0) as usize;
+        let cumulative = match header(1) as u32 {
+            1 => false,
+            2 => true,
+            v => return Err(MarkovErr::UnsupportedVersion{v})
+        };
+        let (words, edges, strings) = (header(2), header(3), header(4));
+        let expected = 4 * (HEADER_LEN + 3 * words + 2 + 2 * edges) + strings;
+        if bytes.len() != expected {
+            return Err(MarkovErr::Corrupt);
+        }
+        Ok(FrozenChain { bytes, words, edges, cumulative })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words == 0
+    }
+
+    pub fn contains(&self, w: &str) -> bool {
+        self.id(w).is_some()
+    }
+
+    /*
+    the words that have followed w, and how many times each did, in alphabetical order
+    */
+    pub fn successors(&self, w: &str) -> Vec<(&str, u32)> {
+        let row = match self.id(w).and_then(|id| self.row(id)) {
+            Some(row) => row,
+            None => return vec![]
+        };
+        let mut total = 0;
+        row.filter_map(|i| {
+            let target = self.word(self.target(i)? as usize)?;
+            let weight = self.weight(i)?;
+            if !self.cumulative {
+                return Some((target, weight));
+            }
+            let count = weight.checked_sub(total)?;
+            total = weight;
+            Some((target, count))
+        }).collect()
+    }
+
+    /*
+    picks a word to follow w, weighted by how often each did. None at a dead end or for a word
+    we've never seen. the running totals make this a binary search, which matters for words like
+    "the" with thousands of successors.
+    */
+    pub fn next<R: Rng>(&self, w: &str, rng: &mut R) -> Option<&str> {
+        if !self.cumulative {
+            return self.next_linear(w, rng);
+        }
+        let row = self.row(self.id(w)?)?;
+        if row.start >= row.end {
+            return None;
+        }
+        let total = self.weight(row.end - 1)?;
+        if total == 0 {
+            return None;
+        }
+        let cursor = rng.gen_range(0, total);
+        // the first edge whose running total goes past the cursor
+        let (mut lo, mut hi) = (row.start, row.end - 1);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.weight(mid)? > cursor {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        self.word(self.target(lo)? as usize)
+    }
+
+    /*
+    next for version 1 files, which only have the counts
+    */
+    fn next_linear<R: Rng>(&self, w: &str, rng: &mut R) -> Option<&str> {
+        let choices = self.successors(w);
+        let total: u64 = choices.iter().map(|&(_, weight)| weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut cursor = rng.gen_range(0, total);
+        for &(next, weight) in &choices {
+            if cursor < weight as u64 {
+                return Some(next);
+            }
+            cursor -= weight as u64;
+        }
+        None
+    }
+
+    /*
+    walks len words from seed (seed included), stopping early at a dead end, like gen does
+    */
+    pub fn generate(&self, seed: &str, len: usize) -> Result<Vec<String>, MarkovErr> {
+        if !self.contains(seed) {
+            return Err(MarkovErr::NotSeen{w: seed.to_string()});
+        }
+        let mut rng = thread_rng();
+        let mut out = vec![seed.to_string()];
+        while out.len() < len {
+            match self.next(&out[out.len() - 1], &mut rng) {
+                Some(next) => out.push(next.to_string()),
+                None => break
+            }
+        }
+        out.truncate(len);
+        Ok(out)
+    }
+
+    /*
+    n independent walks from seed, spread across rayon's thread pool, for when you want a lot of
+    samples (to pad out a dataset, say) and have cores to spare. each thread has its own rng.
+    */
+    #[cfg(feature = "rayon")]
+    pub fn generate_many_parallel(&self, n: usize, seed: &str, len: usize) -> Result<Vec<Vec<String>>, MarkovErr> {
+        (0..n).into_par_iter().map(|_| self.generate(seed, len)).collect()
+    }
+
+    /*
+    how many times w started a training text
+    */
+    pub fn start_count(&self, w: &str) -> u32 {
+        self.id(w).and_then(|id| self.at(self.starts_at() + id)).unwrap_or(0)
+    }
+
+    fn id(&self, w: &str) -> Option<usize> {
+        // the words are sorted, so binary search them without building anything
+        let (mut lo, mut hi) = (0, self.words);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.word(mid)?.cmp(w) {
+                ::std::cmp::Ordering::Less => lo = mid + 1,
+                ::std::cmp::Ordering::Greater => hi = mid,
+                ::std::cmp::Ordering::Equal => return Some(mid)
+            }
+        }
+        None
+    }
+
+    fn word(&self, id: usize) -> Option<&str> {
+        let start = self.at(HEADER_LEN + id)? as usize;
+        let end = self.at(HEADER_LEN + id + 1)? as usize;
+        let strings = 4 * (HEADER_LEN + 3 * self.words + 2 + 2 * self.edges);
+        let bytes = self.bytes.get(strings + start..strings + end)?;
+        ::std::str::from_utf8(bytes).ok()
+    }
+
+    fn row(&self, id: usize) -> Option<::std::ops::Range<usize>> {
+        let rows = HEADER_LEN + self.words + 1;
+        let (start, end) = (self.at(rows + id)? as usize, self.at(rows + id + 1)? as usize);
+        // a damaged file could point anywhere, including billions of edges past the end
+        if start > end || end > self.edges {
+            return None;
+        }
+        Some(start..end)
+    }
+
+    fn target(&self, i: usize) -> Option<u32> {
+        self.at(HEADER_LEN + 2 * (self.words + 1) + i)
+    }
+
+    fn weight(&self, i: usize) -> Option<u32> {
+        self.at(HEADER_LEN + 2 * (self.words + 1) + self.edges + i)
+    }
+
+    fn starts_at(&self) -> usize {
+        HEADER_LEN + 2 * (self.words + 1) + 2 * self.edges
+    }
+
+    // the i-th u32 in the buffer
+    fn at(&self, i: usize) -> Option<u32> {
+        read(&self.bytes, i)
+    }
+}
+
+fn read(bytes: &[u8], i: usize) -> Option<u32> {
+    let b = bytes.get(4 * i..4 * i + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn push(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frozen() -> FrozenChain {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("the dog sat").unwrap();
+        chain.freeze()
+    }
+
+    #[test]
+    fn test_freeze() {
+        let chain = frozen();
+        assert_eq!(chain.len(), 6);
+        assert!(chain.contains("mat"));
+        assert!(!chain.contains("bird"));
+        assert_eq!(chain.successors("the"), vec![("cat", 1), ("dog", 1), ("mat", 1)]);
+        assert_eq!(chain.successors("sat"), vec![("on", 1)]);
+        assert_eq!(chain.successors("mat"), vec![]);
+        assert_eq!(chain.start_count("the"), 2);
+        assert_eq!(chain.next("on", &mut thread_rng()), Some("the"));
+    }
+
+    #[test]
+    fn test_generate() {
+        let chain = frozen();
+        let out = chain.generate("sat", 3).unwrap();
+        assert_eq!(&out[..2], &["sat".to_string(), "on".to_string()]);
+        assert_eq!(chain.generate("mat", 5), Ok(vec!["mat".to_string()]));
+        assert_eq!(chain.generate("bird", 5), Err(MarkovErr::NotSeen{w: "bird".to_string()}));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let chain = frozen();
+        let copy = FrozenChain::from_bytes(chain.as_bytes().to_vec()).unwrap();
+        assert_eq!(copy.successors("the"), chain.successors("the"));
+
+        let bytes = chain.as_bytes();
+        assert!(FrozenChain::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+        assert!(FrozenChain::from_bytes(b"MRKV".to_vec()).is_err());
+        let mut newer = bytes.to_vec();
+        newer[4..8].copy_from_slice(&99u32.to_le_bytes());
+        assert_eq!(FrozenChain::from_bytes(newer).err(), Some(MarkovErr::UnsupportedVersion{v: 99}));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_generate_many_parallel() {
+        let chain = frozen();
+        let out = chain.generate_many_parallel(100, "sat", 2).unwrap();
+        assert_eq!(out.len(), 100);
+        assert!(out.iter().all(|words| words == &["sat".to_string(), "on".to_string()]));
+        assert!(chain.generate_many_parallel(10, "bird", 2).is_err());
+    }
+
+    #[test]
+    fn test_next_weights() {
+        let mut chain = Chain::new();
+        chain.see_weighted("the", "a", 1);
+        chain.see_weighted("the", "b", 3);
+        chain.see_weighted("the", "c", 0);
+        chain.see_weighted("the", "d", 4);
+        let chain = chain.freeze();
+        assert_eq!(chain.successors("the"), vec![("a", 1), ("b", 3), ("d", 4)]);
+        let mut rng = thread_rng();
+        let mut counts = [0; 3];
+        for _ in 0..8000 {
+            match chain.next("the", &mut rng) {
+                Some("a") => counts[0] += 1,
+                Some("b") => counts[1] += 1,
+                Some("d") => counts[2] += 1,
+                other => panic!("unexpected {:?}", other)
+            }
+        }
+        assert!((700..1300).contains(&counts[0]));
+        assert!((2600..3400).contains(&counts[1]));
+        assert!((3600..4400).contains(&counts[2]));
+        assert_eq!(chain.next("a", &mut rng), None);
+    }
+
+    #[test]
+    fn test_version_1() {
+        // version 1 had the same layout with plain counts where the running totals are now
+        let chain = frozen();
+        let mut bytes = chain.as_bytes().to_vec();
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+        let weights = 4 * (HEADER_LEN + 2 * (chain.words + 1) + chain.edges);
+        for i in 0..chain.edges {
+            bytes[weights + 4 * i..weights + 4 * i + 4].copy_from_slice(&1u32.to_le_bytes());
+        }
+        let old = FrozenChain::from_bytes(bytes).unwrap();
+        assert_eq!(old.successors("the"), chain.successors("the"));
+        assert_eq!(old.next("on", &mut thread_rng()), Some("the"));
+    }
+
+    #[test]
+    fn test_empty() {
+        let chain = Chain::new().freeze();
+        assert!(chain.is_empty());
+        assert_eq!(chain.successors("a"), vec![]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_load_mmap() {
+        let chain = frozen();
+        let path = ::std::env::temp_dir().join(format!("markov-test-{}-frozen.mrkf", ::std::process::id()));
+        chain.save(&path).unwrap();
+        let mapped = FrozenChain::load_mmap(&path).unwrap();
+        assert_eq!(mapped.successors("the"), chain.successors("the"));
+        drop(mapped);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+}
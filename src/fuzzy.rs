@@ -0,0 +1,128 @@
+use {rng, Chain, GenConfig, MarkovErr, UNK};
+
+// a word of a seed we'd never seen, and the one we used instead
+type Swap = (String, String);
+
+impl Chain {
+    /*
+    the word we know that's the fewest edits (insertions, deletions or substitutions of one
+    character) away from w, as long as that's at most max_distance. w itself if we know it.
+    ties go to whichever word we've seen most, then alphabetically. None if nothing's close enough.
+    */
+    pub fn nearest(&self, w: &str, max_distance: usize) -> Option<String> {
+        if self.contains(w) {
+            return Some(w.to_string());
+        }
+        let w: Vec<char> = w.chars().collect();
+        self.vocab().into_iter()
+            .filter(|known| known != UNK)
+            .filter_map(|known| {
+                let d = edit_distance(&w, &known.chars().collect::<Vec<char>>(), max_distance)?;
+                Some((d, -self.frequency(&known), known))
+            })
+            .min()
+            .map(|(_, _, known)| known)
+    }
+
+    /*
+    generate, but any word of the seed we've never seen is swapped for the nearest one we have
+    (see nearest) rather than being an error, so a typo in a prompt still gets an answer. also
+    returns each swap we made as (word, what we used instead), in the order they appear, so the
+    caller can say so. NotSeen if a word has nothing within max_distance of it.
+    */
+    pub fn generate_fuzzy(&self, config: &GenConfig, max_distance: usize) -> Result<(Vec<String>, Vec<Swap>), MarkovErr> {
+        let prompt = match config.seed {
+            Some(ref seed) => self.tokenize(seed),
+            None => return Ok((self.generate(config)?, vec![]))
+        };
+
+        let mut swaps = vec![];
+        let mut words = vec![];
+        for word in prompt {
+            match self.nearest(&word, max_distance) {
+                Some(ref known) if *known == word => words.push(word),
+                Some(known) => {
+                    swaps.push((word, known.clone()));
+                    words.push(known);
+                },
+                None => return Err(MarkovErr::NotSeen{w: word})
+            }
+        }
+
+        let out = self.generate_from(Some(words), config, &mut rng(config))?;
+        let (from, to): (Vec<String>, Vec<String>) = swaps.into_iter().unzip();
+        Ok((self.surface(out), from.into_iter().zip(self.surface(to)).collect()))
+    }
+}
+
+/*
+the Levenshtein distance between a and b, or None if it's more than max. only keeps one row of
+the table at a time, and gives up as soon as a whole row is over max.
+*/
+fn edit_distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + if ca == cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+        if row.iter().all(|&d| d > max) {
+            return None;
+        }
+    }
+    Some(row[b.len()]).filter(|&d| d <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Stop;
+
+    fn chars(w: &str) -> Vec<char> {
+        w.chars().collect()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting"), 3), Some(3));
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting"), 2), None);
+        assert_eq!(edit_distance(&chars(""), &chars("abc"), 3), Some(3));
+        assert_eq!(edit_distance(&chars("café"), &chars("cafe"), 1), Some(1));
+        assert_eq!(edit_distance(&chars("same"), &chars("same"), 0), Some(0));
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut chain = Chain::new();
+        chain.train("welcome to the jungle").unwrap();
+        chain.train("the jingle bells").unwrap();
+        assert_eq!(chain.nearest("jungle", 2), Some("jungle".to_string()));
+        assert_eq!(chain.nearest("jangle", 1), Some("jingle".to_string()));
+        assert_eq!(chain.nearest("wellcome", 1), Some("welcome".to_string()));
+        assert_eq!(chain.nearest("zebra", 2), None);
+    }
+
+    #[test]
+    fn test_generate_fuzzy() {
+        let mut chain = Chain::new();
+        chain.train("welcome to the jungle").unwrap();
+        let config = GenConfig {
+            seed: Some("Wellcome ot".to_string()),
+            stop: Stop::Words(4),
+            ..GenConfig::default()
+        };
+        let (out, swaps) = chain.generate_fuzzy(&config, 2).unwrap();
+        assert_eq!(out, vec!["welcome", "to", "the", "jungle"]);
+        assert_eq!(swaps, vec![("wellcome".to_string(), "welcome".to_string()), ("ot".to_string(), "to".to_string())]);
+
+        assert_eq!(chain.generate_fuzzy(&config, 0), Err(MarkovErr::NotSeen{w: "wellcome".to_string()}));
+        let config = GenConfig { seed: Some("the".to_string()), ..config };
+        assert_eq!(chain.generate_fuzzy(&config, 2).unwrap().1, vec![]);
+    }
+}
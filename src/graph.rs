@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use Chain;
+
+impl Chain {
+    /*
+    the chain as a petgraph graph, for the algorithms petgraph has and we don't (dominators,
+    isomorphism, min spanning trees). there's a node for every word we've seen, in alphabetical
+    order and weighted with the word, and an edge for every pair, weighted with how many times we
+    saw it. the counts of where texts started and ended don't come along.
+    */
+    pub fn to_petgraph(&self) -> DiGraph<String, u32> {
+        let words = self.vocab();
+        let mut graph = DiGraph::with_capacity(words.len(), self.edges.len());
+        let ids: HashMap<String, NodeIndex> = words.into_iter().map(|w| (w.clone(), graph.add_node(w))).collect();
+        for ((a, b), &count) in &self.edges {
+            graph.add_edge(ids[a], ids[b], count.max(0) as u32);
+        }
+        graph
+    }
+
+    /*
+    a chain with every edge of graph seen as many times as its weight says (see to_petgraph), the
+    words being the nodes' weights. nodes with no edges aren't kept, since a chain only knows words
+    from the pairs they're in.
+    */
+    pub fn from_petgraph(graph: &DiGraph<String, u32>) -> Chain {
+        let mut chain = Chain::new();
+        for edge in graph.raw_edges() {
+            chain.see_weighted(&graph[edge.source()], &graph[edge.target()], edge.weight);
+        }
+        chain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::algo::{dijkstra, has_path_connecting};
+
+    #[test]
+    fn test_to_petgraph() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        let graph = chain.to_petgraph();
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(graph.edge_count(), 5);
+        let find = |w: &str| graph.node_indices().find(|&i| graph[i] == w).unwrap();
+        assert!(has_path_connecting(&graph, find("cat"), find("mat"), None));
+        assert!(!has_path_connecting(&graph, find("mat"), find("cat"), None));
+        assert_eq!(dijkstra(&graph, find("the"), Some(find("on")), |_| 1)[&find("on")], 3);
+
+        let back = Chain::from_petgraph(&graph);
+        assert_eq!(back.edges, chain.edges);
+        assert_eq!(back.nodes, chain.nodes);
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(feature = "fetch")]
+use std::io::Read;
+
+#[cfg(feature = "fetch")]
+use MarkovErr;
+
+/*
+downloads Project Gutenberg book number id as plain text, with the licence header and footer
+stripped off (see strip_gutenberg), ready to train on
+*/
+#[cfg(feature = "fetch")]
+pub fn fetch_gutenberg(id: u32) -> Result<String, MarkovErr> {
+    let url = format!("https://www.gutenberg.org/cache/epub/{}/pg{}.txt", id, id);
+    let response = ureq::get(&url).call().map_err(|e| MarkovErr::Io{e: e.to_string()})?;
+    let mut text = String::new();
+    response.into_reader().read_to_string(&mut text)?;
+    Ok(strip_gutenberg(&text).to_string())
+}
+
+/*
+the book itself, from the text of a Project Gutenberg ebook: everything between the "*** START OF
+THE PROJECT GUTENBERG EBOOK ..." and "*** END OF ..." lines. without those, it's the whole text,
+since it probably didn't come from Gutenberg.
+*/
+pub fn strip_gutenberg(text: &str) -> &str {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let start = match marker(text, "*** START OF") {
+        Some(i) => text[i..].find('\n').map_or(text.len(), |end| i + end + 1),
+        None => 0
+    };
+    let end = marker(&text[start..], "*** END OF").map_or(text.len(), |i| start + i);
+    text[start..end].trim()
+}
+
+// where the first line that starts with this marker (and mentions Gutenberg) starts
+fn marker(text: &str, marker: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.starts_with(marker) && line.to_uppercase().contains("GUTENBERG") {
+            return Some(offset);
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_gutenberg() {
+        let book = "\u{feff}The Project Gutenberg eBook of Moby Dick\r\n\
+            \r\n\
+            *** START OF THE PROJECT GUTENBERG EBOOK MOBY DICK ***\r\n\
+            \r\n\
+            Call me Ishmael.\r\n\
+            \r\n\
+            *** END OF THE PROJECT GUTENBERG EBOOK MOBY DICK ***\r\n\
+            licence stuff\r\n";
+        assert_eq!(strip_gutenberg(book), "Call me Ishmael.");
+        assert_eq!(strip_gutenberg("*** START OF THIS PROJECT GUTENBERG EBOOK ***\nhi"), "hi");
+        assert_eq!(strip_gutenberg("  just some text\n"), "just some text");
+    }
+}
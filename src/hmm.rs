@@ -0,0 +1,487 @@
+use std::collections::BTreeMap;
+
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use {Chain, MarkovErr};
+
+// how often each thing happened. from training these are whole counts; Baum-Welch replaces them
+// with probabilities, which we treat the same way
+type Counts = BTreeMap<String, f64>;
+
+// Baum-Welch stops once an iteration improves the log-likelihood by less than this
+const CONVERGED: f64 = 1e-6;
+
+/*
+a hidden Markov model: a chain over states we don't get to see, each of which emits something we
+do (a part of speech emitting a word, weather emitting what someone did that day). it learns by
+counting, from sequences where the states have been labelled, or with Baum-Welch from sequences
+where they haven't. a Chain is the special case where every state always emits itself, and
+from_chain makes one of those.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Hmm {
+    // how many sequences started in each state
+    starts: Counts,
+    // how many times each state followed each state
+    transitions: BTreeMap<String, Counts>,
+    // how many times each state emitted each observation
+    emissions: BTreeMap<String, Counts>
+}
+
+impl Hmm {
+    pub fn new() -> Hmm {
+        Hmm::default()
+    }
+
+    /*
+    counts one labelled sequence of (state, observation) pairs. EmptyCorpus if it's empty.
+    */
+    pub fn train<S: AsRef<str>, O: AsRef<str>>(&mut self, sequence: impl IntoIterator<Item = (S, O)>) -> Result<(), MarkovErr> {
+        let mut previous: Option<String> = None;
+        for (state, observation) in sequence {
+            let state = state.as_ref().to_string();
+            let counts = match previous {
+                Some(ref p) => self.transitions.entry(p.clone()).or_default(),
+                None => &mut self.starts
+            };
+            *counts.entry(state.clone()).or_insert(0.0) += 1.0;
+            *self.emissions.entry(state.clone()).or_default().entry(observation.as_ref().to_string()).or_insert(0.0) += 1.0;
+            previous = Some(state);
+        }
+        if previous.is_some() { Ok(()) } else { Err(MarkovErr::EmptyCorpus) }
+    }
+
+    /*
+    a model with the given states and observations and random probabilities, for Baum-Welch to
+    start from. the same seed always gives the same model.
+    */
+    pub fn random(states: &[impl AsRef<str>], observations: &[impl AsRef<str>], seed: u64) -> Hmm {
+        let mut rng = StdRng::seed_from_u64(seed);
+        // uniform probabilities would be a fixed point, since no state would have a reason to
+        // specialize, so every weight is a random one between 1 and 2 instead
+        let mut weights = |keys: &[&str]| -> Counts {
+            keys.iter().map(|k| (k.to_string(), rng.gen_range(1.0, 2.0))).collect()
+        };
+        let states: Vec<&str> = states.iter().map(|s| s.as_ref()).collect();
+        let observations: Vec<&str> = observations.iter().map(|o| o.as_ref()).collect();
+        let mut hmm = Hmm::new();
+        hmm.starts = weights(&states);
+        for state in &states {
+            let next = weights(&states);
+            hmm.transitions.insert(state.to_string(), next);
+            let emits = weights(&observations);
+            hmm.emissions.insert(state.to_string(), emits);
+        }
+        hmm
+    }
+
+    /*
+    the chain as an HMM whose states are its words, each emitting the forms of itself we saw in
+    training (or just itself, if there's only ever been one)
+    */
+    pub fn from_chain(chain: &Chain) -> Hmm {
+        let mut hmm = Hmm::new();
+        hmm.starts = chain.starts.iter().map(|(w, &count)| (w.clone(), count.max(0) as f64)).collect();
+        for ((a, b), &count) in &chain.edges {
+            hmm.transitions.entry(a.clone()).or_default().insert(b.clone(), count.max(0) as f64);
+        }
+        for state in chain.vocab() {
+            let emits = match chain.surfaces.get(&state) {
+                Some(forms) => forms.iter().map(|(form, &count)| (form.clone(), count.max(0) as f64)).collect(),
+                None => Some((state.clone(), 1.0)).into_iter().collect()
+            };
+            hmm.emissions.insert(state, emits);
+        }
+        hmm
+    }
+
+    /*
+    every state we've seen, sorted
+    */
+    pub fn states(&self) -> Vec<String> {
+        self.emissions.keys().cloned().collect()
+    }
+
+    /*
+    the probability that a sequence starts in state
+    */
+    pub fn start_probability(&self, state: &str) -> f64 {
+        probability(&self.starts, state)
+    }
+
+    /*
+    the probability that state b comes next, given we're in state a
+    */
+    pub fn transition_probability(&self, a: &str, b: &str) -> f64 {
+        self.transitions.get(a).map_or(0.0, |next| probability(next, b))
+    }
+
+    /*
+    the probability that state emits observation
+    */
+    pub fn emission_probability(&self, state: &str, observation: &str) -> f64 {
+        self.emissions.get(state).map_or(0.0, |emits| probability(emits, observation))
+    }
+
+    /*
+    walks len states from a start, emitting as it goes, and returns the (state, observation)
+    pairs. stops early at a state nothing ever followed. EmptyCorpus if we haven't been trained.
+    */
+    pub fn sample(&self, len: usize) -> Result<Vec<(String, String)>, MarkovErr> {
+        let mut rng = thread_rng();
+        let mut state = pick(&self.starts, &mut rng).ok_or(MarkovErr::EmptyCorpus)?;
+        let mut out = vec![];
+        while out.len() < len {
+            let observation = self.emissions.get(state).and_then(|emits| pick(emits, &mut rng));
+            out.push((state.clone(), observation.cloned().unwrap_or_default()));
+            state = match self.transitions.get(state).and_then(|next| pick(next, &mut rng)) {
+                Some(next) => next,
+                None => break
+            };
+        }
+        Ok(out)
+    }
+
+    /*
+    the natural log of the probability that the model emits observations, summed over every path
+    of states that could have. negative infinity if none could.
+    */
+    pub fn log_likelihood(&self, observations: &[impl AsRef<str>]) -> f64 {
+        let params = Params::new(self);
+        let observations: Vec<&str> = observations.iter().map(|o| o.as_ref()).collect();
+        match params.forward(&observations) {
+            Ok((_, scales)) => scales.iter().map(|c| c.ln()).sum(),
+            Err(_) => f64::NEG_INFINITY
+        }
+    }
+
+    /*
+    learns the model's probabilities from unlabelled sequences of observations with Baum-Welch
+    (expectation maximization), starting from the ones it has now, e.g. from random or from
+    training on a few labelled sequences. runs until an iteration stops helping or for at most
+    iterations, and returns the sequences' total log-likelihood before the last update.
+    transitions and emissions the model gives no probability stay impossible, so every
+    observation has to be one some state can emit: NotSeen if it isn't, EmptyCorpus if there
+    aren't any sequences.
+    */
+    pub fn baum_welch(&mut self, sequences: &[Vec<String>], iterations: usize) -> Result<f64, MarkovErr> {
+        let sequences: Vec<Vec<&str>> = sequences.iter()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.iter().map(|o| o.as_str()).collect())
+            .collect();
+        if sequences.is_empty() {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+
+        let mut params = Params::new(self);
+        let n = params.states.len();
+        let mut previous = f64::NEG_INFINITY;
+        let mut likelihood = previous;
+        for _ in 0..iterations {
+            let mut starts = vec![0.0; n];
+            let mut transitions = vec![vec![0.0; n]; n];
+            let mut emissions: Vec<Counts> = vec![Counts::new(); n];
+            likelihood = 0.0;
+            for sequence in &sequences {
+                let (alpha, scales) = params.forward(sequence)?;
+                let beta = params.backward(sequence, &scales);
+                likelihood += scales.iter().map(|c| c.ln()).sum::<f64>();
+
+                for (t, observation) in sequence.iter().enumerate() {
+                    for i in 0..n {
+                        // how likely it is we were in state i at t, given everything we saw
+                        let gamma = alpha[t][i] * beta[t][i];
+                        if t == 0 {
+                            starts[i] += gamma;
+                        }
+                        *emissions[i].entry(observation.to_string()).or_insert(0.0) += gamma;
+                        if t + 1 < sequence.len() {
+                            let emit = params.emission(sequence[t + 1]);
+                            for j in 0..n {
+                                transitions[i][j] += alpha[t][i] * params.transitions[i][j] * emit[j] * beta[t + 1][j] / scales[t + 1];
+                            }
+                        }
+                    }
+                }
+            }
+
+            params.starts = normalized(starts);
+            params.transitions = transitions.into_iter().zip(&params.transitions)
+                .map(|(row, old)| if row.iter().sum::<f64>() > 0.0 { normalized(row) } else { old.clone() })
+                .collect();
+            for (i, emits) in emissions.into_iter().enumerate() {
+                let total: f64 = emits.values().sum();
+                if total > 0.0 {
+                    params.emissions[i] = emits.into_iter().map(|(o, p)| (o, p / total)).collect();
+                }
+            }
+
+            if likelihood - previous < CONVERGED {
+                break;
+            }
+            previous = likelihood;
+        }
+        params.store(self);
+        Ok(likelihood)
+    }
+
+    /*
+    the likeliest sequence of states to have emitted observations (the Viterbi path). NotSeen
+    with the first observation no state could have emitted after the ones before it, or
+    EmptyCorpus if there weren't any observations.
+    */
+    pub fn viterbi(&self, observations: &[impl AsRef<str>]) -> Result<Vec<String>, MarkovErr> {
+        let params = Params::new(self);
+        let states = &params.states;
+        let ln = |row: &[f64]| -> Vec<f64> { row.iter().map(|p| p.ln()).collect() };
+        let transitions: Vec<Vec<f64>> = params.transitions.iter().map(|row| ln(row)).collect();
+
+        let first = observations.first().ok_or(MarkovErr::EmptyCorpus)?.as_ref();
+        // best[i]: the log-probability of the likeliest path so far ending in states[i]
+        let mut best: Vec<f64> = ln(&params.starts).iter().zip(ln(&params.emission(first))).map(|(s, e)| s + e).collect();
+        // back[t][i]: the state before states[i] on that path, at observation t + 1
+        let mut back: Vec<Vec<usize>> = vec![];
+        check(&best, first)?;
+
+        for observation in &observations[1..] {
+            let observation = observation.as_ref();
+            let emit = ln(&params.emission(observation));
+            let mut next = vec![f64::NEG_INFINITY; states.len()];
+            let mut from = vec![0; states.len()];
+            for (j, &e) in emit.iter().enumerate() {
+                if e == f64::NEG_INFINITY {
+                    continue;
+                }
+                for (i, row) in transitions.iter().enumerate() {
+                    let score = best[i] + row[j] + e;
+                    if score > next[j] {
+                        next[j] = score;
+                        from[j] = i;
+                    }
+                }
+            }
+            check(&next, observation)?;
+            best = next;
+            back.push(from);
+        }
+
+        let mut state = (0..states.len()).max_by(|&i, &j| best[i].total_cmp(&best[j])).unwrap_or(0);
+        let mut path = vec![states[state].clone()];
+        for from in back.iter().rev() {
+            state = from[state];
+            path.push(states[state].clone());
+        }
+        path.reverse();
+        Ok(path)
+    }
+}
+
+/*
+the model as dense probability tables over its states, for the dynamic programming in
+log_likelihood, baum_welch and viterbi
+*/
+struct Params {
+    states: Vec<String>,
+    starts: Vec<f64>,
+    // transitions[i][j]: the probability of going from states[i] to states[j]
+    transitions: Vec<Vec<f64>>,
+    emissions: Vec<Counts>
+}
+
+impl Params {
+    fn new(hmm: &Hmm) -> Params {
+        let states = hmm.states();
+        let starts = states.iter().map(|s| hmm.start_probability(s)).collect();
+        let transitions = states.iter().map(|a| states.iter().map(|b| hmm.transition_probability(a, b)).collect()).collect();
+        let emissions = states.iter()
+            .map(|s| hmm.emissions[s].keys().map(|o| (o.clone(), hmm.emission_probability(s, o))).collect())
+            .collect();
+        Params { states, starts, transitions, emissions }
+    }
+
+    // each state's probability of emitting observation
+    fn emission(&self, observation: &str) -> Vec<f64> {
+        self.emissions.iter().map(|emits| emits.get(observation).map_or(0.0, |&p| p)).collect()
+    }
+
+    /*
+    the forward probabilities, with each step scaled to sum to 1 so long sequences don't
+    underflow, and the scale of each step. the scales multiply to the probability of the whole
+    sequence. NotSeen with the first observation nothing could have emitted.
+    */
+    fn forward(&self, observations: &[&str]) -> Result<(Vec<Vec<f64>>, Vec<f64>), MarkovErr> {
+        let n = self.states.len();
+        let mut alpha: Vec<Vec<f64>> = vec![];
+        let mut scales = vec![];
+        for (t, observation) in observations.iter().enumerate() {
+            let emit = self.emission(observation);
+            let mut step: Vec<f64> = (0..n)
+                .map(|j| match alpha.last() {
+                    None => self.starts[j],
+                    Some(previous) => (0..n).map(|i| previous[i] * self.transitions[i][j]).sum()
+                } * emit[j])
+                .collect();
+            let scale: f64 = step.iter().sum();
+            if scale <= 0.0 {
+                return Err(MarkovErr::NotSeen{w: observations[t].to_string()});
+            }
+            step.iter_mut().for_each(|p| *p /= scale);
+            alpha.push(step);
+            scales.push(scale);
+        }
+        Ok((alpha, scales))
+    }
+
+    // the backward probabilities, scaled by the forward pass's scales
+    fn backward(&self, observations: &[&str], scales: &[f64]) -> Vec<Vec<f64>> {
+        let n = self.states.len();
+        let mut beta = vec![vec![1.0; n]; observations.len()];
+        for t in (0..observations.len().saturating_sub(1)).rev() {
+            let emit = self.emission(observations[t + 1]);
+            for i in 0..n {
+                beta[t][i] = (0..n).map(|j| self.transitions[i][j] * emit[j] * beta[t + 1][j]).sum::<f64>() / scales[t + 1];
+            }
+        }
+        beta
+    }
+
+    // writes the tables back into hmm, as probabilities
+    fn store(self, hmm: &mut Hmm) {
+        let states = self.states;
+        let keep = |p: &f64| *p > 0.0;
+        hmm.starts = states.iter().cloned().zip(self.starts).filter(|(_, p)| keep(p)).collect();
+        hmm.transitions = states.iter()
+            .zip(self.transitions)
+            .map(|(a, row)| (a.clone(), states.iter().cloned().zip(row).filter(|(_, p)| keep(p)).collect()))
+            .collect();
+        hmm.emissions = states.iter().cloned()
+            .zip(self.emissions)
+            .map(|(s, emits)| (s, emits.into_iter().filter(|(_, p)| keep(p)).collect()))
+            .collect();
+    }
+}
+
+fn normalized(weights: Vec<f64>) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return weights;
+    }
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+fn probability(counts: &Counts, key: &str) -> f64 {
+    let total: f64 = counts.values().sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    counts.get(key).map_or(0.0, |&count| count / total)
+}
+
+// whether any path made it to this observation
+fn check(scores: &[f64], observation: &str) -> Result<(), MarkovErr> {
+    if scores.iter().any(|&s| s > f64::NEG_INFINITY) {
+        Ok(())
+    } else {
+        Err(MarkovErr::NotSeen{w: observation.to_string()})
+    }
+}
+
+fn pick<'a, R: Rng>(counts: &'a Counts, rng: &mut R) -> Option<&'a String> {
+    let total: f64 = counts.values().sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut index = rng.gen_range(0.0, total);
+    for (key, &count) in counts {
+        if index < count {
+            return Some(key);
+        }
+        index -= count;
+    }
+    // rounding can leave index a hair past the end
+    counts.iter().rev().find(|&(_, &count)| count > 0.0).map(|(key, _)| key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather() -> Hmm {
+        let mut hmm = Hmm::new();
+        hmm.train(vec![("sunny", "walk"), ("sunny", "walk"), ("rainy", "read"), ("rainy", "read")]).unwrap();
+        hmm.train(vec![("sunny", "shop"), ("rainy", "read"), ("rainy", "shop")]).unwrap();
+        hmm
+    }
+
+    #[test]
+    fn test_train() {
+        let hmm = weather();
+        assert_eq!(hmm.states(), vec!["rainy".to_string(), "sunny".to_string()]);
+        assert_eq!(hmm.start_probability("sunny"), 1.0);
+        assert_eq!(hmm.transition_probability("sunny", "rainy"), 2.0 / 3.0);
+        assert_eq!(hmm.emission_probability("rainy", "read"), 0.75);
+        assert_eq!(hmm.emission_probability("rainy", "walk"), 0.0);
+        assert_eq!(Hmm::new().train(Vec::<(&str, &str)>::new()), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_sample() {
+        let hmm = weather();
+        let sample = hmm.sample(10).unwrap();
+        assert_eq!(sample[0].0, "sunny");
+        assert!(sample.len() <= 10);
+        for (state, observation) in &sample {
+            assert!(hmm.emission_probability(state, observation) > 0.0);
+        }
+        assert_eq!(Hmm::new().sample(3), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_viterbi() {
+        let hmm = weather();
+        assert_eq!(hmm.viterbi(&["walk", "read", "shop"]), Ok(vec!["sunny".to_string(), "rainy".to_string(), "rainy".to_string()]));
+        assert_eq!(hmm.viterbi(&["walk", "swim"]), Err(MarkovErr::NotSeen{w: "swim".to_string()}));
+        assert_eq!(hmm.viterbi(&[] as &[&str]), Err(MarkovErr::EmptyCorpus));
+
+        // long enough that multiplying the probabilities out would underflow to nothing
+        let long: Vec<&str> = (0..2000).map(|i| if i < 1000 { "walk" } else { "read" }).collect();
+        let path = hmm.viterbi(&long).unwrap();
+        assert_eq!((path[0].as_str(), path[1999].as_str()), ("sunny", "rainy"));
+    }
+
+    #[test]
+    fn test_from_chain() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat").unwrap();
+        let hmm = Hmm::from_chain(&chain);
+        assert_eq!(hmm.start_probability("the"), 1.0);
+        assert_eq!(hmm.transition_probability("cat", "sat"), 1.0);
+        assert_eq!(hmm.emission_probability("sat", "sat"), 1.0);
+        assert_eq!(hmm.viterbi(&["the", "cat"]), Ok(vec!["the".to_string(), "cat".to_string()]));
+    }
+
+    #[test]
+    fn test_baum_welch() {
+        // two hidden states, one that mostly says a and one that mostly says b, which stick around
+        let sequences: Vec<Vec<String>> = ["aaaabbbbbaaaa", "bbbbaaaaabbb", "aaabbbbaaaab"].iter()
+            .map(|s| s.chars().map(|c| c.to_string()).collect())
+            .collect();
+        let mut hmm = Hmm::random(&["x", "y"], &["a", "b"], 7);
+        let before: f64 = sequences.iter().map(|s| hmm.log_likelihood(s)).sum();
+        hmm.baum_welch(&sequences, 100).unwrap();
+        let after: f64 = sequences.iter().map(|s| hmm.log_likelihood(s)).sum();
+        assert!(after > before);
+
+        // whichever state ended up saying a, it says little else and tends to stay put
+        let a = if hmm.emission_probability("x", "a") > 0.5 { "x" } else { "y" };
+        assert!(hmm.emission_probability(a, "a") > 0.9);
+        assert!(hmm.transition_probability(a, a) > 0.7);
+        let path = hmm.viterbi(&sequences[0]).unwrap();
+        assert_eq!(path[0], a);
+
+        let unknown = vec![vec!["c".to_string()]];
+        assert_eq!(hmm.baum_welch(&unknown, 10), Err(MarkovErr::NotSeen{w: "c".to_string()}));
+        assert_eq!(hmm.baum_welch(&[], 10), Err(MarkovErr::EmptyCorpus));
+    }
+}
@@ -0,0 +1,286 @@
+/*
+an append-only log of everything a chain has been trained on, so a bot that crashes can get its
+model back by replaying the log instead of going through its whole corpus again.
+
+a journal is JOURNAL_MAGIC and JOURNAL_VERSION (a little-endian u32), then one record per text: a
+tag byte, then for WEIGHTED records the weight, for SOURCED ones the weight and the source it was
+trained from (see train_from), then the text as (key, surface form) pairs. version 1 only had TEXT
+records, which count once, and version 2 didn't have SOURCED ones. we log the pairs rather than the
+raw text so replaying doesn't depend on the hooks being the same as they were. each record goes to
+the file in one write, and a record cut off by a crash is ignored on replay and trimmed off by
+journal_to. a record that's broken any other way makes the whole journal Corrupt.
+*/
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use format::{read_str, read_u32, read_u8, write_str, write_u32, write_u8};
+use {Chain, MarkovErr};
+
+pub const JOURNAL_MAGIC: &[u8; 4] = b"MRKJ";
+pub const JOURNAL_VERSION: u32 = 3;
+const HEADER_LEN: u64 = 8;
+const TEXT: u8 = 1;
+const WEIGHTED: u8 = 2;
+const SOURCED: u8 = 3;
+
+impl Chain {
+    /*
+    writes down every text we train on from now on at the end of the journal at path, creating it
+    if need be. pairs recorded with see or see_weighted aren't journaled. if the journal ends in a
+    record that was cut off partway through, the partial record is trimmed off first. a journal
+    that's damaged anywhere else is Corrupt and is left alone.
+    */
+    pub fn journal_to(&mut self, path: impl AsRef<Path>) -> Result<(), MarkovErr> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if file.metadata()?.len() == 0 {
+            let mut header = JOURNAL_MAGIC.to_vec();
+            write_u32(&mut header, JOURNAL_VERSION)?;
+            file.write_all(&header)?;
+        } else {
+            let end = records(&mut file, |_| {})?;
+            file.set_len(end)?;
+            // versions 2 and 3 only added record types, so an older journal just needs its header
+            // bumped before we start writing those
+            file.seek(SeekFrom::Start(4))?;
+            file.write_all(&JOURNAL_VERSION.to_le_bytes())?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        self.journal = Some(file);
+        Ok(())
+    }
+
+    /*
+    stops journaling, if we were
+    */
+    pub fn close_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /*
+    trains on every text in the journal at path, in order, and returns how many there were. this
+    doesn't write them to our own journal, if we have one.
+    */
+    pub fn replay(&mut self, path: impl AsRef<Path>) -> Result<usize, MarkovErr> {
+        let mut texts = vec![];
+        records(&mut File::open(path)?, |text| texts.push(text))?;
+        let n = texts.len();
+        for (keyed, weight, source) in texts {
+            self.learn(keyed, weight, source.as_deref());
+        }
+        Ok(n)
+    }
+}
+
+pub(crate) fn append(journal: &mut File, keyed: &[(String, String)], weight: u32, source: Option<&str>) -> Result<(), MarkovErr> {
+    let mut record = vec![];
+    match source {
+        Some(source) => {
+            write_u8(&mut record, SOURCED)?;
+            write_u32(&mut record, weight)?;
+            write_str(&mut record, source)?;
+        },
+        None if weight == 1 => write_u8(&mut record, TEXT)?,
+        None => {
+            write_u8(&mut record, WEIGHTED)?;
+            write_u32(&mut record, weight)?;
+        }
+    }
+    write_u32(&mut record, keyed.len() as u32)?;
+    for (key, surface) in keyed {
+        write_str(&mut record, key)?;
+        write_str(&mut record, surface)?;
+    }
+    journal.write_all(&record)?;
+    Ok(())
+}
+
+/*
+reads a journal from the start, handing each complete record to found, and returns where the last
+complete one ends. only a record that runs off the end of the file counts as cut off; one that
+doesn't parse for any other reason is Corrupt.
+*/
+fn records<F: FnMut(Record)>(file: &mut File, mut found: F) -> Result<u64, MarkovErr> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes)?;
+    if bytes.len() < HEADER_LEN as usize || &bytes[..4] != JOURNAL_MAGIC {
+        return Err(MarkovErr::Corrupt);
+    }
+    let mut input = &bytes[4..];
+    match read_u32(&mut input)? {
+        1..=3 => {},
+        v => return Err(MarkovErr::UnsupportedVersion{v})
+    }
+
+    let mut end = HEADER_LEN;
+    let mut input = Tail { input, short: false };
+    while !input.input.is_empty() {
+        match read_text(&mut input) {
+            Ok(text) => found(text),
+            // cut off by a crash, so everything up to here is all there is
+            Err(_) if input.short => break,
+            Err(e) => return Err(e)
+        }
+        end = (bytes.len() - input.input.len()) as u64;
+    }
+    Ok(end)
+}
+
+// the rest of a journal, noting whether anything ever asked for more of it than there was
+struct Tail<'a> {
+    input: &'a [u8],
+    short: bool
+}
+
+impl<'a> Read for Tail<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.input.read(buf)?;
+        self.short |= n < buf.len();
+        Ok(n)
+    }
+}
+
+// a text as its (key, surface form) pairs, its weight, and where it came from
+type Record = (Vec<(String, String)>, u32, Option<String>);
+
+fn read_text<R: Read>(input: &mut R) -> Result<Record, MarkovErr> {
+    let (weight, source) = match read_u8(input)? {
+        TEXT => (1, None),
+        WEIGHTED => (read_u32(input)?, None),
+        SOURCED => (read_u32(input)?, Some(read_str(input)?)),
+        _ => return Err(MarkovErr::Corrupt)
+    };
+    let keyed = (0..read_u32(input)?)
+        .map(|_| Ok((read_str(input)?, read_str(input)?)))
+        .collect::<Result<_, MarkovErr>>()?;
+    Ok((keyed, weight, source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use {Case, TrainConfig};
+
+    fn temp_path(name: &str) -> ::std::path::PathBuf {
+        ::std::env::temp_dir().join(format!("markov-test-{}-{}.mrkj", ::std::process::id(), name))
+    }
+
+    fn config() -> TrainConfig {
+        TrainConfig { case: Case::FoldKeys, ..TrainConfig::default() }
+    }
+
+    #[test]
+    fn test_replay() {
+        let path = temp_path("replay");
+        let _ = fs::remove_file(&path);
+        let mut chain = Chain::with_config(config());
+        chain.journal_to(&path).unwrap();
+        chain.train("The cat sat").unwrap();
+        chain.train_sequences(vec!["the Dog sat", ""]).unwrap();
+        chain.see("cat", "dog");
+        chain.close_journal();
+
+        let mut recovered = Chain::with_config(config());
+        assert_eq!(recovered.replay(&path), Ok(2));
+        fs::remove_file(&path).unwrap();
+        assert!(recovered.has_transition("the", "dog"));
+        assert!(!recovered.has_transition("cat", "dog"));
+        assert_eq!(recovered.starts, chain.starts);
+        assert_eq!(recovered.surfaces, chain.surfaces);
+    }
+
+    #[test]
+    fn test_truncated_journal() {
+        let path = temp_path("truncated");
+        let _ = fs::remove_file(&path);
+        let mut chain = Chain::new();
+        chain.journal_to(&path).unwrap();
+        chain.train("one two three").unwrap();
+        chain.train("four five six").unwrap();
+        chain.close_journal();
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 2).unwrap();
+
+        assert_eq!(Chain::new().replay(&path), Ok(1));
+
+        // picking the journal back up trims off the partial record before adding to it
+        chain.journal_to(&path).unwrap();
+        chain.train("seven eight").unwrap();
+        chain.close_journal();
+        let mut recovered = Chain::new();
+        assert_eq!(recovered.replay(&path), Ok(2));
+        fs::remove_file(&path).unwrap();
+        assert!(recovered.has_transition("seven", "eight"));
+        assert!(!recovered.contains("five"));
+    }
+
+    #[test]
+    fn test_corrupt_journal() {
+        let path = temp_path("corrupt");
+        let _ = fs::remove_file(&path);
+        let mut chain = Chain::new();
+        chain.journal_to(&path).unwrap();
+        chain.train("one two three").unwrap();
+        let first = fs::metadata(&path).unwrap().len();
+        chain.train("four five six").unwrap();
+        chain.train("seven eight").unwrap();
+        chain.close_journal();
+        let len = fs::metadata(&path).unwrap().len();
+
+        // an unknown tag on the second record, with a perfectly good one after it
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[first as usize] = 9;
+        fs::write(&path, &bytes).unwrap();
+
+        assert_eq!(Chain::new().replay(&path), Err(MarkovErr::Corrupt));
+        assert_eq!(Chain::new().journal_to(&path), Err(MarkovErr::Corrupt));
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_weighted_journal() {
+        let path = temp_path("weighted");
+        let _ = fs::remove_file(&path);
+        let mut chain = Chain::new();
+        chain.journal_to(&path).unwrap();
+        chain.train_weighted("the cat sat", 3).unwrap();
+        chain.train("the dog sat").unwrap();
+        chain.close_journal();
+
+        let mut recovered = Chain::new();
+        assert_eq!(recovered.replay(&path), Ok(2));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(recovered.edges, chain.edges);
+        assert_eq!(recovered.starts, chain.starts);
+    }
+
+    #[test]
+    fn test_sourced_journal() {
+        let path = temp_path("sourced");
+        let _ = fs::remove_file(&path);
+        let mut chain = Chain::new();
+        chain.journal_to(&path).unwrap();
+        chain.train_from("diary.txt", "the cat sat").unwrap();
+        chain.train("the dog sat").unwrap();
+        chain.close_journal();
+
+        let mut recovered = Chain::new();
+        assert_eq!(recovered.replay(&path), Ok(2));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(recovered.edges, chain.edges);
+        assert_eq!(recovered.sources, chain.sources);
+        assert_eq!(recovered.sources("cat", "sat"), vec!["diary.txt"]);
+    }
+
+    #[test]
+    fn test_not_a_journal() {
+        let path = temp_path("bogus");
+        fs::write(&path, b"hello there").unwrap();
+        let result = Chain::new().replay(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result, Err(MarkovErr::Corrupt));
+    }
+}
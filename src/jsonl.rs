@@ -0,0 +1,60 @@
+use std::io::BufRead;
+
+use serde_json::Value;
+
+use {Chain, MarkovErr};
+
+impl Chain {
+    /*
+    trains on JSON Lines, one JSON value per line, taking the text to train on from the field at
+    pointer (a JSON pointer, e.g. "/text" or "/post/body"). each line is its own text. lines
+    that don't have a string there are skipped, but if none do, that's MissingColumn. lines that
+    aren't JSON are Corrupt. returns how many lines had words in them.
+    */
+    pub fn train_jsonl<R: BufRead>(&mut self, reader: R, pointer: &str) -> Result<usize, MarkovErr> {
+        let mut found = false;
+        let mut trained = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(&line).map_err(|_| MarkovErr::Corrupt)?;
+            let text = match value.pointer(pointer).and_then(Value::as_str) {
+                Some(text) => text,
+                None => continue
+            };
+            found = true;
+            match self.train(text) {
+                Ok(()) => trained += 1,
+                Err(MarkovErr::EmptyCorpus) => {},
+                Err(e) => return Err(e)
+            }
+        }
+        match (found, trained) {
+            (false, _) => Err(MarkovErr::MissingColumn{c: pointer.to_string()}),
+            (true, 0) => Err(MarkovErr::EmptyCorpus),
+            _ => Ok(trained)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_train_jsonl() {
+        let jsonl = r#"{"post": {"body": "hello there bob"}}
+{"post": {"body": null}}
+
+{"post": {"body": "hello again"}}
+"#;
+        let mut chain = Chain::new();
+        assert_eq!(chain.train_jsonl(jsonl.as_bytes(), "/post/body"), Ok(2));
+        assert!(chain.has_transition("hello", "again"));
+        assert_eq!(chain.train_jsonl(jsonl.as_bytes(), "/text"), Err(MarkovErr::MissingColumn{c: "/text".to_string()}));
+        assert_eq!(chain.train_jsonl("{\"text\": \"!!\"}".as_bytes(), "/text"), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(chain.train_jsonl("not json".as_bytes(), "/text"), Err(MarkovErr::Corrupt));
+    }
+}
@@ -0,0 +1,108 @@
+use {Classifier, MarkovErr};
+
+/*
+tells which language a text is in from how its letters follow each other, learned from a sample
+of each language. a paragraph or two of each is plenty: "th" and "ng" give English away, "ij"
+Dutch and "sch" German, long before any of the words have to match. the samples and texts are
+read a character at a time, lowercased, with each run of anything but letters counting as one
+space, so words' first and last letters count too.
+*/
+pub struct LanguageId {
+    classifier: Classifier
+}
+
+impl Default for LanguageId {
+    fn default() -> LanguageId {
+        LanguageId::new()
+    }
+}
+
+impl LanguageId {
+    pub fn new() -> LanguageId {
+        LanguageId { classifier: Classifier::new() }
+    }
+
+    /*
+    learns what language looks like from sample, which can be added to with more samples later.
+    EmptyCorpus if sample has no letters.
+    */
+    pub fn train(&mut self, language: &str, sample: &str) -> Result<(), MarkovErr> {
+        let chars = characters(sample);
+        if chars.len() <= 2 {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+        self.classifier.train_tokens(language, chars)
+    }
+
+    /*
+    the languages we've seen samples of, sorted
+    */
+    pub fn languages(&self) -> Vec<String> {
+        self.classifier.classes()
+    }
+
+    /*
+    every language with the natural log of how likely it is to have produced text, best first
+    (see Classifier::scores)
+    */
+    pub fn scores(&self, text: &str) -> Vec<(String, f64)> {
+        self.classifier.scores_tokens(characters(text))
+    }
+
+    /*
+    the language text is most likely in. EmptyCorpus if text has no letters or we haven't seen
+    any samples.
+    */
+    pub fn identify(&self, text: &str) -> Result<String, MarkovErr> {
+        if !text.chars().any(char::is_alphabetic) {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+        self.classifier.classify_tokens(characters(text))
+    }
+}
+
+// text's letters, lowercased, with a space for each gap between them and one at either end
+fn characters(text: &str) -> Vec<String> {
+    let mut out = vec![" ".to_string()];
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphabetic() {
+            out.push(c.to_string());
+        } else if out.last().is_some_and(|last| last != " ") {
+            out.push(" ".to_string());
+        }
+    }
+    if out.last().is_some_and(|last| last != " ") {
+        out.push(" ".to_string());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify() {
+        let mut languages = LanguageId::new();
+        languages.train("en", "The quick brown fox jumps over the lazy dog. She said that they would \
+            be coming home with their friends in the evening, though nobody was sure of it.").unwrap();
+        languages.train("fr", "Le renard brun saute par-dessus le chien paresseux. Elle a dit qu'ils \
+            rentreraient ce soir avec leurs amis, mais personne n'en était sûr.").unwrap();
+        languages.train("de", "Der schnelle braune Fuchs springt über den faulen Hund. Sie sagte, dass \
+            sie am Abend mit ihren Freunden nach Hause kommen würden, doch niemand war sicher.").unwrap();
+        assert_eq!(languages.languages(), vec!["de".to_string(), "en".to_string(), "fr".to_string()]);
+
+        assert_eq!(languages.identify("where is the nearest station"), Ok("en".to_string()));
+        assert_eq!(languages.identify("nous sommes arrivés avec les enfants"), Ok("fr".to_string()));
+        assert_eq!(languages.identify("ich weiß nicht, wo mein Schlüssel ist"), Ok("de".to_string()));
+        assert_eq!(languages.identify("1234 !!"), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(languages.train("xx", "..."), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(LanguageId::new().identify("hello"), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_characters() {
+        assert_eq!(characters("Hi, yo!").concat(), " hi yo ");
+        assert_eq!(characters("").concat(), " ");
+    }
+}
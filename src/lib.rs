@@ -1,110 +1,1362 @@
 extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "sled-store")]
+extern crate sled;
+#[cfg(feature = "stem")]
+extern crate rust_stemmers;
+#[cfg(feature = "server")]
+extern crate tiny_http;
+#[cfg(feature = "fetch")]
+extern crate ureq;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "csv")]
+extern crate csv;
+#[cfg(feature = "unicode")]
+extern crate unicode_normalization;
+#[cfg(feature = "graphemes")]
+extern crate unicode_segmentation;
+#[cfg(feature = "graph")]
+extern crate petgraph;
+#[cfg(feature = "nalgebra")]
+extern crate nalgebra;
+#[cfg(feature = "compact")]
+extern crate smallvec;
 
-use std::collections::HashMap;
-use rand::{thread_rng, Rng};
+mod absorption;
+mod alphabet;
+mod analysis;
+mod anomaly;
+#[cfg(feature = "archive")]
+mod archive;
+mod batch;
+mod bytes;
+mod cache;
+mod chat;
+mod classify;
+#[cfg(feature = "compact")]
+mod compact;
+mod corpus;
+mod dot;
+mod evaluate;
+mod format;
+mod frozen;
+mod fuzzy;
+#[cfg(feature = "graph")]
+mod graph;
+mod gutenberg;
+mod hmm;
+mod journal;
+mod language;
+#[cfg(feature = "nalgebra")]
+mod linalg;
+mod mashup;
+#[cfg(feature = "json")]
+mod jsonl;
+mod ngram;
+#[cfg(feature = "cmudict")]
+mod phonemes;
+#[cfg(feature = "pos")]
+mod pos;
+mod provenance;
+mod reverse;
+#[cfg(feature = "server")]
+mod server;
+mod session;
+mod shard;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sled-store")]
+mod sled_store;
+mod stats;
+mod store;
+mod stream;
+mod subtitles;
+mod suggest;
+#[cfg(feature = "csv")]
+mod table;
+mod trace;
+#[cfg(feature = "unicode")]
+mod unicode;
+mod vocab;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+
+use cache::SuccessorCache;
+#[cfg(feature = "stem")]
+use rust_stemmers::Stemmer;
+#[cfg(feature = "graphemes")]
+use unicode_segmentation::UnicodeSegmentation;
+
+pub use alphabet::AlphabetChain;
+#[cfg(feature = "archive")]
+pub use archive::{archive_posts, ArchiveConfig};
+pub use batch::{BatchConfig, GenResult, Selection};
+pub use bytes::ByteChain;
+pub use chat::{chat_messages, ChatConfig};
+pub use classify::Classifier;
+#[cfg(feature = "compact")]
+pub use compact::CompactChain;
+pub use corpus::{novelty, CorpusIndex, Novelty};
+pub use evaluate::{held_out_perplexity, split_held_out, OrderScore};
+pub use format::FORMAT_VERSION;
+pub use frozen::FrozenChain;
+pub use gutenberg::strip_gutenberg;
+pub use hmm::Hmm;
+pub use language::LanguageId;
+pub use mashup::{blend, mashup, Switch};
+pub use ngram::NgramModel;
+#[cfg(feature = "cmudict")]
+pub use phonemes::PhonemeChain;
+#[cfg(feature = "pos")]
+pub use pos::heuristic_tagger;
+#[cfg(feature = "fetch")]
+pub use gutenberg::fetch_gutenberg;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+#[cfg(feature = "sled-store")]
+pub use sled_store::SledStore;
+#[cfg(feature = "server")]
+pub use server::serve;
+pub use session::GenerationSession;
+pub use shard::ShardedChain;
+pub use stats::Stats;
+pub use store::{ChainStore, MemoryStore, StoredChain};
+pub use stream::Words;
+pub use subtitles::subtitle_cues;
+pub use trace::TraceStep;
+#[cfg(feature = "stem")]
+pub use rust_stemmers::Algorithm;
+
+// what words outside a capped vocabulary get counted as. it can't clash with a real word, since
+// the default tokenizer never keeps '<' or '>'.
+pub const UNK: &str = "<UNK>";
 
 #[derive(Debug, PartialEq)]
 pub enum MarkovErr {
     Error,
     NotImplemented,
-    NotSeen{w: String}
+    NotSeen{w: String},
+    // the training text had no words in it once we'd cleaned it up
+    EmptyCorpus,
+    // we need the training texts, but the chain wasn't trained with index_corpus
+    NotIndexed,
+    // we need to go backwards, but the chain wasn't trained with reverse
+    NotReversed,
+    // reading or writing a model file failed
+    Io{e: String},
+    // what we tried to load isn't a model file, or was cut off partway through
+    Corrupt,
+    // the model file was saved by a newer version of the crate than this one
+    UnsupportedVersion{v: u32},
+    // a ChainStore's backend failed
+    Store{e: String},
+    // the input we were asked to train on doesn't have the column (or field) we were told to use
+    MissingColumn{c: String}
+}
+
+/*
+where a random walk lands when it teleports instead of following an edge
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Teleport {
+    // any word we've seen a successor for, all equally likely
+    Uniform,
+    // a word that started one of the training texts, weighted by how often it did
+    Start,
+    // any word we've seen a successor for, weighted by how often we have, so output without a
+    // seed doesn't keep starting on one-off rarities
+    Frequency
+}
+
+/*
+a closure in a config. it only exists so the config can still be cloned and printed.
+*/
+pub struct Hook<F: ?Sized>(pub Arc<F>);
+
+impl<F: ?Sized> Clone for Hook<F> {
+    fn clone(&self) -> Hook<F> {
+        Hook(self.0.clone())
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hook(..)")
+    }
+}
+
+// maps a token onto the one we should count it as, if any
+pub type Normalize = Hook<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+// decides whether a character is part of a word
+pub type KeepChar = Hook<dyn Fn(char) -> bool + Send + Sync>;
+
+// decides whether a word is worth counting
+pub type TokenFilter = Hook<dyn Fn(&str) -> bool + Send + Sync>;
+
+// tags a text's words with their parts of speech, one tag per word (see TrainConfig::tagger)
+#[cfg(feature = "pos")]
+pub type Tagger = Hook<dyn Fn(&[String]) -> Vec<String> + Send + Sync>;
+
+// decides whether the output so far is finished
+pub type StopFn = Arc<dyn Fn(&[String]) -> bool + Send + Sync>;
+
+/*
+when generation stops. it always stops early at a dead end.
+*/
+#[derive(Clone)]
+pub enum Stop {
+    // this many words, seed included
+    Words(usize),
+    // at least min words and at most max, stopping at the first end token once we have min.
+    // end tokens are words that ended a training text plus the config's stop_tokens.
+    Range{min: usize, max: usize},
+    // whenever done says the output so far is finished, or at max words if it never does
+    When{max: usize, done: StopFn}
+}
+
+impl fmt::Debug for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stop::Words(n) => write!(f, "Words({})", n),
+            Stop::Range{min, max} => write!(f, "Range {{ min: {}, max: {} }}", min, max),
+            Stop::When{max, ..} => write!(f, "When {{ max: {}, .. }}", max)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GenConfig {
+    // how the output starts. this can be a whole prompt, in which case we carry on from its last
+    // word and the output includes all of it. if None, we start wherever a teleport would land.
+    pub seed: Option<String>,
+    pub stop: Stop,
+    // with no seed, only words this returns true for can start the output, e.g. to skip
+    // stopwords, or to insist on a capital letter so the output starts like a sentence. it's given
+    // each word as generation would emit it (see Case::FoldKeys). it doesn't restrict teleports
+    // partway through. if it rules out every word, generating without a seed is EmptyCorpus.
+    pub start_filter: Option<TokenFilter>,
+    // words that count as a good place to end, as far as Stop::Range is concerned
+    pub stop_tokens: HashSet<String>,
+    // stop before the output (words joined by single spaces) would run past this many characters,
+    // e.g. 500 for a toot. if even the seed doesn't fit, the output is empty.
+    pub max_chars: Option<usize>,
+    // probability of following an edge at each step. with probability (1 - damping) we teleport
+    // instead, PageRank-surfer style, which breaks us out of tight loops. 1.0 turns it off.
+    pub damping: f32,
+    pub teleport: Teleport,
+    // probability of ignoring the current word's successors at a step and picking any word we've
+    // seen a successor for instead, all equally likely, whatever teleport says. it keeps output
+    // from a tiny corpus from retracing the same few sentences. 0.0 turns it off.
+    pub epsilon: f32,
+    // words generated within the last repeat_window steps have their weight multiplied by
+    // repeat_penalty, so the output stops ping-ponging between the same couple of words.
+    // a window of 0 turns it off.
+    pub repeat_window: usize,
+    pub repeat_penalty: f32,
+    // OpenAI-style penalties for words already in the output, however long ago: a word's logit
+    // (the log of its weight) goes down by presence_penalty if it's appeared at all, plus
+    // frequency_penalty for every time it has. so its weight is multiplied by
+    // e^-(presence_penalty + frequency_penalty * times). 0.0 turns each off; negative values
+    // encourage repeats instead.
+    pub presence_penalty: f32,
+    pub frequency_penalty: f32,
+    // successors with less than this share of a word's (raw) weight aren't considered at all,
+    // which gets rid of the noise of words seen after it only once or twice without pruning the
+    // chain. the likeliest successor always stays in. 0.0 turns it off.
+    pub min_p: f32,
+    // never emit an n-gram of this many words that's already appeared in the output. if that
+    // leaves nowhere to go, generation stops early, the same as it does at a dead end. 0 turns it off.
+    pub no_repeat_ngram: usize,
+    // never copy more than this many words in a row word for word from a training text, so a bot
+    // trained on private or copyrighted text can't quote it at length. words that would make a
+    // longer run aren't candidates, and if that leaves nowhere to go, generation stops early. needs
+    // a chain trained with index_corpus. 0 turns it off.
+    pub max_verbatim: usize,
+    // whether a word may be followed by itself. if that's the only way forward, we stop early.
+    pub self_loops: bool,
+    // multipliers for the weight of particular words whenever they're a candidate, which steers
+    // the output towards (or away from) a topic without retraining. e.g. {"jungle": 5.0}
+    pub bias: HashMap<String, f32>,
+    // never emit UNK, the stand-in for words a capped vocabulary left out (see Chain::cap_vocab).
+    // if it's the only way forward, we stop early.
+    pub skip_unk: bool,
+    // seeds the random number generator, so the same chain and config always generate the same
+    // thing (on the same version of the crate). None picks a fresh seed every time.
+    pub rng_seed: Option<u64>,
+    // the most steps (words or teleports) one generation takes, whatever stop says, so a stop
+    // condition that's never satisfied can't keep us going forever. we stop early when we hit it.
+    pub max_steps: usize,
+    // stop early once generating has taken this long. for a batch, no new candidate is started
+    // once the whole batch has taken this long.
+    pub timeout: Option<Duration>
+}
+
+impl Default for GenConfig {
+    fn default() -> GenConfig {
+        GenConfig {
+            seed: None,
+            stop: Stop::Words(10),
+            start_filter: None,
+            stop_tokens: HashSet::new(),
+            max_chars: None,
+            damping: 1.0,
+            teleport: Teleport::Uniform,
+            epsilon: 0.0,
+            repeat_window: 0,
+            repeat_penalty: 1.0,
+            presence_penalty: 0.0,
+            frequency_penalty: 0.0,
+            min_p: 0.0,
+            no_repeat_ngram: 0,
+            max_verbatim: 0,
+            self_loops: true,
+            bias: HashMap::new(),
+            skip_unk: false,
+            rng_seed: None,
+            max_steps: 100_000,
+            timeout: None
+        }
+    }
+}
+
+/*
+what we do about upper case letters when splitting text into words
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Case {
+    // lowercase everything, so "The" and "the" are the same word
+    Fold,
+    // keep case as-is, for corpora where it means something (code, chemistry)
+    Preserve,
+    // count "The" and "the" as the same word, but remember which we saw most so that's what
+    // generation emits
+    FoldKeys
 }
 
-struct Chain {
-    nodes: HashMap<String, i32>,
-    edges: HashMap<(String, String), i32>
+/*
+the Unicode normal form we put text into before splitting it up, so a word typed two different
+ways ("café" with é as one character, or as e followed by a combining accent) counts as one word
+*/
+#[cfg(feature = "unicode")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnicodeForm {
+    // canonical composition, which only merges what's the same character written differently
+    Nfc,
+    // compatibility composition, which also merges look-alikes: the "ﬁ" ligature and "fi",
+    // full-width "Ａ" and "A"
+    Nfkc
+}
+
+/*
+how we split text into words
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tokenizer {
+    // split on whitespace and keep the characters keep_chars says to
+    Words,
+    // source code: identifiers (letters, digits and underscores), common operators like == and ->,
+    // and every other bit of punctuation on its own. keep_chars doesn't apply.
+    Code,
+    // every character on its own, for a character-level chain. each run of whitespace counts as
+    // a single " ", so joining the output back up with nothing in between reads as text.
+    // keep_chars doesn't apply.
+    Chars,
+    // the same as Chars, but each token is a whole grapheme cluster (what a reader sees as one
+    // character), so an emoji with a skin tone, or a letter followed by a combining accent, isn't
+    // broken into pieces that don't mean anything on their own
+    #[cfg(feature = "graphemes")]
+    Graphemes
+}
+
+// the operators Tokenizer::Code keeps together, longest first so "..=" beats ".."
+const OPERATORS: [&str; 24] = [
+    "<<=", ">>=", "...", "..=", "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "->", "=>", "::",
+    "+=", "-=", "*=", "/=", "<<", ">>", "++", "--", ".."
+];
+
+#[derive(Debug, Clone)]
+pub struct TrainConfig {
+    // whether to record a word following itself ("very very"). corpora full of these make the
+    // chain emit long runs of the same word.
+    pub self_loops: bool,
+    // whether to link the last word of each training text back around to its first. that
+    // guarantees every word has somewhere to go, but fabricates a transition the text never had.
+    pub wrap_around: bool,
+    // whether to remember the training texts word for word, so we can check generated output
+    // against them. costs about as much memory again as the texts themselves.
+    pub index_corpus: bool,
+    // whether to keep a second chain with every pair backwards, so we can ask what comes before a
+    // word and generate right to left
+    pub reverse: bool,
+    // how many words ahead count as following a word. above 1, we also record skips to the words
+    // after the next one, weighted less the further away they are, which smooths out models
+    // trained on small corpora.
+    pub window: usize,
+    // runs on every token before we count it, and on seeds and prompts before we generate from
+    // them. return a different word to map the token onto it (stemming, slang, spelling fixes) or
+    // None to drop it entirely.
+    pub normalize: Option<Normalize>,
+    pub case: Case,
+    // how many words' successor lists generation remembers between steps, most recently used
+    // first, rather than building each one again every time a walk comes back to it. 0 turns it off.
+    pub successor_cache: usize,
+    // which characters we keep when splitting text into words; the rest are stripped out of the
+    // word they're in. None keeps ASCII letters only.
+    pub keep_chars: Option<KeepChar>,
+    // runs on every word as we split text up (before normalize), dropping the ones it returns
+    // false for, e.g. anything shorter than 2 letters or made up of digits
+    pub filter: Option<TokenFilter>,
+    pub tokenizer: Tokenizer,
+    // stems every token (after normalize) before we count it, so "run", "runs" and "running" all
+    // share statistics. generation still emits whichever form of each stem we saw most.
+    #[cfg(feature = "stem")]
+    pub stem: Option<Algorithm>,
+    // tags each text's words (after normalize and stemming) with their parts of speech, and counts
+    // each word under word/TAG, so "run/VERB" and "run/NOUN" are different states. a first order
+    // chain then knows a verb tends to follow "to", not just which words did. generation still
+    // emits the plain words. see heuristic_tagger for one that needs no model.
+    #[cfg(feature = "pos")]
+    pub tagger: Option<Tagger>,
+    // the normal form text is put into before we split it up, for corpora mixed together from
+    // sources that encode accents differently
+    #[cfg(feature = "unicode")]
+    pub unicode: Option<UnicodeForm>,
+    // strips accents and other combining marks off letters before we split text up, so "café"
+    // and "cafe" are the same word
+    #[cfg(feature = "unicode")]
+    pub fold_diacritics: bool
+}
+
+impl Default for TrainConfig {
+    fn default() -> TrainConfig {
+        TrainConfig {
+            self_loops: true,
+            wrap_around: false,
+            index_corpus: false,
+            reverse: false,
+            window: 1,
+            normalize: None,
+            case: Case::Fold,
+            successor_cache: 0,
+            keep_chars: None,
+            filter: None,
+            tokenizer: Tokenizer::Words,
+            #[cfg(feature = "stem")]
+            stem: None,
+            #[cfg(feature = "pos")]
+            tagger: None,
+            #[cfg(feature = "unicode")]
+            unicode: None,
+            #[cfg(feature = "unicode")]
+            fold_diacritics: false
+        }
+    }
+}
+
+impl TrainConfig {
+    /*
+    a config for training on source code, for generating pseudo-code: Tokenizer::Code, and case
+    kept as-is since it means something in identifiers
+    */
+    pub fn code() -> TrainConfig {
+        TrainConfig { tokenizer: Tokenizer::Code, case: Case::Preserve, ..TrainConfig::default() }
+    }
+}
+
+/*
+how much of a text a chain knows the words for
+*/
+#[derive(Debug, PartialEq)]
+pub struct Coverage {
+    // how many words the text had, once split up the same way we split training texts
+    pub total: usize,
+    // how many of those we've seen before
+    pub known: usize,
+    // the ones we haven't, in the order they first appear
+    pub unknown: Vec<String>
+}
+
+impl Coverage {
+    /*
+    the fraction of the text's words we know. 1.0 for an empty text, since nothing's missing.
+    */
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.known as f64 / self.total as f64
+    }
+}
+
+pub struct Chain {
+    config: TrainConfig,
+    nodes: BTreeMap<String, i32>,
+    edges: BTreeMap<(String, String), i32>,
+    // how many times we've seen each word come after something, so we know about words that
+    // never lead anywhere without going through every edge
+    incoming: BTreeMap<String, i32>,
+    starts: BTreeMap<String, i32>,
+    ends: BTreeMap<String, i32>,
+    // when the words we count aren't the words in the text (stems, say), how many times we've
+    // seen each form of each one
+    surfaces: BTreeMap<String, BTreeMap<String, i32>>,
+    // which sources each pair was seen in, for texts trained with train_from
+    sources: BTreeMap<(String, String), BTreeSet<String>>,
+    corpus: Option<CorpusIndex>,
+    reverse: Option<Box<Chain>>,
+    // once the vocabulary's capped, the words we still count as themselves
+    known: Option<HashSet<String>>,
+    // where we write down every text we train on, if anywhere (see journal_to)
+    journal: Option<File>,
+    cache: Mutex<SuccessorCache>,
+    // how many batches are holding on to the cache right now (see hold_cache)
+    batches: AtomicUsize
+}
+
+impl Default for Chain {
+    fn default() -> Chain {
+        Chain::new()
+    }
 }
 
 impl Chain {
-    fn new() -> Chain {
+    pub fn new() -> Chain {
+        Chain::with_config(TrainConfig::default())
+    }
+
+    pub fn with_config(config: TrainConfig) -> Chain {
+        let reverse = if config.reverse { Some(Box::new(Chain::new())) } else { None };
         Chain {
-            nodes: HashMap::new(),
-            edges: HashMap::new()
+            config,
+            nodes: BTreeMap::new(),
+            edges: BTreeMap::new(),
+            incoming: BTreeMap::new(),
+            starts: BTreeMap::new(),
+            ends: BTreeMap::new(),
+            surfaces: BTreeMap::new(),
+            sources: BTreeMap::new(),
+            corpus: None,
+            reverse,
+            known: None,
+            journal: None,
+            cache: Mutex::new(SuccessorCache::default()),
+            batches: AtomicUsize::new(0)
         }
     }
 
     /*
     marks an ordered string pair as seen once
     */
-    fn see(&mut self, a: &str, b: &str) {
-        let key = (a.to_string(), b.to_string());
-        let counter = self.nodes.entry(a.to_string()).or_insert(0);
-        let weight = self.edges.entry(key).or_insert(0);
-        *counter += 1;
-        *weight += 1;
+    pub fn see(&mut self, a: &str, b: &str) {
+        self.see_weighted(a, b, 1);
+    }
+
+    /*
+    marks an ordered string pair as seen count times at once, for bulk imports or for texts that
+    should count for more than others
+    */
+    pub fn see_weighted(&mut self, a: &str, b: &str, count: u32) {
+        if count == 0 {
+            return;
+        }
+        self.clear_cache();
+        let n = count.min(i32::MAX as u32) as i32;
+        count_in(&mut self.nodes, a.to_string(), n);
+        count_in(&mut self.edges, (a.to_string(), b.to_string()), n);
+        count_in(&mut self.incoming, b.to_string(), n);
+        if let Some(ref mut reverse) = self.reverse {
+            reverse.see_weighted(b, a, count);
+        }
+    }
+
+    /*
+    sees a pair from a training text, subject to the training config
+    */
+    fn observe(&mut self, a: &str, b: &str, count: u32, source: Option<&str>) {
+        if a == b && !self.config.self_loops {
+            return;
+        }
+        self.see_weighted(a, b, count);
+        if let Some(source) = source {
+            self.sources.entry((a.to_string(), b.to_string())).or_default().insert(source.to_string());
+        }
+    }
+
+    /*
+    records every consecutive word pair in the input, and optionally the pair wrapping the last word
+    back around to the first
+    */
+    pub fn train(&mut self, input: &str) -> Result<(), MarkovErr> {
+        self.train_weighted(input, 1)
+    }
+
+    /*
+    the same as train, but the text counts weight times over, so a recent or trustworthy source
+    can outweigh old or scrappy ones. a weight of 0 doesn't count it at all.
+    */
+    pub fn train_weighted(&mut self, input: &str, weight: u32) -> Result<(), MarkovErr> {
+        let words = self.split(input);
+        self.train_tokens_weighted(words, weight)
+    }
+
+    /*
+    the same as train, but for text someone else has already split up (or for tokens that were
+    never text to begin with). the tokens are used as given, apart from the config's normalize hook.
+    */
+    pub fn train_tokens(&mut self, tokens: impl IntoIterator<Item = impl AsRef<str>>) -> Result<(), MarkovErr> {
+        self.train_tokens_weighted(tokens, 1)
+    }
+
+    /*
+    train_tokens, with a weight as for train_weighted
+    */
+    pub fn train_tokens_weighted(&mut self, tokens: impl IntoIterator<Item = impl AsRef<str>>, weight: u32) -> Result<(), MarkovErr> {
+        let keyed = self.keyed(tokens);
+        self.train_keyed(keyed, weight, None)
+    }
+
+    /*
+    trains on a text that's already been turned into (key, surface form) pairs, crediting every
+    pair it has to source, if there is one
+    */
+    fn train_keyed(&mut self, keyed: Vec<(String, String)>, weight: u32, source: Option<&str>) -> Result<(), MarkovErr> {
+        if keyed.is_empty() {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+        if weight == 0 {
+            return Ok(());
+        }
+        if let Some(ref mut journal) = self.journal {
+            journal::append(journal, &keyed, weight, source)?;
+        }
+        self.learn(keyed, weight, source);
+        Ok(())
+    }
+
+    /*
+    counts a text that's already been turned into (key, surface form) pairs, weight times over
+    */
+    fn learn(&mut self, keyed: Vec<(String, String)>, weight: u32, source: Option<&str>) {
+        // train_tokens never gets this far with nothing, but a damaged journal could
+        if keyed.is_empty() {
+            return;
+        }
+        let times = weight.min(i32::MAX as u32) as i32;
+        if self.records_surfaces() {
+            for (key, surface) in keyed.iter().filter(|(key, _)| key != UNK) {
+                count_in(self.surfaces.entry(key.clone()).or_default(), surface.clone(), times);
+            }
+        }
+        let words: Vec<String> = keyed.into_iter().map(|(key, _)| key).collect();
+
+        if self.config.index_corpus {
+            self.corpus.get_or_insert_with(CorpusIndex::new).add(&words);
+        }
+
+        // with a window of w, the next word counts w times, the one after that w - 1 times, and so on
+        let window = self.config.window.max(1);
+        for (i, a) in words.iter().enumerate() {
+            for (d, b) in words[i + 1..].iter().take(window).enumerate() {
+                self.observe(a, b, ((window - d) as u32).saturating_mul(weight), source);
+            }
+        }
+        let first = &words[0];
+        let last = &words[words.len() - 1];
+        if self.config.wrap_around {
+            self.observe(last, first, (window as u32).saturating_mul(weight), source);
+        }
+        if let Some(ref mut reverse) = self.reverse {
+            count_in(&mut reverse.starts, last.clone(), times);
+            count_in(&mut reverse.ends, first.clone(), times);
+        }
+        count_in(&mut self.starts, first.clone(), times);
+        count_in(&mut self.ends, last.clone(), times);
+    }
+
+    /*
+    trains on many small texts at once, each its own sequence with its own start and end, so we
+    don't invent transitions from the end of one to the start of the next. texts with no words
+    in them are skipped; it's only an error if they all were.
+    */
+    pub fn train_sequences(&mut self, texts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<(), MarkovErr> {
+        self.train_sequences_weighted(texts.into_iter().map(|text| (text, 1)))
+    }
+
+    /*
+    train_sequences, with a weight for each text as for train_weighted
+    */
+    pub fn train_sequences_weighted(&mut self, texts: impl IntoIterator<Item = (impl AsRef<str>, u32)>) -> Result<(), MarkovErr> {
+        let mut trained = false;
+        for (text, weight) in texts {
+            match self.train_weighted(text.as_ref(), weight) {
+                Ok(()) => trained = true,
+                Err(MarkovErr::EmptyCorpus) => {},
+                Err(e) => return Err(e)
+            }
+        }
+        if trained { Ok(()) } else { Err(MarkovErr::EmptyCorpus) }
+    }
+
+    /*
+    splits text into words the same way we split training texts
+    */
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        self.normalize(self.split(text))
+    }
+
+    /*
+    splits text into tokens, before any normalizing
+    */
+    fn split(&self, text: &str) -> Vec<String> {
+        #[cfg(feature = "unicode")]
+        let normalized = unicode::normalize(text, self.config.unicode, self.config.fold_diacritics);
+        #[cfg(feature = "unicode")]
+        let text = &*normalized;
+        let text = match self.config.case {
+            Case::Fold => text.to_lowercase(),
+            Case::Preserve | Case::FoldKeys => text.to_string()
+        };
+        let keep = |c: char| match self.config.keep_chars {
+            Some(Hook(ref keep)) => keep(c),
+            None => c.is_ascii_alphabetic()
+        };
+        let words: Vec<String> = match self.config.tokenizer {
+            Tokenizer::Words => text.split_whitespace()
+                .map(|word| word.chars().filter(|&c| keep(c)).collect::<String>())
+                .filter(|word| !word.is_empty())
+                .collect(),
+            Tokenizer::Code => split_code(&text),
+            Tokenizer::Chars => {
+                let text = text.trim();
+                split_chars(text.char_indices().map(|(i, c)| &text[i..i + c.len_utf8()]))
+            },
+            #[cfg(feature = "graphemes")]
+            Tokenizer::Graphemes => split_chars(text.trim().graphemes(true))
+        };
+        words.into_iter()
+            .filter(|word| match self.config.filter {
+                Some(Hook(ref filter)) => filter(word),
+                None => true
+            })
+            .collect()
+    }
+
+    /*
+    runs tokens through the config's normalize hook, dropping the ones it rejects, and returns the
+    keys we count the rest under
+    */
+    fn normalize(&self, tokens: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<String> {
+        self.keyed(tokens).into_iter().map(|(key, _)| key).collect()
+    }
+
+    /*
+    the same as normalize, but returns (key, surface form) pairs, where the surface form is the
+    token as it came out of the normalize hook
+    */
+    fn keyed(&self, tokens: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<(String, String)> {
+        let surfaces: Vec<String> = tokens.into_iter()
+            .filter_map(|t| match self.config.normalize {
+                Some(Hook(ref normalize)) => normalize(t.as_ref()),
+                None => Some(t.as_ref().to_string())
+            })
+            .collect();
+        // one stemmer for the whole text, rather than one per word
+        #[cfg(feature = "stem")]
+        let stemmer = self.config.stem.map(Stemmer::create);
+        #[allow(unused_mut)]
+        let mut keys: Vec<String> = surfaces.iter()
+            .map(|surface| {
+                let key = self.key(surface);
+                #[cfg(feature = "stem")]
+                {
+                    if let Some(ref stemmer) = stemmer {
+                        return stemmer.stem(&key).into_owned();
+                    }
+                }
+                key
+            })
+            .collect();
+        #[cfg(feature = "pos")]
+        {
+            if let Some(Hook(ref tagger)) = self.config.tagger {
+                // a word the tagger didn't give a tag keeps its plain key
+                for (key, tag) in keys.iter_mut().zip(tagger(&surfaces)) {
+                    *key = format!("{}/{}", key, tag);
+                }
+            }
+        }
+        keys.into_iter().map(|key| self.in_vocab(key)).zip(surfaces).collect()
+    }
+
+    /*
+    the word we count a normalized token as, before stemming
+    */
+    fn key(&self, token: &str) -> String {
+        match self.config.case {
+            Case::FoldKeys => token.to_lowercase(),
+            Case::Fold | Case::Preserve => token.to_string()
+        }
+    }
+
+    /*
+    whether keys can differ from the words in the text, so we need to remember which is which
+    */
+    fn records_surfaces(&self) -> bool {
+        if self.config.case == Case::FoldKeys {
+            return true;
+        }
+        #[cfg(feature = "stem")]
+        {
+            if self.config.stem.is_some() {
+                return true;
+            }
+        }
+        #[cfg(feature = "pos")]
+        {
+            if self.config.tagger.is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /*
+    swaps each key for the form of it we saw most in training, so output reads like the text did
+    */
+    fn surface(&self, words: Vec<String>) -> Vec<String> {
+        if self.surfaces.is_empty() {
+            return words;
+        }
+        words.into_iter()
+            .map(|w| {
+                let forms = match self.surfaces.get(&w) {
+                    Some(forms) => forms,
+                    None => return w
+                };
+                forms.iter()
+                    .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                    .map(|(form, _)| form.clone())
+                    .unwrap_or(w)
+            })
+            .collect()
+    }
+
+    /*
+    the training texts, if we were configured to remember them
+    */
+    pub fn corpus(&self) -> Option<&CorpusIndex> {
+        self.corpus.as_ref()
     }
 
     /*
     returns a random word, weighted by the probability that it is the next word to occur based on 
     what we've seen.
     */
-    fn next(&self, seed: &str) -> Result<String, MarkovErr> {
-        /*
-        This part gets a bit cray. We're going to simulate a slot machine to choose the next word.
-        We do this by picking a random value in the range [0..1) and using that as an index for
-        the output word.
-        Now, the way these indices work is you can think of all possible next words stacked with occurance proportional to
-        their probabilities. If there is only one possible next word, it will fill the full range p(w) = 1.0.
-        If there are two, equally likely words, they would each take up 0.5 of the range and so on.
-        We have the total number of occurences of our key word, so we simply iterate through all edges starting
-        at that word and add the probability of the the destination node to a running total.
-        As soon as we exceed our target value, we know that's the one we want.
-        */
-        let index: f32 = thread_rng().gen_range(0.0, 1.0);
-        let counter: i32 = *self.nodes.get(seed).unwrap_or(&0);
-        if counter == 0 {
-            return Err(MarkovErr::NotSeen{w: seed.to_string()});
-        }
-
-        let mut cursor: f32 = 0.0;
-        for key in self.edges.keys() {
-            if key.0 != seed {
-                continue;
+    pub fn next(&self, seed: &str) -> Result<String, MarkovErr> {
+        self.next_with(seed, &mut thread_rng())
+    }
+
+    fn next_with<R: Rng>(&self, seed: &str, rng: &mut R) -> Result<String, MarkovErr> {
+        let choices = self.successors(seed);
+        match pick(&choices, rng) {
+            Some(w) => Ok(w.clone()),
+            None => Err(MarkovErr::NotSeen{w: seed.to_string()})
+        }
+    }
+
+    /*
+    whether we've seen w anywhere, even if nothing ever followed it
+    */
+    pub fn contains(&self, w: &str) -> bool {
+        self.nodes.contains_key(w) || self.incoming.contains_key(w) || self.starts.contains_key(w)
+    }
+
+    /*
+    whether we've ever seen b come straight after a
+    */
+    pub fn has_transition(&self, a: &str, b: &str) -> bool {
+        self.edges.contains_key(&(a.to_string(), b.to_string()))
+    }
+
+    /*
+    every word we've seen, sorted
+    */
+    pub fn vocab(&self) -> Vec<String> {
+        let mut out: Vec<String> = self.nodes.keys()
+            .chain(self.starts.keys())
+            .chain(self.incoming.keys())
+            .collect::<HashSet<&String>>()
+            .into_iter()
+            .cloned()
+            .collect();
+        out.sort();
+        out
+    }
+
+    /*
+    checks which words of text we've never seen, so you can tell in advance whether it'll work
+    as a seed or prompt
+    */
+    pub fn coverage(&self, text: &str) -> Coverage {
+        let words = self.tokenize(text);
+        let mut unknown: Vec<String> = vec![];
+        let mut known = 0;
+        for word in &words {
+            if self.contains(word) {
+                known += 1;
+            } else if !unknown.contains(word) {
+                unknown.push(word.clone());
+            }
+        }
+        Coverage { total: words.len(), known, unknown }
+    }
+
+    /*
+    returns every word we've seen follow w, weighted by how many times we saw it
+    */
+    fn successors(&self, w: &str) -> Vec<(&String, f32)> {
+        if !self.nodes.contains_key(w) {
+            return vec![];
+        }
+        // pairs are sorted by their first word, so all of w's sit together starting at (w, "")
+        self.edges.range((w.to_string(), String::new())..)
+            .take_while(|&(key, _)| key.0 == w)
+            .map(|(key, weight)| (&key.1, *weight as f32))
+            .collect()
+    }
+
+    /*
+    chooses the word to follow everything generated so far, after applying the sampling options
+    in the config to the raw successor weights. Ok(None) means the constraints in the config ruled
+    out every word we could have picked.
+    */
+    fn step<R: Rng>(&self, out: &[String], config: &GenConfig, rng: &mut R) -> Result<Option<String>, MarkovErr> {
+        // if the penalties zeroed out everything, fall back to what the chain would have said anyway
+        self.weigh(out, config, |choices, allowed| pick(choices, rng).or_else(|| pick(allowed, rng)).cloned())
+    }
+
+    /*
+    works out the candidates to follow everything generated so far and hands them to decide, both
+    with the config's weightings and penalties applied (choices) and without (allowed). Ok(None)
+    without calling decide if there are no candidates.
+    */
+    fn weigh<T, F>(&self, out: &[String], config: &GenConfig, decide: F) -> Result<Option<T>, MarkovErr>
+        where F: FnOnce(&[(&String, f32)], &[(&String, f32)]) -> Option<T>
+    {
+        let w = &out[out.len() - 1];
+        let cached;
+        let mut allowed = if self.caching() {
+            cached = self.cached_successors(w);
+            cached.iter().map(|(next, weight)| (next, *weight)).collect()
+        } else {
+            self.successors(w)
+        };
+        if allowed.is_empty() {
+            // a word we've seen end a text is a natural place to stop, just not one we can go on from
+            return if self.contains(w) { Ok(None) } else { Err(MarkovErr::NotSeen{w: w.clone()}) };
+        }
+
+        let n = config.no_repeat_ngram;
+        if n > 0 && out.len() + 1 >= n {
+            let tail = &out[out.len() + 1 - n..];
+            allowed.retain(|&(next, _)| !repeats_ngram(out, tail, next));
+        }
+        let m = config.max_verbatim;
+        if m > 0 && out.len() >= m {
+            let corpus = self.corpus.as_ref().ok_or(MarkovErr::NotIndexed)?;
+            let mut run = out[out.len() - m..].to_vec();
+            run.push(String::new());
+            allowed.retain(|&(next, _)| {
+                run[m] = next.clone();
+                !corpus.contains(&run)
+            });
+        }
+        if !config.self_loops {
+            allowed.retain(|&(next, _)| next != w);
+        }
+        if config.skip_unk {
+            allowed.retain(|&(next, _)| next != UNK);
+        }
+        if allowed.is_empty() {
+            return Ok(None);
+        }
+        if config.min_p > 0.0 {
+            let total: f32 = allowed.iter().map(|&(_, weight)| weight).sum();
+            let top = allowed.iter().map(|&(_, weight)| weight).fold(0.0, f32::max);
+            allowed.retain(|&(_, weight)| weight == top || weight / total >= config.min_p);
+        }
+
+        let mut choices = allowed.clone();
+
+        for choice in choices.iter_mut() {
+            if let Some(boost) = config.bias.get(choice.0) {
+                choice.1 *= boost;
+            }
+        }
+        if config.repeat_window > 0 {
+            let recent = &out[out.len().saturating_sub(config.repeat_window)..];
+            for choice in choices.iter_mut() {
+                if recent.contains(choice.0) {
+                    choice.1 *= config.repeat_penalty;
+                }
+            }
+        }
+        if config.presence_penalty != 0.0 || config.frequency_penalty != 0.0 {
+            for choice in choices.iter_mut() {
+                let times = out.iter().filter(|w| *w == choice.0).count();
+                if times > 0 {
+                    choice.1 *= (-(config.presence_penalty + config.frequency_penalty * times as f32)).exp();
+                }
+            }
+        }
+
+        Ok(decide(&choices, &allowed))
+    }
+
+    /*
+    picks a word to jump to, ignoring whatever word we're currently on. None if we haven't been
+    trained on anything that could land us somewhere.
+    */
+    fn teleport<R: Rng>(&self, config: &GenConfig, rng: &mut R) -> Option<String> {
+        pick(&self.landings(config.teleport, config.skip_unk), rng).cloned()
+    }
+
+    /*
+    picks a word to start from when there's no seed: where a teleport would land, as long as the
+    config's start_filter allows it
+    */
+    fn start<R: Rng>(&self, config: &GenConfig, rng: &mut R) -> Option<String> {
+        let mut choices = self.landings(config.teleport, config.skip_unk);
+        if let Some(Hook(ref eligible)) = config.start_filter {
+            choices.retain(|&(w, _)| self.surface(vec![w.clone()]).first().is_some_and(|surface| eligible(surface)));
+        }
+        pick(&choices, rng).cloned()
+    }
+
+    /*
+    every word a teleport could land on, weighted by how likely it is to
+    */
+    fn landings(&self, teleport: Teleport, skip_unk: bool) -> Vec<(&String, f32)> {
+        let mut choices: Vec<(&String, f32)> = match teleport {
+            Teleport::Uniform => self.nodes.keys().map(|w| (w, 1.0)).collect(),
+            Teleport::Start => self.starts.iter().map(|(w, count)| (w, *count as f32)).collect(),
+            Teleport::Frequency => self.nodes.iter().map(|(w, count)| (w, *count as f32)).collect()
+        };
+        if skip_unk {
+            choices.retain(|&(w, _)| w != UNK);
+        }
+        choices
+    }
+
+    pub fn generate(&self, config: &GenConfig) -> Result<Vec<String>, MarkovErr> {
+        let prompt = config.seed.as_ref().map(|seed| self.tokenize(seed));
+        Ok(self.surface(self.generate_from(prompt, config, &mut rng(config))?))
+    }
+
+    /*
+    generates carrying on from a prompt that's already been split into keys, rather than the
+    config's seed. None starts wherever a teleport would land. the output is keys too.
+    */
+    fn generate_from<R: Rng>(&self, prompt: Option<Vec<String>>, config: &GenConfig, rng: &mut R) -> Result<Vec<String>, MarkovErr> {
+        self.generate_jumping(prompt, config, rng, &mut vec![])
+    }
+
+    /*
+    generate_from, also noting in jumps where in the output each word we teleported to (or
+    explored, see GenConfig::epsilon) went, rather than following an edge there
+    */
+    fn generate_jumping<R: Rng>(&self, prompt: Option<Vec<String>>, config: &GenConfig, rng: &mut R, jumps: &mut Vec<usize>) -> Result<Vec<String>, MarkovErr> {
+        let out = match prompt {
+            Some(words) => words,
+            None => self.start(config, rng).into_iter().collect()
+        };
+        if out.is_empty() {
+            return Err(match config.seed {
+                Some(ref seed) => MarkovErr::NotSeen{w: seed.clone()},
+                None => MarkovErr::EmptyCorpus
+            });
+        }
+
+        walk(out, config, rng, jumps, |_, _| self)
+    }
+
+    /*
+    whether out is long enough, as far as the config's stop condition is concerned
+    */
+    fn done(&self, out: &[String], config: &GenConfig) -> bool {
+        match config.stop {
+            Stop::Words(n) => out.len() >= n,
+            Stop::Range{min, max} => {
+                let last = &out[out.len() - 1];
+                let end = self.ends.contains_key(last) || config.stop_tokens.contains(last);
+                out.len() >= max || (out.len() >= min && end)
+            },
+            Stop::When{max, ref done} => out.len() >= max || done(out)
+        }
+    }
+
+    /*
+    the natural log of the probability of walking through words in order, starting from the first.
+    negative infinity if we've never seen one of the steps.
+    */
+    pub fn log_likelihood(&self, words: &[String]) -> f64 {
+        words.windows(2)
+            .map(|pair| {
+                let total = *self.nodes.get(&pair[0]).unwrap_or(&0);
+                let weight = *self.edges.get(&(pair[0].clone(), pair[1].clone())).unwrap_or(&0);
+                if weight == 0 {
+                    return f64::NEG_INFINITY;
+                }
+                (weight as f64 / total as f64).ln()
+            })
+            .sum()
+    }
+
+    /*
+    log_likelihood, but smoothed Witten-Bell style so that no step is impossible: a word followed
+    by t different words in c counts sets aside t / (c + t) of its probability for words in
+    proportion to how often we saw them at all, and those counts get one extra each over a
+    vocabulary of vocab words (plus one for anything new). the first word is smoothed the same
+    way, as if it followed the start of a text.
+    */
+    fn smoothed_log_likelihood(&self, words: &[String], vocab: usize) -> f64 {
+        let total: i32 = self.nodes.values().sum::<i32>() + self.ends.values().sum::<i32>();
+        let unigram = |w: &String| {
+            let seen = self.nodes.get(w).unwrap_or(&0) + self.ends.get(w).unwrap_or(&0);
+            (seen as f64 + 1.0) / (total as f64 + vocab as f64 + 1.0)
+        };
+        let smoothed = |seen: i32, count: i32, types: usize, w: &String| {
+            if count == 0 {
+                return unigram(w);
             }
+            (seen as f64 + types as f64 * unigram(w)) / (count as f64 + types as f64)
+        };
 
-            let weight: i32 = *self.edges.get(key).unwrap_or(&0);
-            cursor += weight as f32 / counter as f32;
-            if cursor > index {
-                return Ok(key.1.clone());
+        let start = words.first().map_or(0.0, |w| {
+            let seen = *self.starts.get(w).unwrap_or(&0);
+            smoothed(seen, self.starts.values().sum(), self.starts.len(), w).ln()
+        });
+        let steps: f64 = words.windows(2)
+            .map(|pair| {
+                let seen = *self.edges.get(&(pair[0].clone(), pair[1].clone())).unwrap_or(&0);
+                let types = self.edges.range((pair[0].clone(), String::new())..).take_while(|(key, _)| key.0 == pair[0]).count();
+                smoothed(seen, *self.nodes.get(&pair[0]).unwrap_or(&0), types, &pair[1]).ln()
+            })
+            .sum();
+        start + steps
+    }
+
+    /*
+    generates a response to an incoming message, markov-bot style. we seed from the rarest word in
+    the message that we know how to continue from, since rare words say more about what the
+    message was about than "the" does. if we don't know any of its words, we fall back to the
+    config's own seed.
+    */
+    pub fn reply(&self, message: &str, config: &GenConfig) -> Result<Vec<String>, MarkovErr> {
+        let mut best: Option<(String, i32)> = None;
+        for word in self.tokenize(message) {
+            if !self.nodes.contains_key(&word) {
+                continue;
+            }
+            let frequency = self.frequency(&word);
+            match best {
+                Some((_, f)) if f <= frequency => {},
+                _ => best = Some((word, frequency))
             }
         }
 
-        Err(MarkovErr::NotSeen{w: seed.to_string()})
+        match best {
+            Some((seed, _)) => self.generate(&GenConfig { seed: Some(seed), ..config.clone() }),
+            None => self.generate(config)
+        }
+    }
+
+    /*
+    roughly how many times we've seen w in training. every occurrence has a successor, a
+    predecessor or both, so the bigger of the two counts is within one per text of the truth.
+    */
+    fn frequency(&self, w: &str) -> i32 {
+        let outgoing = *self.nodes.get(w).unwrap_or(&0);
+        let incoming = *self.incoming.get(w).unwrap_or(&0);
+        outgoing.max(incoming)
+    }
+}
+
+/*
+carries out on until the config says it's done, taking each step with whichever chain current
+returns for the output so far. that's always the same chain, except in a mashup. the positions
+of words we jumped to rather than followed an edge to go in jumps.
+*/
+fn walk<'c, R: Rng>(mut out: Vec<String>, config: &GenConfig, rng: &mut R, jumps: &mut Vec<usize>, mut current: impl FnMut(&[String], &mut R) -> &'c Chain) -> Result<Vec<String>, MarkovErr> {
+    let mut chars = out.iter().map(|w| w.chars().count()).sum::<usize>() + out.len() - 1;
+    if config.max_chars.is_some_and(|max| chars > max) {
+        return Ok(vec![]);
+    }
+
+    let deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+    let mut steps = 0;
+    loop {
+        let chain = current(&out, rng);
+        if chain.done(&out, config) {
+            break;
+        }
+        if steps >= config.max_steps || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
+        steps += 1;
+        let jump = config.damping < 1.0 && rng.gen_range(0.0, 1.0) >= config.damping;
+        let explore = !jump && config.epsilon > 0.0 && rng.gen_range(0.0, 1.0) < config.epsilon;
+        let target = if jump {
+            chain.teleport(config, rng)
+        } else if explore {
+            pick(&chain.landings(Teleport::Uniform, config.skip_unk), rng).cloned()
+        } else {
+            None
+        };
+        let jumped = target.is_some();
+        let w = match target {
+            Some(target) => target,
+            None => match chain.step(&out, config, rng)? {
+                Some(next) => next,
+                // a dead end, or nothing we're allowed to say next, so this is as long as it gets
+                None => break
+            }
+        };
+        // plus one for the space in front of it
+        chars += w.chars().count() + 1;
+        if config.max_chars.is_some_and(|max| chars > max) {
+            break;
+        }
+        if jumped {
+            jumps.push(out.len());
+        }
+        out.push(w);
     }
+
+    Ok(out)
 }
 
-fn split(input: &str) -> Vec<String> {
-    let mut s = input.to_lowercase();
-    s.retain(|c| (c >= 'a' && c <= 'z') || c == ' ');
+/*
+splits source code into identifiers, operators and single punctuation characters
+*/
+fn split_code(text: &str) -> Vec<String> {
+    let ident = |c: char| c.is_alphanumeric() || c == '_';
     let mut out = vec![];
-    for word in s.split_whitespace() {
-        out.push(word.to_string());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        let len = if ident(c) {
+            rest.find(|c: char| !ident(c)).unwrap_or(rest.len())
+        } else {
+            OPERATORS.iter().find(|op| rest.starts_with(*op)).map_or(c.len_utf8(), |op| op.len())
+        };
+        out.push(rest[..len].to_string());
+        rest = &rest[len..];
     }
     out
 }
 
-pub fn gen(input: &str, init: &str, length: i32) -> Result<Vec<String>, MarkovErr> {
-    let mut chain = Chain::new();
-    let mut first = "".to_string();
-    let mut prev = "".to_string();
-    for word in split(input) {
-        if prev != "" {
-            chain.see(&prev, &word);
-        } else {
-            first = word.clone();
+/*
+turns characters (or grapheme clusters) into tokens, squashing each run of whitespace into one " "
+*/
+fn split_chars<'a>(units: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+    for unit in units {
+        if !unit.chars().all(char::is_whitespace) {
+            out.push(unit.to_string());
+        } else if out.last().is_some_and(|last| last != " ") {
+            out.push(" ".to_string());
+        }
+    }
+    out
+}
+
+/*
+adds n to key's count, topping out at i32::MAX rather than overflowing on a corpus that big
+*/
+fn count_in<K: Ord>(counts: &mut BTreeMap<K, i32>, key: K, n: i32) {
+    let count = counts.entry(key).or_insert(0);
+    *count = count.saturating_add(n);
+}
+
+/*
+whether appending next to out would produce an n-gram (tail followed by next) that's already in out
+*/
+fn repeats_ngram(out: &[String], tail: &[String], next: &String) -> bool {
+    let n = tail.len() + 1;
+    out.windows(n).any(|window| &window[..n - 1] == tail && &window[n - 1] == next)
+}
+
+/*
+chooses one of the given words at random, weighted by their share of the total
+*/
+fn pick<'a, R: Rng>(choices: &[(&'a String, f32)], rng: &mut R) -> Option<&'a String> {
+    /*
+    This part gets a bit cray. We're going to simulate a slot machine to choose the next word.
+    We do this by picking a random value in the range [0..1) and using that as an index for
+    the output word.
+    Now, the way these indices work is you can think of all possible next words stacked with occurance proportional to
+    their probabilities. If there is only one possible next word, it will fill the full range p(w) = 1.0.
+    If there are two, equally likely words, they would each take up 0.5 of the range and so on.
+    We have the total weight of all the choices, so we simply iterate through them and add the
+    probability of each one to a running total.
+    As soon as we exceed our target value, we know that's the one we want.
+    */
+    let total: f32 = choices.iter().map(|&(_, weight)| weight).sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let index: f32 = rng.gen_range(0.0, 1.0);
+    let mut cursor: f32 = 0.0;
+    for &(w, weight) in choices {
+        cursor += weight / total;
+        if cursor > index {
+            return Some(w);
         }
-        prev = word;
     }
-    chain.see(&prev, &first);
-    
-    let mut out = vec![init.to_string()];
-    let mut w = init.to_string();
-    for _ in 1..length {
-        w = chain.next(&w)?;
-        out.push(w.clone());
+
+    // floating point rounding can leave the cursor a hair under the index on the last choice
+    choices.iter().rev().find(|&&(_, weight)| weight > 0.0).map(|&(w, _)| w)
+}
+
+/*
+the random number generator a generation config asks for
+*/
+fn rng(config: &GenConfig) -> Box<dyn RngCore> {
+    match config.rng_seed {
+        Some(seed) => Box::new(StdRng::seed_from_u64(seed)),
+        None => Box::new(thread_rng())
     }
+}
 
-    Ok(out)
+pub fn gen(input: &str, init: &str, length: i32) -> Result<Vec<String>, MarkovErr> {
+    let mut chain = Chain::new();
+    chain.train(input)?;
+    chain.generate(&GenConfig {
+        seed: Some(init.to_string()),
+        stop: Stop::Words(length.max(0) as usize),
+        ..GenConfig::default()
+    })
 }
 
 #[cfg(test)]
@@ -121,9 +1373,59 @@ mod tests {
         assert_eq!(gen("hello bob", "hello", 2), Ok(vec!["hello".to_string(), "bob".to_string()]));
     }
 
+    #[test]
+    fn test_repeats_ngram() {
+        let out: Vec<String> = ["a", "b", "c", "a"].iter().map(|w| w.to_string()).collect();
+        assert!(repeats_ngram(&out, &out[3..], &"b".to_string()));
+        assert!(!repeats_ngram(&out, &out[3..], &"c".to_string()));
+        assert!(repeats_ngram(&out, &[], &"c".to_string()));
+    }
+
+    #[test]
+    fn test_empty_corpus() {
+        assert_eq!(gen("", "x", 3), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(gen(" \n\t ", "x", 3), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(gen("?!", "x", 3), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_single_word() {
+        assert_eq!(gen("hello", "hello", 3), Ok(vec!["hello".to_string()]));
+        assert_eq!(gen("hello", "bob", 2), Err(MarkovErr::NotSeen{w: "bob".to_string()}));
+    }
+
+    #[test]
+    fn test_degenerate_input() {
+        assert_eq!(gen("hello", "", 3), Err(MarkovErr::NotSeen{w: "".to_string()}));
+        assert_eq!(gen("hello", "hello", 0), Ok(vec!["hello".to_string()]));
+        assert_eq!(gen("hello", "hello", i32::MIN), Ok(vec!["hello".to_string()]));
+        assert_eq!(gen("\u{0}\u{feff} \u{202e}日本", "x", 3), Err(MarkovErr::EmptyCorpus));
+        let mut chain = Chain::new();
+        chain.see_weighted("a", "b", u32::MAX);
+        chain.see_weighted("a", "b", u32::MAX);
+        assert_eq!(chain.edges[&("a".to_string(), "b".to_string())], i32::MAX);
+        assert_eq!(chain.next("a"), Ok("b".to_string()));
+    }
+
+    #[test]
+    fn test_split_cased() {
+        let chain = Chain::with_config(TrainConfig { case: Case::Preserve, ..TrainConfig::default() });
+        assert_eq!(chain.split("NaCl, H2O!"), vec!["NaCl".to_string(), "HO".to_string()]);
+    }
+
     #[test]
     fn test_split() {
-        assert_eq!(split("Hello, world!"), vec!["hello".to_string(), "world".to_string()]);
+        assert_eq!(Chain::new().split("Hello, world!"), vec!["hello".to_string(), "world".to_string()]);
+    }
+
+    #[test]
+    fn test_split_filtered() {
+        let chain = Chain::with_config(TrainConfig {
+            keep_chars: Some(Hook(Arc::new(|c: char| c.is_alphanumeric()))),
+            filter: Some(Hook(Arc::new(|w: &str| w.len() >= 2 && !w.chars().all(|c| c.is_ascii_digit())))),
+            ..TrainConfig::default()
+        });
+        assert_eq!(chain.split("A café, 1984 times in r2d2"), vec!["café", "times", "in", "r2d2"]);
     }
 }
 
@@ -131,6 +1433,17 @@ mod tests {
 mod chain_tests {
     use super::*;
 
+    #[test]
+    fn test_see_weighted() {
+        let mut chain = Chain::new();
+        chain.see_weighted("pinned", "post", 10);
+        chain.see("pinned", "note");
+        chain.see_weighted("pinned", "nothing", 0);
+        assert_eq!(chain.nodes.get("pinned"), Some(&11));
+        assert_eq!(chain.edges.get(&("pinned".to_string(), "post".to_string())), Some(&10));
+        assert!(!chain.contains("nothing"));
+    }
+
     #[test]
     fn test_new() {
         let chain = Chain::new();
@@ -156,10 +1469,628 @@ mod chain_tests {
         assert_eq!(chain.edges.entry(("australian".to_string(), "kangaroo".to_string())).or_insert(0), &1);
     }
 
+    #[test]
+    fn test_train_without_self_loops() {
+        let mut chain = Chain::with_config(TrainConfig { self_loops: false, ..TrainConfig::default() });
+        chain.train("very very good").unwrap();
+        assert_eq!(chain.edges.get(&("very".to_string(), "very".to_string())), None);
+        assert_eq!(chain.edges.get(&("very".to_string(), "good".to_string())), Some(&1));
+    }
+
+    #[test]
+    fn test_train_wrap_around() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        assert_eq!(chain.edges.get(&("bob".to_string(), "hello".to_string())), None);
+
+        let mut chain = Chain::with_config(TrainConfig { wrap_around: true, ..TrainConfig::default() });
+        chain.train("hello bob").unwrap();
+        assert_eq!(chain.edges.get(&("bob".to_string(), "hello".to_string())), Some(&1));
+    }
+
+    #[test]
+    fn test_vocab() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("hello").unwrap();
+        let expected: Vec<String> = ["cat", "hello", "mat", "on", "sat", "the"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.vocab(), expected);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        let coverage = chain.coverage("The dog sat on the dog's mat");
+        assert_eq!(coverage.total, 7);
+        assert_eq!(coverage.known, 5);
+        assert_eq!(coverage.unknown, vec!["dog".to_string(), "dogs".to_string()]);
+        assert!((coverage.ratio() - 5.0 / 7.0).abs() < 1e-9);
+        assert_eq!(chain.coverage("").ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_contains() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        chain.train("alone").unwrap();
+        assert!(chain.contains("hello"));
+        assert!(chain.contains("bob"));
+        assert!(chain.contains("alone"));
+        assert!(!chain.contains("alice"));
+    }
+
+    #[test]
+    fn test_has_transition() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        assert!(chain.has_transition("hello", "bob"));
+        assert!(!chain.has_transition("bob", "hello"));
+        assert!(!chain.has_transition("hello", "alice"));
+    }
+
+    #[test]
+    fn test_train_tokens() {
+        let mut chain = Chain::new();
+        chain.train_tokens(vec!["Hello,", "World!"]).unwrap();
+        chain.train_tokens(vec!["World!".to_string(), "".to_string()]).unwrap();
+        assert!(chain.has_transition("Hello,", "World!"));
+        assert!(chain.has_transition("World!", ""));
+        assert_eq!(chain.train_tokens(Vec::<String>::new()), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_train_sequences() {
+        let mut chain = Chain::new();
+        chain.train_sequences(vec!["hello bob.", "", "goodbye alice."]).unwrap();
+        assert!(chain.has_transition("hello", "bob"));
+        assert!(!chain.has_transition("bob", "goodbye"));
+        assert_eq!(chain.starts.len(), 2);
+        assert_eq!(chain.ends.len(), 2);
+        assert_eq!(chain.train_sequences(vec!["", "?"]), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_train_window() {
+        let mut chain = Chain::with_config(TrainConfig { window: 2, ..TrainConfig::default() });
+        chain.train("a b c").unwrap();
+        assert_eq!(chain.edges.get(&("a".to_string(), "b".to_string())), Some(&2));
+        assert_eq!(chain.edges.get(&("a".to_string(), "c".to_string())), Some(&1));
+        assert_eq!(chain.edges.get(&("b".to_string(), "c".to_string())), Some(&2));
+        assert_eq!(chain.nodes.get("a"), Some(&3));
+        assert_eq!(chain.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let slang = |w: &str| match w {
+            "u" => Some("you".to_string()),
+            "um" => None,
+            _ => Some(w.to_string())
+        };
+        let mut chain = Chain::with_config(TrainConfig {
+            normalize: Some(Hook(Arc::new(slang))),
+            ..TrainConfig::default()
+        });
+        chain.train("um see u later").unwrap();
+        assert!(chain.has_transition("see", "you"));
+        assert!(!chain.contains("um"));
+        assert_eq!(chain.coverage("u").unknown, Vec::<String>::new());
+
+        let config = GenConfig {
+            seed: Some("see u".to_string()),
+            stop: Stop::Words(3),
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["see", "you", "later"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_case_preserve() {
+        let mut chain = Chain::with_config(TrainConfig { case: Case::Preserve, ..TrainConfig::default() });
+        chain.train("Fe and FE and fe").unwrap();
+        assert!(chain.has_transition("Fe", "and"));
+        assert!(chain.has_transition("and", "FE"));
+        assert!(!chain.contains("fe and"));
+        assert_eq!(chain.vocab().len(), 4);
+    }
+
+    #[test]
+    fn test_case_fold_keys() {
+        let mut chain = Chain::with_config(TrainConfig { case: Case::FoldKeys, ..TrainConfig::default() });
+        chain.train("Bob met Toby").unwrap();
+        chain.train("Bob met toby and Toby left").unwrap();
+        assert!(chain.has_transition("met", "toby"));
+        let config = GenConfig {
+            seed: Some("BOB".to_string()),
+            stop: Stop::Words(3),
+            bias: [("and".to_string(), 0.0)].iter().cloned().collect(),
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["Bob", "met", "Toby"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_token_filter() {
+        let mut chain = Chain::with_config(TrainConfig {
+            filter: Some(Hook(Arc::new(|w: &str| w.len() >= 2))),
+            ..TrainConfig::default()
+        });
+        chain.train("we saw a cat").unwrap();
+        assert!(chain.has_transition("saw", "cat"));
+        assert!(!chain.contains("a"));
+        assert_eq!(chain.train("a b c"), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_rng_seed() {
+        let train = || {
+            let mut chain = Chain::new();
+            chain.train("the cat sat on the mat and the dog sat on the cat and the bird flew off").unwrap();
+            chain
+        };
+        let config = GenConfig { stop: Stop::Words(30), rng_seed: Some(42), ..GenConfig::default() };
+        let first = train().generate(&config).unwrap();
+        for _ in 0..5 {
+            assert_eq!(train().generate(&config).unwrap(), first);
+        }
+
+        let batch = BatchConfig { gen: config, dedup: true, ..BatchConfig::default() };
+        let candidates = train().generate_many(5, &batch).unwrap();
+        assert_eq!(candidates.len(), 5);
+        assert_eq!(train().generate_many(5, &batch).unwrap(), candidates);
+    }
+
+    #[test]
+    fn test_train_weighted() {
+        let mut chain = Chain::new();
+        chain.train_weighted("the cat sat", 3).unwrap();
+        chain.train_sequences_weighted(vec![("the dog sat", 1), ("the cow sat", 0)]).unwrap();
+        assert_eq!(chain.edges[&("the".to_string(), "cat".to_string())], 3);
+        assert_eq!(chain.edges[&("the".to_string(), "dog".to_string())], 1);
+        assert_eq!(chain.starts["the"], 4);
+        assert!(!chain.contains("cow"));
+        assert_eq!(chain.train_weighted("!!", 2), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_code_tokenizer() {
+        let chain = Chain::with_config(TrainConfig::code());
+        let words = chain.split("if x_1 >= MAX { return a->b..=c; }");
+        let expected = ["if", "x_1", ">=", "MAX", "{", "return", "a", "->", "b", "..=", "c", ";", "}"];
+        assert_eq!(words, expected.iter().map(|w| w.to_string()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn test_chars_tokenizer() {
+        let chain = Chain::with_config(TrainConfig { tokenizer: Tokenizer::Chars, ..TrainConfig::default() });
+        assert_eq!(chain.split(" Hi,\t\n you "), vec!["h", "i", ",", " ", "y", "o", "u"]);
+        assert_eq!(chain.split("e\u{301}"), vec!["e", "\u{301}"]);
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn test_graphemes_tokenizer() {
+        let mut chain = Chain::with_config(TrainConfig { tokenizer: Tokenizer::Graphemes, ..TrainConfig::default() });
+        assert_eq!(chain.split("e\u{301}h \u{1f44d}\u{1f3fd}!"), vec!["e\u{301}", "h", " ", "\u{1f44d}\u{1f3fd}", "!"]);
+        chain.train("\u{1f44d}\u{1f3fd}\u{1f44d}\u{1f3fd}").unwrap();
+        assert!(chain.has_transition("\u{1f44d}\u{1f3fd}", "\u{1f44d}\u{1f3fd}"));
+        assert!(!chain.contains("\u{1f3fd}"));
+    }
+
+    #[test]
+    fn test_step_limits() {
+        let mut chain = Chain::new();
+        chain.train("a b a b").unwrap();
+        // this stop condition never fires, so only the limits end it
+        let never = Stop::When{max: usize::MAX, done: Arc::new(|_| false)};
+        let config = GenConfig { seed: Some("a".to_string()), stop: never, max_steps: 5, ..GenConfig::default() };
+        assert_eq!(chain.generate(&config).unwrap().len(), 6);
+
+        let config = GenConfig { max_steps: usize::MAX, timeout: Some(Duration::from_millis(20)), ..config };
+        assert!(!chain.generate(&config).unwrap().is_empty());
+        let batch = BatchConfig { gen: GenConfig { timeout: Some(Duration::from_secs(0)), ..config }, ..BatchConfig::default() };
+        assert_eq!(chain.generate_many(3, &batch), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_surface() {
+        let mut chain = Chain::new();
+        chain.surfaces.entry("run".to_string()).or_default().insert("running".to_string(), 2);
+        chain.surfaces.entry("run".to_string()).or_default().insert("runs".to_string(), 1);
+        let words = vec!["run".to_string(), "fast".to_string()];
+        assert_eq!(chain.surface(words), vec!["running".to_string(), "fast".to_string()]);
+    }
+
+    #[cfg(feature = "stem")]
+    #[test]
+    fn test_stem() {
+        let mut chain = Chain::with_config(TrainConfig { stem: Some(Algorithm::English), ..TrainConfig::default() });
+        chain.train("running dogs run").unwrap();
+        chain.train("running late").unwrap();
+        assert!(chain.has_transition("run", "dog"));
+        assert!(chain.has_transition("run", "late"));
+        let config = GenConfig {
+            seed: Some("runs".to_string()),
+            stop: Stop::Words(2),
+            bias: [("late".to_string(), 0.0)].iter().cloned().collect(),
+            ..GenConfig::default()
+        };
+        assert_eq!(chain.generate(&config), Ok(vec!["running".to_string(), "dogs".to_string()]));
+    }
+
     #[test]
     fn test_next() {
         let mut chain = Chain::new();
         chain.see("canadian", "hockey");
         assert_eq!(chain.next("canadian"), Ok("hockey".to_string()));
     }
+
+    #[test]
+    fn test_train_starts() {
+        let mut chain = Chain::new();
+        chain.train("the quick brown fox").unwrap();
+        chain.train("the lazy dog").unwrap();
+        assert_eq!(chain.starts.get("the"), Some(&2));
+        assert_eq!(chain.starts.len(), 1);
+    }
+
+    #[test]
+    fn test_generate_always_teleports() {
+        let mut chain = Chain::new();
+        chain.train("hello bob and alice").unwrap();
+        let config = GenConfig {
+            seed: Some("alice".to_string()),
+            stop: Stop::Words(4),
+            damping: 0.0,
+            teleport: Teleport::Start,
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["alice", "hello", "hello", "hello"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_generate_frequency_start() {
+        let mut chain = Chain::new();
+        chain.see_weighted("common", "word", 1000);
+        chain.see("rare", "word");
+        for i in 0..20 {
+            let config = GenConfig { stop: Stop::Words(1), teleport: Teleport::Frequency, rng_seed: Some(i), ..GenConfig::default() };
+            assert_eq!(chain.generate(&config), Ok(vec!["common".to_string()]));
+        }
+    }
+
+    #[test]
+    fn test_generate_start_filter() {
+        let mut chain = Chain::with_config(TrainConfig { case: Case::FoldKeys, ..TrainConfig::default() });
+        chain.train("the cat saw Bob and the dog").unwrap();
+        let capitalized = |w: &str| w.chars().next().is_some_and(char::is_uppercase);
+        let config = GenConfig {
+            stop: Stop::Words(2),
+            start_filter: Some(Hook(Arc::new(capitalized))),
+            ..GenConfig::default()
+        };
+        for _ in 0..10 {
+            assert_eq!(chain.generate(&config), Ok(vec!["Bob".to_string(), "and".to_string()]));
+        }
+
+        let config = GenConfig { start_filter: Some(Hook(Arc::new(|_: &str| false))), ..config };
+        assert_eq!(chain.generate(&config), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_generate_epsilon() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        chain.train("goodbye alice").unwrap();
+        let config = GenConfig {
+            seed: Some("hello".to_string()),
+            stop: Stop::Words(3),
+            epsilon: 1.0,
+            ..GenConfig::default()
+        };
+        for _ in 0..10 {
+            let out = chain.generate(&config).unwrap();
+            assert!(out[1..].iter().all(|w| w == "hello" || w == "goodbye"));
+        }
+    }
+
+    #[test]
+    fn test_generate_without_seed() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        let config = GenConfig {
+            stop: Stop::Words(2),
+            teleport: Teleport::Start,
+            ..GenConfig::default()
+        };
+        assert_eq!(chain.generate(&config), Ok(vec!["hello".to_string(), "bob".to_string()]));
+    }
+
+    #[test]
+    fn test_generate_repeat_penalty() {
+        let mut chain = Chain::new();
+        chain.see("ping", "pong");
+        chain.see("pong", "ping");
+        chain.see("pong", "pang");
+        chain.see("pang", "ping");
+        let config = GenConfig {
+            seed: Some("ping".to_string()),
+            stop: Stop::Words(3),
+            repeat_window: 2,
+            repeat_penalty: 0.0,
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["ping", "pong", "pang"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_generate_repeat_penalty_falls_back() {
+        let mut chain = Chain::new();
+        chain.see("very", "very");
+        let config = GenConfig {
+            seed: Some("very".to_string()),
+            stop: Stop::Words(3),
+            repeat_window: 2,
+            repeat_penalty: 0.0,
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["very", "very", "very"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_generate_presence_penalty() {
+        let mut chain = Chain::new();
+        chain.see_weighted("and", "again", 100);
+        chain.see("and", "then");
+        chain.see("again", "and");
+        chain.see("then", "and");
+        let config = GenConfig {
+            seed: Some("and".to_string()),
+            stop: Stop::Words(5),
+            presence_penalty: 50.0,
+            rng_seed: Some(7),
+            ..GenConfig::default()
+        };
+        let out = chain.generate(&config).unwrap();
+        assert_eq!(out[1..4], ["again".to_string(), "and".to_string(), "then".to_string()]);
+
+        let config = GenConfig { presence_penalty: 0.0, frequency_penalty: 50.0, ..config };
+        assert_eq!(chain.generate(&config).unwrap()[3], "then");
+    }
+
+    #[test]
+    fn test_generate_min_p() {
+        let mut chain = Chain::new();
+        chain.see_weighted("the", "cat", 8);
+        chain.see_weighted("the", "dog", 4);
+        chain.see("the", "typo");
+        let config = GenConfig {
+            seed: Some("the".to_string()),
+            stop: Stop::Words(2),
+            min_p: 0.1,
+            ..GenConfig::default()
+        };
+        for _ in 0..50 {
+            assert_ne!(chain.generate(&config).unwrap()[1], "typo");
+        }
+
+        let config = GenConfig { min_p: 0.9, ..config };
+        assert_eq!(chain.generate(&config).unwrap(), vec!["the", "cat"]);
+    }
+
+    #[test]
+    fn test_generate_without_self_loops() {
+        let mut chain = Chain::new();
+        chain.train("very very very good").unwrap();
+        let config = GenConfig {
+            seed: Some("very".to_string()),
+            stop: Stop::Words(3),
+            self_loops: false,
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["very", "good"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_generate_bias() {
+        let mut chain = Chain::new();
+        chain.see("welcome", "to");
+        chain.see("welcome", "the");
+        chain.see("to", "the");
+        chain.see("the", "jungle");
+        chain.see("the", "city");
+        let mut bias = HashMap::new();
+        bias.insert("jungle".to_string(), 2.0);
+        bias.insert("city".to_string(), 0.0);
+        bias.insert("to".to_string(), 0.0);
+        let config = GenConfig {
+            seed: Some("welcome".to_string()),
+            stop: Stop::Words(3),
+            bias,
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["welcome", "the", "jungle"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_log_likelihood() {
+        let mut chain = Chain::new();
+        chain.train("a b a c").unwrap();
+        let words: Vec<String> = ["a", "b", "a", "c"].iter().map(|w| w.to_string()).collect();
+        assert!((chain.log_likelihood(&words) - 0.25f64.ln()).abs() < 1e-9);
+        assert_eq!(chain.log_likelihood(&words[..1]), 0.0);
+        assert_eq!(chain.log_likelihood(&["c".to_string(), "a".to_string()]), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_reply() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("the dog ate the cat").unwrap();
+        let config = GenConfig {
+            stop: Stop::Words(2),
+            ..GenConfig::default()
+        };
+        assert_eq!(chain.reply("Is THE DOG on the loose?", &config), Ok(vec!["dog".to_string(), "ate".to_string()]));
+    }
+
+    #[test]
+    fn test_reply_without_keywords() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        let config = GenConfig {
+            seed: Some("hello".to_string()),
+            stop: Stop::Words(2),
+            ..GenConfig::default()
+        };
+        assert_eq!(chain.reply("bonjour", &config), Ok(vec!["hello".to_string(), "bob".to_string()]));
+        assert_eq!(chain.reply("bob", &config), Ok(vec!["hello".to_string(), "bob".to_string()]));
+    }
+
+    #[test]
+    fn test_generate_max_chars() {
+        let mut chain = Chain::new();
+        chain.train("we got fun and games").unwrap();
+        let config = GenConfig {
+            seed: Some("we".to_string()),
+            stop: Stop::Words(100),
+            max_chars: Some(14),
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["we", "got", "fun", "and"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+
+        let config = GenConfig { max_chars: Some(1), ..config };
+        assert_eq!(chain.generate(&config), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_generate_stop_range() {
+        let mut chain = Chain::new();
+        chain.see("round", "and");
+        chain.see("and", "round");
+        let config = GenConfig {
+            seed: Some("round".to_string()),
+            stop: Stop::Range{min: 2, max: 6},
+            ..GenConfig::default()
+        };
+        assert_eq!(chain.generate(&config).unwrap().len(), 6);
+
+        let mut stop_tokens = HashSet::new();
+        stop_tokens.insert("round".to_string());
+        let config = GenConfig { stop_tokens, ..config };
+        let expected: Vec<String> = ["round", "and", "round"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_generate_stop_range_at_text_end() {
+        let mut chain = Chain::with_config(TrainConfig { wrap_around: true, ..TrainConfig::default() });
+        chain.train("one two three").unwrap();
+        let config = GenConfig {
+            seed: Some("one".to_string()),
+            stop: Stop::Range{min: 4, max: 10},
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["one", "two", "three", "one", "two", "three"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_generate_stop_when() {
+        let mut chain = Chain::new();
+        chain.see("round", "and");
+        chain.see("and", "round");
+        let config = GenConfig {
+            seed: Some("round".to_string()),
+            stop: Stop::When{max: 10, done: Arc::new(|out: &[String]| out.len() == 3)},
+            ..GenConfig::default()
+        };
+        assert_eq!(chain.generate(&config).unwrap().len(), 3);
+
+        let config = GenConfig {
+            stop: Stop::When{max: 5, done: Arc::new(|_: &[String]| false)},
+            ..config
+        };
+        assert_eq!(chain.generate(&config).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_generate_from_prompt() {
+        let mut chain = Chain::new();
+        chain.train("welcome to the jungle").unwrap();
+        let config = GenConfig {
+            seed: Some("Hello and Welcome".to_string()),
+            stop: Stop::Words(5),
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["hello", "and", "welcome", "to", "the"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate(&config), Ok(expected));
+
+        let config = GenConfig { seed: Some("welcome stranger".to_string()), ..config };
+        assert_eq!(chain.generate(&config), Err(MarkovErr::NotSeen{w: "stranger".to_string()}));
+
+        let config = GenConfig { seed: Some("!!".to_string()), ..config };
+        assert_eq!(chain.generate(&config), Err(MarkovErr::NotSeen{w: "!!".to_string()}));
+    }
+
+    #[test]
+    fn test_generate_stops_at_dead_end() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        let config = GenConfig {
+            seed: Some("hello".to_string()),
+            stop: Stop::Words(5),
+            ..GenConfig::default()
+        };
+        assert_eq!(chain.generate(&config), Ok(vec!["hello".to_string(), "bob".to_string()]));
+    }
+
+    #[test]
+    fn test_generate_max_verbatim() {
+        let mut chain = Chain::with_config(TrainConfig { index_corpus: true, ..TrainConfig::default() });
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("a cat sat on a hat").unwrap();
+        let config = GenConfig {
+            seed: Some("the".to_string()),
+            stop: Stop::Words(6),
+            max_verbatim: 3,
+            ..GenConfig::default()
+        };
+        // "on" would make four words in a row from the first text, and nothing else follows "sat"
+        for _ in 0..20 {
+            let out = chain.generate(&config).unwrap();
+            assert!(out == ["the", "cat", "sat"] || out == ["the", "mat"]);
+        }
+
+        let config = GenConfig { seed: Some("the cat sat".to_string()), max_verbatim: 2, ..config };
+        let mut plain = Chain::new();
+        plain.train("the cat sat on").unwrap();
+        assert_eq!(plain.generate(&config), Err(MarkovErr::NotIndexed));
+    }
+
+    #[test]
+    fn test_generate_no_repeat_ngram() {
+        let mut chain = Chain::new();
+        chain.train("we go round and round and round").unwrap();
+        let config = GenConfig {
+            seed: Some("round".to_string()),
+            stop: Stop::Words(20),
+            no_repeat_ngram: 2,
+            ..GenConfig::default()
+        };
+        let out = chain.generate(&config).unwrap();
+        for (i, window) in out.windows(2).enumerate() {
+            assert!(!out[i + 1..].windows(2).any(|other| other == window));
+        }
+        assert!(out.len() < 20);
+    }
 }
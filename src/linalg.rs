@@ -0,0 +1,85 @@
+use std::cmp::Ordering;
+
+use nalgebra::{DMatrix, DVector};
+
+use Chain;
+
+impl Chain {
+    /*
+    the same ranking as pagerank, but solved for exactly instead of estimated by iterating: the
+    stationary distribution is the eigenvector of the surfer's transition matrix with eigenvalue
+    1, which we find by solving the linear system for it directly. that takes a dense matrix with a
+    row and a column per word, so it's for chains of up to a few thousand words. None if the
+    distribution isn't unique, which can only happen with a damping of 1.0 on a chain that falls
+    apart into islands a walk can't leave. most important first.
+    */
+    pub fn stationary(&self, damping: f64) -> Option<Vec<(String, f64)>> {
+        let (words, ids) = self.numbered();
+        let n = words.len();
+        if n == 0 {
+            return Some(vec![]);
+        }
+
+        // transposed, so column i is where a surfer on word i goes next
+        let mut transitions = DMatrix::from_element(n, n, (1.0 - damping) / n as f64);
+        for (i, w) in words.iter().enumerate() {
+            // dead ends have nowhere to go, so the surfer jumps anywhere, as in pagerank
+            if !self.nodes.contains_key(*w) {
+                transitions.column_mut(i).fill(1.0 / n as f64);
+            }
+        }
+        for (key, weight) in &self.edges {
+            let total = self.nodes[&key.0];
+            transitions[(ids[&key.1], ids[&key.0])] += damping * *weight as f64 / total as f64;
+        }
+
+        // (P^T - I) x = 0 only pins x down up to scale, so swap one of its equations for the one
+        // that says x adds up to 1
+        let mut system = transitions - DMatrix::identity(n, n);
+        system.row_mut(n - 1).fill(1.0);
+        let mut target = DVector::zeros(n);
+        target[n - 1] = 1.0;
+        let rank = system.lu().solve(&target)?;
+
+        let mut out: Vec<(String, f64)> = words.into_iter().cloned().zip(rank.iter().cloned()).collect();
+        out.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stationary() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat and the dog sat on the cat").unwrap();
+        let exact = chain.stationary(0.85).unwrap();
+        let estimate = chain.pagerank(0.85, 200);
+        assert_eq!(exact.len(), estimate.len());
+        for ((w, p), (v, q)) in exact.iter().zip(&estimate) {
+            assert_eq!(w, v);
+            assert!((p - q).abs() < 1e-9);
+        }
+
+        let mut chain = Chain::new();
+        chain.see_weighted("a", "b", 3);
+        chain.see("a", "a");
+        chain.see("b", "a");
+        let exact = chain.stationary(1.0).unwrap();
+        assert_eq!(exact[0].0, "a");
+        assert!((exact[0].1 - 4.0 / 7.0).abs() < 1e-9);
+        assert!((exact[1].1 - 3.0 / 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stationary_islands() {
+        let mut chain = Chain::new();
+        chain.train("a b a").unwrap();
+        chain.train("c d c").unwrap();
+        assert_eq!(chain.stationary(1.0), None);
+        assert!(chain.stationary(0.9).is_some());
+        assert_eq!(Chain::new().stationary(0.85), Some(vec![]));
+    }
+}
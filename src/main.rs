@@ -0,0 +1,451 @@
+extern crate markov;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use markov::{Chain, GenConfig, MarkovErr, Stop};
+
+const DEFAULT_PORT: u16 = 8080;
+
+const USAGE: &str = "usage:
+    markov train <model> [file...]
+    markov generate <model> [--seed <words>] [--words <n>] [--seed-rng <n>]
+                    [--format text|json]
+    markov stats <model>
+    markov export-dot <model> [--top <n>]
+    markov export-mermaid <model> [--top <n>]
+    markov watch <dir> [--out <model>] [--interval <ms>] [--serve] [--port <n>]
+    markov serve <model> [--port <n>]
+
+train learns from each line of the files (or stdin). export-dot prints a Graphviz graph, keeping
+only each word's top n likeliest successors if --top is given, and export-mermaid does the same
+as a Mermaid flowchart for pasting into Markdown. generate --format json prints
+the seed it started from, the words, the probability of each step (null for the seed's words, or
+the word it started on), and whether each step teleported instead of following the chain.
+the same --seed-rng with the same model and options always generates the same thing. watch
+trains on every file in dir, then keeps checking them, training again (and saving to --out, and
+printing a sample) whenever they change. serve answers GET /generate?seed=welcome&words=50 on
+--port (8080 by default), and watch --serve does the same with the chain it's keeping up to
+date. both need the server feature.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let rest = if args.is_empty() { &args[..] } else { &args[1..] };
+    let result = match args.first().map(|command| command.as_str()) {
+        Some("train") => train(rest),
+        Some("generate") => generate(rest),
+        Some("stats") => stats(rest),
+        Some("export-dot") => export(rest, Chain::to_dot),
+        Some("export-mermaid") => export(rest, Chain::to_mermaid),
+        Some("watch") => watch(rest),
+        Some("serve") => serve(rest),
+        _ => Err(USAGE.to_string())
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn train(args: &[String]) -> Result<(), String> {
+    let (model, files) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(USAGE.to_string())
+    };
+    let mut text = String::new();
+    if files.is_empty() {
+        io::stdin().read_to_string(&mut text).map_err(|e| e.to_string())?;
+    }
+    for file in files {
+        text.push_str(&fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?);
+        text.push('\n');
+    }
+
+    let mut chain = Chain::new();
+    chain.train_sequences(text.lines()).map_err(describe)?;
+    chain.save(model).map_err(describe)
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    println!("{}", generated(args)?);
+    Ok(())
+}
+
+// what generate prints
+fn generated(args: &[String]) -> Result<String, String> {
+    let mut model = None;
+    let mut config = GenConfig::default();
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => config.seed = Some(value(args.next(), arg)?.to_string()),
+            "--format" => json = match value(args.next(), arg)? {
+                "text" => false,
+                "json" => true,
+                _ => return Err(USAGE.to_string())
+            },
+            "--seed-rng" => config.rng_seed = Some(value(args.next(), arg)?.parse().map_err(|_| USAGE.to_string())?),
+            "--words" => config.stop = Stop::Words(value(args.next(), arg)?.parse().map_err(|_| USAGE.to_string())?),
+            _ if model.is_none() => model = Some(arg),
+            _ => return Err(USAGE.to_string())
+        }
+    }
+    let chain = load(model)?;
+    if !json {
+        return Ok(chain.generate(&config).map_err(describe)?.join(" "));
+    }
+
+    let (words, steps) = chain.generate_traced(&config, 0).map_err(describe)?;
+    // without a seed we started wherever a teleport landed, which is the first word
+    let seed = config.seed.as_ref().or(words.first()).map_or("null".to_string(), |seed| quote(seed));
+    let tokens: Vec<String> = words.iter().map(|w| quote(w)).collect();
+    // the seed's words (or the one we started on) weren't steps we took
+    let given = words.len() - steps.len();
+    let probabilities: Vec<String> = (0..given).map(|_| "null".to_string())
+        .chain(steps.iter().map(|step| step.probability.to_string()))
+        .collect();
+    let teleported: Vec<String> = (0..given).map(|_| "false".to_string())
+        .chain(steps.iter().map(|step| step.teleported.to_string()))
+        .collect();
+    let rng_seed = config.rng_seed.map_or("null".to_string(), |seed| seed.to_string());
+    Ok(format!(
+        "{{\"seed\": {}, \"rng_seed\": {}, \"tokens\": [{}], \"probabilities\": [{}], \"teleported\": [{}]}}",
+        seed, rng_seed, tokens.join(", "), probabilities.join(", "), teleported.join(", ")
+    ))
+}
+
+fn stats(args: &[String]) -> Result<(), String> {
+    if args.len() != 1 {
+        return Err(USAGE.to_string());
+    }
+    let stats = load(args.first())?.stats(10);
+    println!("vocabulary: {} words", stats.vocab);
+    println!("edges: {}", stats.edges);
+    println!("hubs (words with the most neighbours):");
+    for (w, neighbours) in &stats.hubs {
+        println!("    {}: {}", w, neighbours);
+    }
+    println!("entropy: {:.2} bits on average, {:.2} at most", stats.mean_entropy, stats.max_entropy);
+    println!("deterministic words: {}", stats.deterministic);
+    println!("memory: about {} KiB", stats.memory.div_ceil(1024));
+    Ok(())
+}
+
+fn export(args: &[String], render: fn(&Chain, usize) -> String) -> Result<(), String> {
+    let mut model = None;
+    let mut top = 0;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top" => top = value(args.next(), arg)?.parse().map_err(|_| USAGE.to_string())?,
+            _ if model.is_none() => model = Some(arg),
+            _ => return Err(USAGE.to_string())
+        }
+    }
+    print!("{}", render(&load(model)?, top));
+    Ok(())
+}
+
+/*
+keeps a chain up to date with the files in dir. when a file only had lines added to the end, we
+train on just those; anything else (an edit, a deleted file) and we start over from scratch, since
+a chain can't unlearn.
+*/
+fn watch(args: &[String]) -> Result<(), String> {
+    let mut dir = None;
+    let mut out = None;
+    let mut interval = 1000;
+    let mut serving = false;
+    let mut port = DEFAULT_PORT;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(value(args.next(), arg)?.to_string()),
+            "--serve" => serving = true,
+            "--port" => port = value(args.next(), arg)?.parse().map_err(|_| USAGE.to_string())?,
+            "--interval" => interval = value(args.next(), arg)?.parse().map_err(|_| USAGE.to_string())?,
+            _ if dir.is_none() => dir = Some(arg),
+            _ => return Err(USAGE.to_string())
+        }
+    }
+    let dir = dir.ok_or_else(|| USAGE.to_string())?;
+
+    let chain = Arc::new(RwLock::new(Chain::new()));
+    if serving {
+        let chain = chain.clone();
+        let server = start_server()?;
+        thread::spawn(move || {
+            if let Err(e) = server(chain, port) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        });
+    }
+    let mut seen: Files = BTreeMap::new();
+    loop {
+        let mut files = vec![];
+        for entry in fs::read_dir(dir).map_err(|e| format!("{}: {}", dir, e))? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            match fs::metadata(&path) {
+                Ok(meta) if meta.is_file() => files.push((path, meta.modified().map_err(|e| e.to_string())?)),
+                _ => continue
+            }
+        }
+        let (current, Update { added, rebuild }) = rescan(seen, files, |path| fs::read_to_string(path));
+        seen = current;
+
+        if rebuild {
+            // train the new chain on the side, so anyone we're serving never sees it half done
+            let mut fresh = Chain::new();
+            learn(&mut fresh, seen.values().map(|(_, text)| text))?;
+            *chain.write().unwrap_or_else(|e| e.into_inner()) = fresh;
+        } else if !added.is_empty() {
+            learn(&mut chain.write().unwrap_or_else(|e| e.into_inner()), added.iter())?;
+        }
+        if rebuild || !added.is_empty() {
+            let chain = chain.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(ref out) = out {
+                chain.save(out).map_err(|e| format!("{}: {}", out, describe(e)))?;
+            }
+            let sample = chain.generate(&GenConfig { stop: Stop::Words(20), ..GenConfig::default() });
+            println!("trained on {} files: {}", seen.len(), sample.map(|words| words.join(" ")).unwrap_or_default());
+        }
+        thread::sleep(Duration::from_millis(interval));
+    }
+}
+
+// every file we're watching, when it was last modified and what was in it then
+type Files = BTreeMap<PathBuf, (SystemTime, String)>;
+
+// what to do about the changes a rescan found
+#[derive(Debug, PartialEq)]
+struct Update {
+    // text to train on: whole new files, and whatever was added to the end of old ones
+    added: Vec<String>,
+    // whether something was edited or deleted, so we have to train from scratch
+    rebuild: bool
+}
+
+/*
+compares files (and when each was last modified) with what we saw last time, reading the ones
+that are new or have been modified with read, and returns what we've seen now along with what's
+changed. a file that's been modified but has the same text, say because it was touched, hasn't
+changed. one we can't read is skipped, as if it weren't there.
+*/
+fn rescan(mut seen: Files, files: Vec<(PathBuf, SystemTime)>, read: impl Fn(&Path) -> io::Result<String>) -> (Files, Update) {
+    let mut current = BTreeMap::new();
+    let mut added = vec![];
+    let mut rebuild = false;
+    for (path, modified) in files {
+        let text = match seen.remove(&path) {
+            Some((then, text)) if then == modified => text,
+            old => {
+                let text = match read(&path) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        eprintln!("skipping {}: {}", path.display(), e);
+                        // hang on to what we had, with its old time so we try it again next scan
+                        if let Some(old) = old {
+                            current.insert(path, old);
+                        }
+                        continue;
+                    }
+                };
+                match old {
+                    Some((_, ref old)) if *old == text => {},
+                    Some((_, ref old)) if old.ends_with('\n') && text.starts_with(old.as_str()) => added.push(text[old.len()..].to_string()),
+                    Some(_) => rebuild = true,
+                    None => added.push(text.clone())
+                }
+                text
+            }
+        };
+        current.insert(path, (modified, text));
+    }
+    // anything left over isn't there any more
+    rebuild |= !seen.is_empty();
+    (current, Update { added, rebuild })
+}
+
+// trains on every line of every text, if there's anything to train on
+fn learn<'a>(chain: &mut Chain, texts: impl Iterator<Item = &'a String>) -> Result<(), String> {
+    match chain.train_sequences(texts.flat_map(|text| text.lines())) {
+        Ok(()) | Err(MarkovErr::EmptyCorpus) => Ok(()),
+        Err(e) => Err(describe(e))
+    }
+}
+
+fn serve(args: &[String]) -> Result<(), String> {
+    let mut model = None;
+    let mut port = DEFAULT_PORT;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = value(args.next(), arg)?.parse().map_err(|_| USAGE.to_string())?,
+            _ if model.is_none() => model = Some(arg),
+            _ => return Err(USAGE.to_string())
+        }
+    }
+    let chain = load(model)?;
+    start_server()?(Arc::new(RwLock::new(chain)), port)
+}
+
+type Serve = fn(Arc<RwLock<Chain>>, u16) -> Result<(), String>;
+
+// the server, as long as we were built with it
+#[cfg(feature = "server")]
+fn start_server() -> Result<Serve, String> {
+    Ok(|chain, port| markov::serve(chain, &format!("0.0.0.0:{}", port)).map_err(describe))
+}
+
+#[cfg(not(feature = "server"))]
+fn start_server() -> Result<Serve, String> {
+    Err("this markov was built without the server feature".to_string())
+}
+
+fn load(model: Option<&String>) -> Result<Chain, String> {
+    let model = model.ok_or_else(|| USAGE.to_string())?;
+    Chain::load(model).map_err(|e| format!("{}: {}", model, describe(e)))
+}
+
+// the value after a flag, or a usage error if it's missing
+fn value<'a>(value: Option<&'a String>, flag: &str) -> Result<&'a str, String> {
+    value.map(|v| v.as_str()).ok_or_else(|| format!("{} needs a value\n{}", flag, USAGE))
+}
+
+// w as a JSON string
+fn quote(w: &str) -> String {
+    let mut out = String::from("\"");
+    for c in w.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn describe(e: MarkovErr) -> String {
+    match e {
+        MarkovErr::NotSeen{w} => format!("never seen \"{}\"", w),
+        MarkovErr::EmptyCorpus => "nothing to train on".to_string(),
+        MarkovErr::Io{e} => e,
+        MarkovErr::Corrupt => "not a model file, or cut off partway through".to_string(),
+        MarkovErr::UnsupportedVersion{v} => format!("saved by a newer version (format {})", v),
+        e => format!("{:?}", e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use markov::{Case, TrainConfig};
+
+    // a model saved where generate can load it, trained with config on texts
+    fn model(name: &str, config: TrainConfig, texts: &[&str]) -> String {
+        let path = env::temp_dir().join(format!("markov-cli-test-{}-{}.mrkv", process::id(), name));
+        let mut chain = Chain::with_config(config);
+        chain.train_sequences(texts).unwrap();
+        chain.save(&path).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_generate_json() {
+        // the surface forms are capitalized, but the chain counts lowercase keys
+        let config = TrainConfig { case: Case::FoldKeys, ..TrainConfig::default() };
+        let path = model("json", config, &["The Cat sat", "The Cat ran", "The Cat sat"]);
+        let out = generated(&args(&[&path, "--seed", "The", "--words", "3", "--format", "json", "--seed-rng", "1"])).unwrap();
+        let sat = "{\"seed\": \"The\", \"rng_seed\": 1, \"tokens\": [\"The\", \"Cat\", \"sat\"], \"probabilities\": [null, 1, 0.6666666666666666], \"teleported\": [false, false, false]}";
+        let ran = "{\"seed\": \"The\", \"rng_seed\": 1, \"tokens\": [\"The\", \"Cat\", \"ran\"], \"probabilities\": [null, 1, 0.3333333333333333], \"teleported\": [false, false, false]}";
+        assert!(out == sat || out == ran, "{}", out);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_generate_seed_rng() {
+        let path = model("seed-rng", TrainConfig::default(), &[
+            "the cat sat on the mat", "the dog ran to the park", "a cat ran on the grass", "the bird sat in a tree"
+        ]);
+        for format in &["text", "json"] {
+            let run = || generated(&args(&[&path, "--words", "30", "--seed-rng", "42", "--format", format])).unwrap();
+            let first = run();
+            for _ in 0..5 {
+                assert_eq!(run(), first);
+            }
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    // reads files out of texts instead of off disk
+    fn read(texts: &BTreeMap<PathBuf, &'static str>) -> impl Fn(&Path) -> io::Result<String> {
+        let texts = texts.clone();
+        move |path| texts.get(path).map(|text| text.to_string()).ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    #[test]
+    fn test_rescan() {
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let texts: BTreeMap<PathBuf, &'static str> = [("a", "one\n"), ("b", "two\nthree\n"), ("c", "four\n")].iter()
+            .map(|&(path, text)| (PathBuf::from(path), text))
+            .collect();
+        let files = |files: &[(&str, u64)]| files.iter().map(|&(path, secs)| (PathBuf::from(path), at(secs))).collect::<Vec<_>>();
+
+        // everything's new
+        let (seen, update) = rescan(BTreeMap::new(), files(&[("a", 1), ("b", 1)]), read(&texts));
+        assert_eq!(update, Update { added: vec!["one\n".to_string(), "two\nthree\n".to_string()], rebuild: false });
+
+        // nothing's been modified
+        let (seen, update) = rescan(seen, files(&[("a", 1), ("b", 1)]), read(&texts));
+        assert_eq!(update, Update { added: vec![], rebuild: false });
+
+        // touched, so modified but with the same text
+        let (seen, update) = rescan(seen, files(&[("a", 2), ("b", 1)]), read(&texts));
+        assert_eq!(update, Update { added: vec![], rebuild: false });
+
+        // appended to, and a new file
+        let mut appended = texts.clone();
+        appended.insert(PathBuf::from("a"), "one\nfive\n");
+        let (seen, update) = rescan(seen, files(&[("a", 3), ("b", 1), ("c", 1)]), read(&appended));
+        assert_eq!(update, Update { added: vec!["five\n".to_string(), "four\n".to_string()], rebuild: false });
+        assert_eq!(seen[&PathBuf::from("a")].1, "one\nfive\n");
+
+        // edited
+        let mut edited = appended.clone();
+        edited.insert(PathBuf::from("b"), "two\nsix\n");
+        let (seen, update) = rescan(seen, files(&[("a", 3), ("b", 2), ("c", 1)]), read(&edited));
+        assert_eq!(update, Update { added: vec![], rebuild: true });
+
+        // deleted
+        let (seen, update) = rescan(seen, files(&[("a", 3), ("c", 1)]), read(&edited));
+        assert_eq!(update, Update { added: vec![], rebuild: true });
+        assert_eq!(seen.len(), 2);
+
+        // modified but unreadable for now, then readable again
+        let mut unreadable = edited.clone();
+        unreadable.remove(&PathBuf::from("a"));
+        let (seen, update) = rescan(seen, files(&[("a", 4), ("c", 1)]), read(&unreadable));
+        assert_eq!(update, Update { added: vec![], rebuild: false });
+        assert_eq!(seen[&PathBuf::from("a")], (at(3), "one\nfive\n".to_string()));
+
+        let mut readable = edited.clone();
+        readable.insert(PathBuf::from("a"), "one\nfive\nseven\n");
+        let (_, update) = rescan(seen, files(&[("a", 4), ("c", 1)]), read(&readable));
+        assert_eq!(update, Update { added: vec!["seven\n".to_string()], rebuild: false });
+    }
+}
@@ -0,0 +1,154 @@
+use rand::{Rng, RngCore};
+
+use {rng, walk, Chain, GenConfig, MarkovErr};
+
+/*
+when a mashup moves on from one chain to the next
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Switch {
+    // once the output is this many words long, seed included
+    At(usize),
+    // with this probability at each step
+    Chance(f32)
+}
+
+/*
+generates with each of chains in turn, moving on from one to the next when its Switch says to, so
+the output can start in one voice and drift into another. we only ever change chains on a word
+the next chain knows how to carry on from, so once it's time to switch, we keep going with the
+current chain until we reach one. the last chain's Switch doesn't matter. the seed is split up the
+way the first chain splits text, and each word comes out the way the chain that chose it would
+have written it. EmptyCorpus if there are no chains.
+*/
+pub fn mashup(chains: &[(&Chain, Switch)], config: &GenConfig) -> Result<Vec<String>, MarkovErr> {
+    let mut stage = 0;
+    let mut leaving = false;
+    let only: Vec<&Chain> = chains.iter().map(|&(chain, _)| chain).collect();
+    generate_with(&only, config, |out, rng| {
+        if stage + 1 < chains.len() {
+            leaving = leaving || match chains[stage].1 {
+                Switch::At(n) => out.len() >= n,
+                Switch::Chance(p) => rng.gen_range(0.0, 1.0) < p
+            };
+            if leaving && chains[stage + 1].0.nodes.contains_key(&out[out.len() - 1]) {
+                stage += 1;
+                leaving = false;
+            }
+        }
+        stage
+    })
+}
+
+/*
+generates from a mix of two chains, where each step's distribution is alpha times a's plus
+(1 - alpha) times b's, to dial smoothly between two voices: 1.0 is all a, 0.0 all b. we do that by
+letting a choose each word with probability alpha and b otherwise, each with the config's sampling
+options applied, which comes to the same thing. where only one of them knows how to carry on from
+a word, that one chooses. the seed is split up the way a splits text, and without one we start
+where a would.
+*/
+pub fn blend(a: &Chain, b: &Chain, alpha: f32, config: &GenConfig) -> Result<Vec<String>, MarkovErr> {
+    generate_with(&[a, b], config, |out, rng| {
+        let last = &out[out.len() - 1];
+        match (a.nodes.contains_key(last), b.nodes.contains_key(last)) {
+            (true, true) => if rng.gen_range(0.0, 1.0) < alpha { 0 } else { 1 },
+            (true, false) => 0,
+            (false, true) => 1,
+            // neither can go on, so let one that's seen the word say so
+            (false, false) => if a.contains(last) { 0 } else { 1 }
+        }
+    })
+}
+
+/*
+generates with several chains at once, where choose says which of them takes the next step given
+the output so far. the first chain splits the seed up, or picks where to start without one. each
+word comes out the way the chain that chose it would have written it.
+*/
+fn generate_with<F>(chains: &[&Chain], config: &GenConfig, mut choose: F) -> Result<Vec<String>, MarkovErr>
+    where F: FnMut(&[String], &mut Box<dyn RngCore>) -> usize
+{
+    let first = *chains.first().ok_or(MarkovErr::EmptyCorpus)?;
+    let mut rng = rng(config);
+    let prompt: Vec<String> = match config.seed {
+        Some(ref seed) => first.tokenize(seed),
+        None => first.start(config, &mut rng).into_iter().collect()
+    };
+    if prompt.is_empty() {
+        return Err(match config.seed {
+            Some(ref seed) => MarkovErr::NotSeen{w: seed.clone()},
+            None => MarkovErr::EmptyCorpus
+        });
+    }
+
+    // which chain chose each word of the output, and which one's choosing the next
+    let mut owners = vec![0; prompt.len()];
+    let mut current = 0;
+    let out = walk(prompt, config, &mut rng, &mut vec![], |out, rng| {
+        owners.resize(out.len(), current);
+        current = choose(out, rng);
+        chains[current]
+    })?;
+    owners.resize(out.len(), current);
+
+    Ok(out.into_iter().zip(owners).flat_map(|(w, i)| chains[i].surface(vec![w])).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Stop;
+
+    fn chains() -> (Chain, Chain) {
+        let mut a = Chain::new();
+        a.see("one", "two");
+        a.see("two", "three");
+        a.see("three", "shared");
+        a.see("shared", "four");
+        let mut b = Chain::new();
+        b.see("shared", "x");
+        b.see("x", "y");
+        b.see("y", "z");
+        (a, b)
+    }
+
+    #[test]
+    fn test_mashup() {
+        let (a, b) = chains();
+        let config = GenConfig { seed: Some("one".to_string()), stop: Stop::Words(6), ..GenConfig::default() };
+        let out = mashup(&[(&a, Switch::At(2)), (&b, Switch::At(0))], &config).unwrap();
+        assert_eq!(out, vec!["one", "two", "three", "shared", "x", "y"]);
+
+        let out = mashup(&[(&a, Switch::Chance(1.0)), (&b, Switch::At(0))], &config).unwrap();
+        assert_eq!(out, vec!["one", "two", "three", "shared", "x", "y"]);
+        let out = mashup(&[(&a, Switch::Chance(0.0)), (&b, Switch::At(0))], &config).unwrap();
+        assert_eq!(out, vec!["one", "two", "three", "shared", "four"]);
+    }
+
+    #[test]
+    fn test_blend() {
+        let mut a = Chain::new();
+        a.train("the cat sat").unwrap();
+        let mut b = Chain::new();
+        b.train("the dog ran").unwrap();
+        let config = GenConfig { seed: Some("the".to_string()), stop: Stop::Words(3), ..GenConfig::default() };
+        for _ in 0..10 {
+            assert_eq!(blend(&a, &b, 1.0, &config).unwrap(), vec!["the", "cat", "sat"]);
+            assert_eq!(blend(&a, &b, 0.0, &config).unwrap(), vec!["the", "dog", "ran"]);
+        }
+
+        let config = GenConfig { stop: Stop::Words(2), ..config };
+        let cats = (0..400).filter(|_| blend(&a, &b, 0.25, &config).unwrap()[1] == "cat").count();
+        assert!(cats > 50 && cats < 150);
+    }
+
+    #[test]
+    fn test_mashup_errors() {
+        let (a, _) = chains();
+        assert_eq!(mashup(&[], &GenConfig::default()), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(blend(&Chain::new(), &Chain::new(), 0.5, &GenConfig::default()), Err(MarkovErr::EmptyCorpus));
+        let config = GenConfig { seed: Some("x".to_string()), ..GenConfig::default() };
+        assert_eq!(mashup(&[(&a, Switch::At(0))], &config), Err(MarkovErr::NotSeen{w: "x".to_string()}));
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, HashSet};
+
+use {Chain, MarkovErr, TrainConfig};
+
+// stand-ins for before the first word of a text and after the last
+const START: &str = "<s>";
+const END: &str = "</s>";
+// learn_weights stops early once no weight moves by more than this in a round
+const TOLERANCE: f64 = 1e-6;
+
+/*
+an n-gram language model for scoring text rather than generating it: how likely a text is, how
+surprised the model is by it, and what it expects next. each word depends on the n - 1 before it,
+and contexts we have few counts for back off to shorter ones (Witten-Bell smoothing), so a text
+with words or n-grams training never saw still gets a finite score.
+*/
+pub struct NgramModel {
+    n: usize,
+    // splits and normalizes text the way a chain with the same config would. it's never trained.
+    words: Chain,
+    // how many times each word followed each context, for every context length from 0 to n - 1
+    counts: BTreeMap<Vec<String>, BTreeMap<String, u64>>,
+    // the words we've seen, END included
+    vocab: HashSet<String>,
+    // how much to trust each context length, from 0 up, once learn_weights has worked it out.
+    // until then we back off Witten-Bell style.
+    weights: Option<Vec<f64>>
+}
+
+impl NgramModel {
+    /*
+    a model where each word depends on the n - 1 (at least 0) before it, splitting text the way
+    the default config does
+    */
+    pub fn new(n: usize) -> NgramModel {
+        NgramModel::with_config(n, TrainConfig::default())
+    }
+
+    /*
+    a model that splits and normalizes text the way a chain with config does. the config's
+    options for counting pairs (window, self_loops and so on) don't apply.
+    */
+    pub fn with_config(n: usize, config: TrainConfig) -> NgramModel {
+        NgramModel { n: n.max(1), words: Chain::with_config(config), counts: BTreeMap::new(), vocab: HashSet::new(), weights: None }
+    }
+
+    pub fn order(&self) -> usize {
+        self.n
+    }
+
+    /*
+    how many different n-grams we've counted, of every length up to n, which is roughly what the
+    model costs to keep around
+    */
+    pub fn ngrams(&self) -> usize {
+        self.counts.values().map(BTreeMap::len).sum()
+    }
+
+    /*
+    counts every n-gram in the text, and every shorter one. EmptyCorpus if it has no words.
+    */
+    pub fn train(&mut self, text: &str) -> Result<(), MarkovErr> {
+        let words = self.words.tokenize(text);
+        if words.is_empty() {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+        let padded = self.pad(words);
+        for i in self.n - 1..padded.len() {
+            let word = &padded[i];
+            for len in 0..self.n {
+                let context = padded[i - len..i].to_vec();
+                *self.counts.entry(context).or_default().entry(word.clone()).or_insert(0) += 1;
+            }
+            self.vocab.insert(word.clone());
+        }
+        Ok(())
+    }
+
+    /*
+    trains on each text in turn (see Chain::train_sequences), skipping the empty ones
+    */
+    pub fn train_sequences(&mut self, texts: impl IntoIterator<Item = impl AsRef<str>>) -> Result<(), MarkovErr> {
+        let mut trained = false;
+        for text in texts {
+            trained |= self.train(text.as_ref()).is_ok();
+        }
+        if trained { Ok(()) } else { Err(MarkovErr::EmptyCorpus) }
+    }
+
+    /*
+    the natural log of the probability of the whole text, ending included. the longer the text,
+    the lower this gets, so compare texts of different lengths with perplexity instead.
+    */
+    pub fn score(&self, text: &str) -> f64 {
+        self.log_probabilities(text).iter().sum()
+    }
+
+    /*
+    how surprised the model is by text, on average per word: e to the minus the mean log
+    probability per word (counting the ending as one). lower is more like the training text, and
+    a model that had to guess uniformly between k words would score k.
+    */
+    pub fn perplexity(&self, text: &str) -> f64 {
+        let logs = self.log_probabilities(text);
+        (-logs.iter().sum::<f64>() / logs.len() as f64).exp()
+    }
+
+    /*
+    perplexity over several texts at once, pooling their words rather than averaging their
+    perplexities, so long texts count for more than short ones. NaN if there are none.
+    */
+    pub fn perplexity_of(&self, texts: impl IntoIterator<Item = impl AsRef<str>>) -> f64 {
+        let (total, count) = texts.into_iter()
+            .flat_map(|text| self.log_probabilities(text.as_ref()))
+            .fold((0.0, 0), |(total, count), log| (total + log, count + 1));
+        (-total / count as f64).exp()
+    }
+
+    /*
+    how likely each word we've seen is to come next after the text so far, likeliest first. the
+    chance that the text ends here instead isn't included, so the probabilities add up to a bit
+    less than 1.
+    */
+    pub fn next_word_distribution(&self, context: &str) -> Vec<(String, f64)> {
+        let mut padded = self.pad(self.words.tokenize(context));
+        padded.pop();
+        let context = &padded[padded.len() + 1 - self.n..];
+        let mut out: Vec<(String, f64)> = self.vocab.iter()
+            .filter(|w| *w != END)
+            .map(|w| (w.clone(), self.probability(context, w)))
+            .collect();
+        out.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+
+    /*
+    learns how much to trust each context length, from none up to n - 1 words, from texts held
+    out of training (see split_held_out), by expectation maximization: the weights that make the
+    held out texts likeliest when the probability of a word is the weighted sum of how often it
+    followed each length of context. from then on that's how the model scores, instead of backing
+    off to shorter contexts by fixed rules, which tends to suit large corpora better. runs for up
+    to rounds rounds and returns the weights, shortest context first. EmptyCorpus if held_out is
+    empty.
+    */
+    pub fn learn_weights(&mut self, held_out: impl IntoIterator<Item = impl AsRef<str>>, rounds: usize) -> Result<Vec<f64>, MarkovErr> {
+        // for every held out word, how likely each context length on its own thinks it is
+        let mut estimates: Vec<Vec<f64>> = vec![];
+        for text in held_out {
+            let padded = self.pad(self.words.tokenize(text.as_ref()));
+            for i in self.n - 1..padded.len() {
+                estimates.push((0..self.n).map(|len| self.estimate(&padded[i - len..i], &padded[i])).collect());
+            }
+        }
+        if estimates.is_empty() {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+
+        let mut weights = vec![1.0 / self.n as f64; self.n];
+        for _ in 0..rounds {
+            // how much of each word's probability each length accounts for, on average
+            let mut shares = vec![0.0; self.n];
+            for estimate in &estimates {
+                let total: f64 = weights.iter().zip(estimate).map(|(w, p)| w * p).sum();
+                for (len, share) in shares.iter_mut().enumerate() {
+                    *share += weights[len] * estimate[len] / total;
+                }
+            }
+            let next: Vec<f64> = shares.iter().map(|share| share / estimates.len() as f64).collect();
+            let moved = next.iter().zip(&weights).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+            weights = next;
+            if moved < TOLERANCE {
+                break;
+            }
+        }
+        self.weights = Some(weights.clone());
+        Ok(weights)
+    }
+
+    // the log probability of each word of text (and its ending) given the ones before it
+    fn log_probabilities(&self, text: &str) -> Vec<f64> {
+        let padded = self.pad(self.words.tokenize(text));
+        (self.n - 1..padded.len())
+            .map(|i| self.probability(&padded[i + 1 - self.n..i], &padded[i]).ln())
+            .collect()
+    }
+
+    // n - 1 STARTs, the words, then END
+    fn pad(&self, words: Vec<String>) -> Vec<String> {
+        let mut padded = vec![START.to_string(); self.n - 1];
+        padded.extend(words);
+        padded.push(END.to_string());
+        padded
+    }
+
+    fn probability(&self, context: &[String], word: &str) -> f64 {
+        match &self.weights {
+            Some(weights) => weights.iter().enumerate()
+                .map(|(len, weight)| weight * self.estimate(&context[context.len() - len..], word))
+                .sum(),
+            None => self.witten_bell(context, word)
+        }
+    }
+
+    /*
+    how often word followed context out of the times we saw context, with nothing set aside for
+    words that never did. with no context, every word gets one extra count as in witten_bell, so
+    nothing is impossible.
+    */
+    fn estimate(&self, context: &[String], word: &str) -> f64 {
+        let next = self.counts.get(context);
+        let count = next.and_then(|next| next.get(word)).map_or(0, |&c| c) as f64;
+        let total = next.map_or(0, |next| next.values().sum::<u64>()) as f64;
+        if context.is_empty() {
+            (count + 1.0) / (total + self.vocab.len() as f64 + 1.0)
+        } else if total > 0.0 {
+            count / total
+        } else {
+            0.0
+        }
+    }
+
+    /*
+    the probability of word after context, Witten-Bell style: a context followed by t different
+    words in c counts sets aside t / (c + t) of its probability for backing off to the context
+    one word shorter. with no context at all, every word gets one extra count, and so does a
+    word we've never seen.
+    */
+    fn witten_bell(&self, context: &[String], word: &str) -> f64 {
+        let next = match self.counts.get(context) {
+            Some(next) => next,
+            None if context.is_empty() => return 1.0 / (self.vocab.len() + 1) as f64,
+            None => return self.witten_bell(&context[1..], word)
+        };
+        let count = next.get(word).map_or(0, |&c| c) as f64;
+        let total = next.values().sum::<u64>() as f64;
+        if context.is_empty() {
+            return (count + 1.0) / (total + self.vocab.len() as f64 + 1.0);
+        }
+        let types = next.len() as f64;
+        (count + types * self.witten_bell(&context[1..], word)) / (total + types)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ngram_model() {
+        let mut model = NgramModel::new(3);
+        model.train_sequences(["the cat sat on the mat", "the cat sat on the hat", "the dog ran"]).unwrap();
+        assert_eq!(model.order(), 3);
+        // 8 words and the ending, then 12 bigrams and 12 trigrams counting the padding at the start
+        assert_eq!(model.ngrams(), 33);
+
+        let seen = model.perplexity("the cat sat on the mat");
+        let shuffled = model.perplexity("mat the on sat cat the");
+        let unseen = model.perplexity("a zebra sat");
+        assert!(seen < shuffled);
+        assert!(shuffled < unseen);
+        assert!(unseen.is_finite());
+        assert!(model.score("the cat sat") > model.score("the cat sat on"));
+
+        let next = model.next_word_distribution("cat sat");
+        assert_eq!(next[0].0, "on");
+        let total: f64 = next.iter().map(|(_, p)| p).sum();
+        assert!(total > 0.9 && total <= 1.0);
+
+        assert_eq!(model.train("!!"), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_perplexity_of() {
+        let mut model = NgramModel::new(2);
+        model.train_sequences(["a b a b", "b a"]).unwrap();
+        let pooled = model.perplexity_of(["a b", "b a b a"]);
+        let logs = model.score("a b") + model.score("b a b a");
+        assert!((pooled - (-logs / 8.0).exp()).abs() < 1e-9);
+        assert_eq!(model.perplexity_of(["a b"]), model.perplexity("a b"));
+        assert!(model.perplexity_of(Vec::<&str>::new()).is_nan());
+    }
+
+    #[test]
+    fn test_unigram_model() {
+        let mut model = NgramModel::new(0);
+        assert_eq!(model.order(), 1);
+        model.train("a a a b").unwrap();
+        // 3 a's, a b and an ending, plus one each for a, b, the ending and anything unseen
+        assert_eq!(model.next_word_distribution("b"), vec![("a".to_string(), 4.0 / 9.0), ("b".to_string(), 2.0 / 9.0)]);
+        assert_eq!(model.score(""), (2.0f64 / 9.0).ln());
+    }
+
+    #[test]
+    fn test_learn_weights() {
+        let mut model = NgramModel::new(3);
+        model.train_sequences(["the cat sat on the mat", "the dog sat on the rug", "the cat sat on the rug"]).unwrap();
+        let before = model.perplexity("the cat sat on the mat");
+        let weights = model.learn_weights(["the cat sat on the mat", "the dog sat on the rug"], 100).unwrap();
+        assert_eq!(weights.len(), 3);
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        // the held out texts are all well predicted by two words of context
+        assert!(weights[2] > weights[0]);
+        assert!(model.perplexity("the cat sat on the mat") < before);
+        assert!(model.perplexity("a zebra sat").is_finite());
+        let total: f64 = model.next_word_distribution("on the").iter().map(|(_, p)| p).sum();
+        assert!(total > 0.9 && total <= 1.0);
+
+        assert_eq!(model.learn_weights(Vec::<&str>::new(), 10), Err(MarkovErr::EmptyCorpus));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use {Chain, GenConfig, MarkovErr, Stop, Teleport};
+
+// the most letters one phoneme can be spelled with, as in "augh" for AO
+const MAX_SPELLING: usize = 4;
+
+/*
+invents pronounceable words by chaining phonemes rather than letters, then spelling the result.
+it learns both from a pronouncing dictionary in CMUdict's format: which sounds follow which inside
+a word, and which letters each sound tends to be written with.
+*/
+pub struct PhonemeChain {
+    chain: Chain,
+    // how many times we saw each phoneme spelled each way
+    spellings: BTreeMap<String, BTreeMap<String, u32>>
+}
+
+impl PhonemeChain {
+    /*
+    reads the dictionary at path (see from_cmudict)
+    */
+    pub fn load_cmudict(path: impl AsRef<Path>) -> Result<PhonemeChain, MarkovErr> {
+        PhonemeChain::from_cmudict(&fs::read_to_string(path)?)
+    }
+
+    /*
+    learns from the text of a CMUdict-style dictionary: one "WORD  PH O NE MES" entry per line,
+    with ;;; comments, (2)-style alternate pronunciations and stress digits (AH0) all allowed.
+    stress is dropped. EmptyCorpus if there weren't any entries.
+    */
+    pub fn from_cmudict(dict: &str) -> Result<PhonemeChain, MarkovErr> {
+        let entries: Vec<(String, Vec<String>)> = dict.lines()
+            .filter(|line| !line.starts_with(";;;"))
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let word = fields.next()?;
+                let word = word.split('(').next().unwrap_or(word).to_lowercase();
+                let phonemes: Vec<String> = fields.map(|p| p.trim_end_matches(|c: char| c.is_ascii_digit()).to_string()).collect();
+                let spellable = !phonemes.is_empty() && word.len() >= phonemes.len() && word.chars().all(|c| c.is_ascii_lowercase());
+                if spellable { Some((word, phonemes)) } else { None }
+            })
+            .collect();
+
+        let mut chain = Chain::new();
+        let mut trained = false;
+        for (_, phonemes) in &entries {
+            trained |= chain.train_tokens(phonemes).is_ok();
+        }
+        if !trained {
+            return Err(MarkovErr::EmptyCorpus);
+        }
+
+        // first guess each word's letters are shared out evenly between its sounds, then line every
+        // word up again the way those guesses say is likeliest, which fixes most of the first pass
+        let mut guess = BTreeMap::new();
+        for (word, phonemes) in &entries {
+            count_spellings(&mut guess, word, phonemes, &even_split(word.len(), phonemes.len()));
+        }
+        let mut spellings = BTreeMap::new();
+        for (word, phonemes) in &entries {
+            let lengths = align(&guess, word, phonemes).unwrap_or_else(|| even_split(word.len(), phonemes.len()));
+            count_spellings(&mut spellings, word, phonemes, &lengths);
+        }
+        Ok(PhonemeChain { chain, spellings })
+    }
+
+    /*
+    a made-up word's phonemes, between min and max of them long, starting with a sound words
+    start with and (if we get there by max) ending with one they end with
+    */
+    pub fn phonemes(&self, min: usize, max: usize) -> Result<Vec<String>, MarkovErr> {
+        self.chain.generate(&GenConfig {
+            stop: Stop::Range{min, max},
+            teleport: Teleport::Start,
+            ..GenConfig::default()
+        })
+    }
+
+    /*
+    spells phonemes the way each one was most often spelled in the dictionary. phonemes we never
+    saw are left out.
+    */
+    pub fn spell(&self, phonemes: &[String]) -> String {
+        phonemes.iter()
+            .filter_map(|p| self.spellings.get(p)?.iter().max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0))))
+            .map(|(spelling, _)| spelling.as_str())
+            .collect()
+    }
+
+    /*
+    a made-up word of 3 to 8 sounds, spelled out
+    */
+    pub fn invent(&self) -> Result<String, MarkovErr> {
+        Ok(self.spell(&self.phonemes(3, 8)?))
+    }
+}
+
+// how many letters each of n phonemes gets if letters are shared out as evenly as they can be
+fn even_split(letters: usize, n: usize) -> Vec<usize> {
+    (0..n).map(|i| (i + 1) * letters / n - i * letters / n).collect()
+}
+
+fn count_spellings(counts: &mut BTreeMap<String, BTreeMap<String, u32>>, word: &str, phonemes: &[String], lengths: &[usize]) {
+    let mut start = 0;
+    for (phoneme, &len) in phonemes.iter().zip(lengths) {
+        let spelling = word[start..start + len].to_string();
+        *counts.entry(phoneme.clone()).or_default().entry(spelling).or_insert(0) += 1;
+        start += len;
+    }
+}
+
+/*
+how many letters of word each phoneme takes, choosing the split that the spelling counts make
+likeliest. None if there's no way to give every phoneme 1 to MAX_SPELLING letters.
+*/
+fn align(counts: &BTreeMap<String, BTreeMap<String, u32>>, word: &str, phonemes: &[String]) -> Option<Vec<usize>> {
+    let n = word.len();
+    // best[i][j]: the best log-probability of spelling the first i phonemes with the first j letters,
+    // and how many letters the i'th took to get it
+    let mut best = vec![vec![None; n + 1]; phonemes.len() + 1];
+    best[0][0] = Some((0.0, 0));
+    for (i, phoneme) in phonemes.iter().enumerate() {
+        let spellings = counts.get(phoneme);
+        let total = spellings.map_or(0, |s| s.values().sum::<u32>()) as f64;
+        for j in 0..n {
+            let (score, _) = match best[i][j] {
+                Some(b) => b,
+                None => continue
+            };
+            for len in 1..=MAX_SPELLING.min(n - j) {
+                let seen = spellings.and_then(|s| s.get(&word[j..j + len])).map_or(0, |&c| c);
+                // a little probability for spellings we never saw, so some split always exists
+                let p = (seen as f64 + 0.01) / (total + 1.0);
+                let candidate = score + p.ln();
+                if best[i + 1][j + len].is_none_or(|(s, _)| candidate > s) {
+                    best[i + 1][j + len] = Some((candidate, len));
+                }
+            }
+        }
+    }
+
+    best[phonemes.len()][n]?;
+    let mut lengths = vec![0; phonemes.len()];
+    let mut j = n;
+    for i in (0..phonemes.len()).rev() {
+        let (_, len) = best[i + 1][j]?;
+        lengths[i] = len;
+        j -= len;
+    }
+    Some(lengths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DICT: &str = ";;; a tiny CMUdict
+CAT  K AE1 T
+CATS  K AE1 T S
+MAT  M AE1 T
+KIT  K IH1 T
+THAT  DH AE1 T
+THOUGHT  TH AO1 T
+TOUGH(2)  T AH1 F
+";
+
+    #[test]
+    fn test_from_cmudict() {
+        let chain = PhonemeChain::from_cmudict(DICT).unwrap();
+        let phonemes = |s: &str| s.split(' ').map(|p| p.to_string()).collect::<Vec<String>>();
+        assert_eq!(chain.spell(&phonemes("K AE T")), "cat");
+        assert_eq!(chain.spell(&phonemes("M IH T S")), "mits");
+        assert_eq!(chain.spell(&phonemes("ZH AE")), "a");
+
+        let word = chain.phonemes(2, 4).unwrap();
+        assert!(word.len() >= 2 && word.len() <= 4);
+        assert!(!chain.invent().unwrap().is_empty());
+        assert_eq!(PhonemeChain::from_cmudict(";;; nothing\n").err(), Some(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_align() {
+        let mut counts = BTreeMap::new();
+        for (word, phonemes) in &[("at", ["AE", "T"]), ("it", ["IH", "T"]), ("an", ["AE", "N"])] {
+            let phonemes: Vec<String> = phonemes.iter().map(|p| p.to_string()).collect();
+            count_spellings(&mut counts, word, &phonemes, &[1, 1]);
+        }
+        let phonemes: Vec<String> = ["AE", "T"].iter().map(|p| p.to_string()).collect();
+        assert_eq!(align(&counts, "att", &phonemes), Some(vec![1, 2]));
+        assert_eq!(even_split(7, 3), vec![2, 2, 3]);
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use {Hook, Tagger};
+
+// the words of English's closed classes, which a few suffix rules can't be trusted with
+const LEXICON: &[(&str, &[&str])] = &[
+    ("DET", &["a", "an", "the", "this", "that", "these", "those", "my", "your", "his", "her", "its",
+        "our", "their", "some", "any", "no", "every", "each", "all", "both"]),
+    ("PRON", &["i", "you", "he", "she", "it", "we", "they", "me", "him", "us", "them", "who",
+        "what", "myself", "yourself", "himself", "herself", "itself", "ourselves", "themselves"]),
+    ("ADP", &["of", "in", "on", "at", "by", "for", "with", "from", "into", "onto", "over", "under",
+        "about", "after", "before", "through", "between", "against", "without", "near", "up", "down"]),
+    ("CCONJ", &["and", "or", "but", "nor", "yet", "so"]),
+    ("SCONJ", &["if", "because", "while", "when", "although", "though", "unless", "since", "until",
+        "whether", "where"]),
+    ("AUX", &["is", "am", "are", "was", "were", "be", "been", "being", "has", "have", "had", "do",
+        "does", "did", "will", "would", "shall", "should", "can", "could", "may", "might", "must"]),
+    ("PART", &["to", "not"]),
+    ("ADV", &["very", "too", "also", "just", "then", "now", "here", "there", "never", "always",
+        "often", "soon", "again", "still", "only"])
+];
+
+/*
+a rough English tagger that needs no model: a lexicon for the closed classes (DET, PRON, ADP,
+CCONJ, SCONJ, AUX, PART, ADV), numbers as NUM, and for everything else suffix rules plus the word
+before (after "to", a pronoun or an auxiliary comes a VERB). what's left is a NOUN. it's wrong
+often enough that a real tagger will do better, but it's right about the words that matter most
+to grammaticality.
+*/
+pub fn heuristic_tagger() -> Tagger {
+    Hook(Arc::new(|words: &[String]| {
+        let mut tags: Vec<String> = vec![];
+        for word in words {
+            let previous = tags.last().map(|t| t.as_str());
+            tags.push(tag(&word.to_lowercase(), previous).to_string());
+        }
+        tags
+    }))
+}
+
+fn tag(word: &str, previous: Option<&str>) -> &'static str {
+    if let Some(&(tag, _)) = LEXICON.iter().find(|(_, words)| words.contains(&word)) {
+        return tag;
+    }
+    if word.chars().any(|c| c.is_ascii_digit()) && word.chars().all(|c| c.is_ascii_digit() || ".,".contains(c)) {
+        return "NUM";
+    }
+    if word.ends_with("ly") {
+        return "ADV";
+    }
+    if ["ous", "ful", "able", "ible", "ive", "less", "ish", "ic", "est"].iter().any(|s| word.ends_with(s)) {
+        return "ADJ";
+    }
+    match previous {
+        Some("PART") | Some("PRON") | Some("AUX") => "VERB",
+        _ if word.ends_with("ing") || word.ends_with("ed") => "VERB",
+        _ => "NOUN"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Chain, GenConfig, TrainConfig};
+
+    fn words(text: &str) -> Vec<String> {
+        text.split(' ').map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_heuristic_tagger() {
+        let Hook(tagger) = heuristic_tagger();
+        assert_eq!(tagger(&words("they want to run")), words("PRON VERB PART VERB"));
+        assert_eq!(tagger(&words("The run was quickly famous in 1999")), words("DET NOUN AUX ADV ADJ ADP NUM"));
+    }
+
+    #[test]
+    fn test_tagged_chain() {
+        let mut chain = Chain::with_config(TrainConfig { tagger: Some(heuristic_tagger()), ..TrainConfig::default() });
+        chain.train("we run to the run").unwrap();
+        assert!(chain.contains("run/VERB"));
+        assert!(chain.contains("run/NOUN"));
+        assert!(!chain.contains("run"));
+        assert!(chain.has_transition("the/DET", "run/NOUN"));
+        assert!(!chain.has_transition("the/DET", "run/VERB"));
+
+        let out = chain.generate(&GenConfig { seed: Some("the".to_string()), ..GenConfig::default() }).unwrap();
+        assert_eq!(out[..2], words("the run")[..]);
+        assert!(out.iter().all(|w| !w.contains('/')));
+    }
+}
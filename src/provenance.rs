@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+use {Chain, MarkovErr};
+
+impl Chain {
+    /*
+    the same as train, but remembers that every pair in input came from source (a file name, a
+    document ID), so output can be traced back to the texts it was learned from (see sources and
+    provenance). the journal records the source along with the text.
+    */
+    pub fn train_from(&mut self, source: &str, input: &str) -> Result<(), MarkovErr> {
+        let keyed = self.keyed(self.split(input));
+        self.train_keyed(keyed, 1, Some(source))
+    }
+
+    /*
+    the sources we saw b follow a in, sorted. empty if we never did, or only in texts trained
+    without one.
+    */
+    pub fn sources(&self, a: &str, b: &str) -> Vec<String> {
+        self.sources.get(&(a.to_string(), b.to_string()))
+            .map(|sources| sources.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /*
+    for each source, how many of the steps from one of words to the next we saw in it, most first
+    and then alphabetically. words are matched the way generated output is, so that can go
+    straight in. a source that had every step is likely where the whole run was lifted from.
+    */
+    pub fn provenance(&self, words: &[impl AsRef<str>]) -> Vec<(String, usize)> {
+        let keys = self.normalize(words);
+        let mut counts: BTreeMap<&String, usize> = BTreeMap::new();
+        for pair in keys.windows(2) {
+            if let Some(sources) = self.sources.get(&(pair[0].clone(), pair[1].clone())) {
+                for source in sources {
+                    *counts.entry(source).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut out: Vec<(String, usize)> = counts.into_iter().map(|(source, n)| (source.clone(), n)).collect();
+        out.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provenance() {
+        let mut chain = Chain::new();
+        chain.train_from("diary.txt", "the cat sat on the mat").unwrap();
+        chain.train_from("notes.txt", "the cat ran away").unwrap();
+        chain.train("the cat slept").unwrap();
+        assert_eq!(chain.sources("the", "cat"), vec!["diary.txt", "notes.txt"]);
+        assert_eq!(chain.sources("cat", "slept"), Vec::<String>::new());
+        assert_eq!(chain.edges[&("the".to_string(), "cat".to_string())], 3);
+
+        let out = ["the", "cat", "sat", "on"];
+        assert_eq!(chain.provenance(&out), vec![("diary.txt".to_string(), 3), ("notes.txt".to_string(), 1)]);
+        assert_eq!(chain.provenance(&["cat"]), vec![]);
+        assert_eq!(chain.train_from("empty.txt", "!!"), Err(MarkovErr::EmptyCorpus));
+    }
+}
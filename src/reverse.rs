@@ -0,0 +1,121 @@
+use std::cmp::Ordering;
+
+use {rng, Chain, GenConfig, MarkovErr, Stop};
+
+impl Chain {
+    /*
+    returns every word we've seen come right before w, with the probability that it's the one
+    that did, most likely first
+    */
+    pub fn predecessors(&self, w: &str) -> Result<Vec<(String, f32)>, MarkovErr> {
+        let reverse = self.reverse.as_ref().ok_or(MarkovErr::NotReversed)?;
+        let total = *reverse.nodes.get(w).unwrap_or(&0) as f32;
+        let mut out: Vec<(String, f32)> = reverse.successors(w).into_iter()
+            .map(|(prev, weight)| (prev.clone(), weight / total))
+            .collect();
+        out.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+        Ok(out)
+    }
+
+    /*
+    generates right to left, so the output ends with the seed (or prompt) instead of starting with
+    it. good for completing a sentence that has to end with a particular word. each option in the
+    config means the same thing it does going forwards, except Stop::Range ends at words that
+    started a training text.
+    */
+    pub fn generate_backward(&self, config: &GenConfig) -> Result<Vec<String>, MarkovErr> {
+        let reverse = self.reverse.as_ref().ok_or(MarkovErr::NotReversed)?;
+        let prompt = config.seed.as_ref().map(|seed| {
+            let mut words = self.tokenize(seed);
+            words.reverse();
+            words
+        });
+        let mut out = reverse.generate_from(prompt, config, &mut rng(config))?;
+        out.reverse();
+        Ok(self.surface(out))
+    }
+
+    /*
+    grows a sentence outwards from a word that has to be in the middle of it: up to left_len words
+    before and right_len words after. either side can come up short at a dead end.
+    */
+    pub fn generate_around(&self, word: &str, left_len: usize, right_len: usize) -> Result<Vec<String>, MarkovErr> {
+        let left = GenConfig {
+            seed: Some(word.to_string()),
+            stop: Stop::Words(left_len + 1),
+            ..GenConfig::default()
+        };
+        let right = GenConfig {
+            stop: Stop::Words(right_len + 1),
+            ..left.clone()
+        };
+
+        let mut out = self.generate_backward(&left)?;
+        out.extend(self.generate(&right)?.into_iter().skip(1));
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TrainConfig;
+
+    fn reversed() -> Chain {
+        Chain::with_config(TrainConfig { reverse: true, ..TrainConfig::default() })
+    }
+
+    #[test]
+    fn test_predecessors() {
+        let mut chain = reversed();
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("a cat ate").unwrap();
+        let expected = vec![("a".to_string(), 0.5), ("the".to_string(), 0.5)];
+        assert_eq!(chain.predecessors("cat"), Ok(expected));
+        assert_eq!(chain.predecessors("the"), Ok(vec![("on".to_string(), 1.0)]));
+        assert_eq!(chain.predecessors("a"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_generate_around() {
+        let mut chain = reversed();
+        chain.train("we got fun and games").unwrap();
+        let expected: Vec<String> = ["got", "fun", "and", "games"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate_around("fun", 1, 5), Ok(expected));
+        assert_eq!(chain.generate_around("fun", 0, 0), Ok(vec!["fun".to_string()]));
+        assert_eq!(chain.generate_around("sad", 1, 1), Err(MarkovErr::NotSeen{w: "sad".to_string()}));
+    }
+
+    #[test]
+    fn test_predecessors_not_reversed() {
+        let mut chain = Chain::new();
+        chain.train("hello bob").unwrap();
+        assert_eq!(chain.predecessors("bob"), Err(MarkovErr::NotReversed));
+    }
+
+    #[test]
+    fn test_generate_backward() {
+        let mut chain = reversed();
+        chain.train("we got fun and games").unwrap();
+        let config = GenConfig {
+            seed: Some("and games".to_string()),
+            stop: Stop::Words(4),
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["got", "fun", "and", "games"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate_backward(&config), Ok(expected));
+    }
+
+    #[test]
+    fn test_generate_backward_to_start() {
+        let mut chain = Chain::with_config(TrainConfig { reverse: true, wrap_around: true, ..TrainConfig::default() });
+        chain.train("we got fun and games").unwrap();
+        let config = GenConfig {
+            seed: Some("games".to_string()),
+            stop: Stop::Range{min: 1, max: 100},
+            ..GenConfig::default()
+        };
+        let expected: Vec<String> = ["we", "got", "fun", "and", "games"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate_backward(&config), Ok(expected));
+    }
+}
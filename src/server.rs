@@ -0,0 +1,115 @@
+use std::sync::{Arc, RwLock};
+
+use tiny_http::{Header, Response, Server};
+
+use {Chain, GenConfig, MarkovErr, Stop};
+
+// the most words one request can ask for, so nobody can tie the server up with words=1000000000
+const MAX_WORDS: usize = 1000;
+
+/*
+answers GET /generate?seed=welcome&words=50 on addr (e.g. "0.0.0.0:8080") with generated text,
+forever. seed and words are both optional, with the same defaults as GenConfig, and words tops
+out at MAX_WORDS (1000). the chain is behind a lock so whoever else holds it can keep training it
+while we serve.
+*/
+pub fn serve(chain: Arc<RwLock<Chain>>, addr: &str) -> Result<(), MarkovErr> {
+    let server = Server::http(addr).map_err(|e| MarkovErr::Io{e: e.to_string()})?;
+    for request in server.incoming_requests() {
+        let (status, body) = {
+            let chain = chain.read().unwrap_or_else(|e| e.into_inner());
+            respond(&chain, request.url())
+        };
+        let mut response = Response::from_string(body).with_status_code(status);
+        if let Ok(header) = Header::from_bytes("Content-Type", "text/plain; charset=utf-8") {
+            response.add_header(header);
+        }
+        // the client hanging up on us is their problem, not a reason to stop serving
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+/*
+the status and body for a request to url (path and query string)
+*/
+fn respond(chain: &Chain, url: &str) -> (u16, String) {
+    let (path, query) = match url.find('?') {
+        Some(i) => (&url[..i], &url[i + 1..]),
+        None => (url, "")
+    };
+    if path != "/generate" {
+        return (404, "not found\n".to_string());
+    }
+
+    let mut config = GenConfig::default();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = match pair.find('=') {
+            Some(i) => (&pair[..i], decode(&pair[i + 1..])),
+            None => (pair, String::new())
+        };
+        match key {
+            "seed" => config.seed = Some(value),
+            "words" => match value.parse::<usize>() {
+                Ok(n) => config.stop = Stop::Words(n.min(MAX_WORDS)),
+                Err(_) => return (400, "words has to be a number\n".to_string())
+            },
+            _ => {}
+        }
+    }
+
+    match chain.generate(&config) {
+        Ok(words) => (200, words.join(" ") + "\n"),
+        Err(MarkovErr::NotSeen{w}) => (404, format!("never seen \"{}\"\n", w)),
+        Err(MarkovErr::EmptyCorpus) => (503, "nothing trained yet\n".to_string()),
+        Err(e) => (500, format!("{:?}\n", e))
+    }
+}
+
+/*
+undoes the %XX and + escapes in a query string value. anything malformed is left as it is.
+*/
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = if bytes[i] == b'%' { value.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) } else { None };
+        match (bytes[i], hex) {
+            (_, Some(byte)) => {
+                out.push(byte);
+                i += 3;
+                continue;
+            },
+            (b'+', _) => out.push(b' '),
+            (byte, _) => out.push(byte)
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        let mut chain = Chain::new();
+        chain.train("welcome to the jungle").unwrap();
+        assert_eq!(respond(&chain, "/generate?seed=welcome&words=3"), (200, "welcome to the\n".to_string()));
+        assert_eq!(respond(&chain, "/generate?seed=to+the&words=3"), (200, "to the jungle\n".to_string()));
+        assert_eq!(respond(&chain, "/generate?seed=bob").0, 404);
+        assert_eq!(respond(&chain, "/generate?words=lots").0, 400);
+        assert_eq!(respond(&chain, "/").0, 404);
+        assert_eq!(respond(&Chain::new(), "/generate").0, 503);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("hello%20there+bob"), "hello there bob");
+        assert_eq!(decode("caf%C3%A9"), "café");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz"), "%zz");
+    }
+}
@@ -0,0 +1,130 @@
+use {rng, Chain, GenConfig, MarkovErr};
+
+/*
+generation one word at a time, with someone else choosing the words: a person picking from a
+list, or a ranker scoring the candidates by something the chain can't see. the config's
+weightings, penalties and filters still decide what the candidates are and how likely each is.
+*/
+pub struct GenerationSession<'a> {
+    chain: &'a Chain,
+    config: GenConfig,
+    // the output so far, as keys
+    out: Vec<String>,
+    // how many of out's words came from the seed (or the start), which undo won't take back
+    given: usize
+}
+
+impl Chain {
+    /*
+    starts a session from the config's seed, or without one, from wherever a teleport would land
+    (as generate does). NotSeen if the seed has no words, or its last word (the one we'd carry on
+    from) is one we've never seen. EmptyCorpus if there's nowhere to start.
+    */
+    pub fn session(&self, config: GenConfig) -> Result<GenerationSession<'_>, MarkovErr> {
+        let out = match config.seed {
+            Some(ref seed) => self.tokenize(seed),
+            None => self.start(&config, &mut rng(&config)).into_iter().collect()
+        };
+        if let Some(last) = out.last().filter(|w| !self.contains(w)) {
+            return Err(MarkovErr::NotSeen{w: last.clone()});
+        }
+        if out.is_empty() {
+            return Err(match config.seed {
+                Some(ref seed) => MarkovErr::NotSeen{w: seed.clone()},
+                None => MarkovErr::EmptyCorpus
+            });
+        }
+        Ok(GenerationSession { chain: self, given: out.len(), out, config })
+    }
+}
+
+impl<'a> GenerationSession<'a> {
+    /*
+    the top words that could come next, with the chance each has, likeliest first and then
+    alphabetically. empty at a dead end.
+    */
+    pub fn candidates(&self, top: usize) -> Result<Vec<(String, f64)>, MarkovErr> {
+        let (words, chances): (Vec<String>, Vec<f64>) = self.chain.chances(&self.out, &self.config)?.into_iter().take(top).unzip();
+        Ok(self.chain.surface(words).into_iter().zip(chances).collect())
+    }
+
+    /*
+    adds word to the output. it has to be a single word and one of the candidates (of any rank),
+    matched the way training text is, or it's NotSeen.
+    */
+    pub fn choose(&mut self, word: &str) -> Result<(), MarkovErr> {
+        let not_seen = || MarkovErr::NotSeen{w: word.to_string()};
+        let mut keys = self.chain.tokenize(word);
+        if keys.len() != 1 {
+            return Err(not_seen());
+        }
+        let key = keys.remove(0);
+        if !self.chain.chances(&self.out, &self.config)?.iter().any(|(w, _)| *w == key) {
+            return Err(not_seen());
+        }
+        self.out.push(key);
+        Ok(())
+    }
+
+    /*
+    takes back the last word chosen and returns it. None once we're back to the seed.
+    */
+    pub fn undo(&mut self) -> Option<String> {
+        if self.out.len() <= self.given {
+            return None;
+        }
+        let key = self.out.pop()?;
+        self.chain.surface(vec![key]).pop()
+    }
+
+    /*
+    the output so far, seed included
+    */
+    pub fn words(&self) -> Vec<String> {
+        self.chain.surface(self.out.clone())
+    }
+
+    /*
+    whether the config's stop condition says the output is long enough
+    */
+    pub fn is_done(&self) -> bool {
+        self.chain.done(&self.out, &self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Stop;
+
+    #[test]
+    fn test_session() {
+        let mut chain = Chain::new();
+        chain.see_weighted("the", "cat", 3);
+        chain.see("the", "dog");
+        chain.see("cat", "sat");
+        let config = GenConfig { seed: Some("The".to_string()), stop: Stop::Words(3), ..GenConfig::default() };
+        let mut session = chain.session(config).unwrap();
+        assert_eq!(session.candidates(5), Ok(vec![("cat".to_string(), 0.75), ("dog".to_string(), 0.25)]));
+        assert_eq!(session.candidates(1).unwrap().len(), 1);
+
+        session.choose("Dog").unwrap();
+        assert_eq!(session.words(), vec!["the", "dog"]);
+        assert_eq!(session.candidates(5), Ok(vec![]));
+        assert_eq!(session.undo(), Some("dog".to_string()));
+        assert_eq!(session.undo(), None);
+
+        assert_eq!(session.choose("sat"), Err(MarkovErr::NotSeen{w: "sat".to_string()}));
+        assert_eq!(session.choose("!!"), Err(MarkovErr::NotSeen{w: "!!".to_string()}));
+        session.choose("cat").unwrap();
+        assert!(!session.is_done());
+        session.choose("sat").unwrap();
+        assert!(session.is_done());
+        assert_eq!(session.words(), vec!["the", "cat", "sat"]);
+
+        let config = GenConfig { seed: Some("!!".to_string()), ..GenConfig::default() };
+        assert_eq!(chain.session(config).err(), Some(MarkovErr::NotSeen{w: "!!".to_string()}));
+        let config = GenConfig { seed: Some("the zebra".to_string()), ..GenConfig::default() };
+        assert_eq!(chain.session(config).err(), Some(MarkovErr::NotSeen{w: "zebra".to_string()}));
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use store::{pairs, walk};
+use {Chain, ChainStore, MarkovErr, MemoryStore, TrainConfig};
+
+/*
+a chain split into shards by a hash of each pair's first word, each behind its own lock, so many
+threads can train and generate from one chain at once and only wait on each other when they touch
+the same shard. everything that follows a word is in that word's shard, so a step of a walk only
+ever reads one.
+*/
+pub struct ShardedChain {
+    shards: Vec<RwLock<MemoryStore>>,
+    // an empty chain with our config, which we only use to split text up the same way a Chain would
+    tokenizer: Chain
+}
+
+impl ShardedChain {
+    /*
+    a chain with this many shards (at least one). a few times the number of threads that'll use it
+    keeps them from running into each other much.
+    */
+    pub fn new(shards: usize) -> ShardedChain {
+        ShardedChain::with_config(shards, TrainConfig::default())
+    }
+
+    /*
+    config works as it does for StoredChain
+    */
+    pub fn with_config(shards: usize, config: TrainConfig) -> ShardedChain {
+        ShardedChain {
+            shards: (0..shards.max(1)).map(|_| RwLock::new(MemoryStore::new())).collect(),
+            tokenizer: Chain::with_config(TrainConfig { index_corpus: false, reverse: false, ..config })
+        }
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /*
+    the same as StoredChain::train, but it only needs &self, so threads can share the chain. each
+    shard the text touches is locked once, not once per pair.
+    */
+    pub fn train(&self, input: &str) -> Result<(), MarkovErr> {
+        let words = self.tokenizer.tokenize(input);
+        let mut by_shard: HashMap<usize, Vec<(&String, &String, u64)>> = HashMap::new();
+        for pair in pairs(&words, &self.tokenizer.config)? {
+            by_shard.entry(self.shard(pair.0)).or_default().push(pair);
+        }
+        for (shard, pairs) in by_shard {
+            let mut store = write(&self.shards[shard]);
+            for (a, b, count) in pairs {
+                store.increment(a, b, count)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, a: &str, b: &str) -> Result<u64, MarkovErr> {
+        read(&self.shards[self.shard(a)]).get(a, b)
+    }
+
+    pub fn successors(&self, a: &str) -> Result<Vec<(String, u64)>, MarkovErr> {
+        read(&self.shards[self.shard(a)]).successors(a)
+    }
+
+    /*
+    the same as StoredChain::generate
+    */
+    pub fn generate(&self, seed: &str, len: usize) -> Result<Vec<String>, MarkovErr> {
+        walk(self.tokenizer.tokenize(seed), seed, len, |w| self.successors(w))
+    }
+
+    fn shard(&self, w: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        w.hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+}
+
+// a thread that panicked partway through an update leaves counts that are slightly off, which
+// isn't worth taking everyone else down over
+fn read(shard: &RwLock<MemoryStore>) -> RwLockReadGuard<'_, MemoryStore> {
+    shard.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write(shard: &RwLock<MemoryStore>) -> RwLockWriteGuard<'_, MemoryStore> {
+    shard.write().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_sharded_chain() {
+        let chain = ShardedChain::new(4);
+        chain.train("hello there bob").unwrap();
+        assert_eq!(chain.get("hello", "there"), Ok(1));
+        assert_eq!(chain.successors("there"), Ok(vec![("bob".to_string(), 1)]));
+        let expected: Vec<String> = ["hello", "there", "bob"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate("hello", 5), Ok(expected));
+        assert_eq!(chain.train(""), Err(MarkovErr::EmptyCorpus));
+        assert_eq!(ShardedChain::new(0).shards(), 1);
+    }
+
+    #[test]
+    fn test_parallel_training() {
+        let chain = ShardedChain::new(8);
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        chain.train("the cat sat on the mat").unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(chain.get("the", "cat"), Ok(400));
+        assert_eq!(chain.get("on", "the"), Ok(400));
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use sled::{Config, Db};
+
+use {ChainStore, MarkovErr};
+
+/*
+a ChainStore in a sled database, an embedded key-value store on disk, so a StoredChain trained on
+hundreds of millions of words only needs as much memory as sled's cache.
+
+each pair is one key, the first word's length (a big-endian u32), the first word, then the second
+word, so all of a word's successors sit together and successors is one prefix scan. the value is
+the count as a big-endian u64.
+*/
+pub struct SledStore {
+    db: Db
+}
+
+impl From<sled::Error> for MarkovErr {
+    fn from(e: sled::Error) -> MarkovErr {
+        MarkovErr::Store{e: e.to_string()}
+    }
+}
+
+impl SledStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<SledStore, MarkovErr> {
+        Ok(SledStore { db: sled::open(path)? })
+    }
+
+    /*
+    a store that's deleted once it's dropped, for tests and one-off runs
+    */
+    pub fn temporary() -> Result<SledStore, MarkovErr> {
+        Ok(SledStore { db: Config::new().temporary(true).open()? })
+    }
+
+    /*
+    waits for everything written so far to reach the disk. sled does this by itself every so
+    often, but not on drop.
+    */
+    pub fn flush(&self) -> Result<(), MarkovErr> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+impl ChainStore for SledStore {
+    fn get(&self, a: &str, b: &str) -> Result<u64, MarkovErr> {
+        Ok(self.db.get(key(a, b))?.map_or(0, |count| decode(&count)))
+    }
+
+    fn increment(&mut self, a: &str, b: &str, by: u64) -> Result<(), MarkovErr> {
+        self.db.update_and_fetch(key(a, b), |count| {
+            Some((count.map_or(0, decode) + by).to_be_bytes().to_vec())
+        })?;
+        Ok(())
+    }
+
+    fn successors(&self, a: &str) -> Result<Vec<(String, u64)>, MarkovErr> {
+        let prefix = key(a, "");
+        self.db.scan_prefix(&prefix)
+            .map(|entry| {
+                let (k, count) = entry?;
+                let b = String::from_utf8(k[prefix.len()..].to_vec()).map_err(|_| MarkovErr::Corrupt)?;
+                Ok((b, decode(&count)))
+            })
+            .collect()
+    }
+}
+
+fn key(a: &str, b: &str) -> Vec<u8> {
+    let mut key = (a.len() as u32).to_be_bytes().to_vec();
+    key.extend_from_slice(a.as_bytes());
+    key.extend_from_slice(b.as_bytes());
+    key
+}
+
+// anything that isn't 8 bytes wasn't written by us, so count it as never seen
+fn decode(count: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    if count.len() == 8 {
+        bytes.copy_from_slice(count);
+    }
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StoredChain;
+    use TrainConfig;
+
+    #[test]
+    fn test_sled_store() {
+        let mut store = SledStore::temporary().unwrap();
+        store.increment("a", "b", 2).unwrap();
+        store.increment("a", "b", 1).unwrap();
+        store.increment("a", "c", 1).unwrap();
+        store.increment("ab", "c", 1).unwrap();
+        assert_eq!(store.get("a", "b"), Ok(3));
+        assert_eq!(store.get("b", "a"), Ok(0));
+        assert_eq!(store.successors("a"), Ok(vec![("b".to_string(), 3), ("c".to_string(), 1)]));
+    }
+
+    #[test]
+    fn test_stored_chain() {
+        let mut chain = StoredChain::with_store(SledStore::temporary().unwrap(), TrainConfig::default());
+        chain.train("hello there bob").unwrap();
+        let expected: Vec<String> = ["hello", "there", "bob"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate("hello", 3), Ok(expected));
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use {ChainStore, MarkovErr};
+
+/*
+a ChainStore in an SQLite database, for a StoredChain, so a long-running bot can keep adding to its
+model a text at a time without holding all of it in memory or saving the whole thing over and
+over. every count is in one table, edges (a, b, count).
+*/
+pub struct SqliteStore {
+    conn: Connection
+}
+
+impl From<rusqlite::Error> for MarkovErr {
+    fn from(e: rusqlite::Error) -> MarkovErr {
+        MarkovErr::Store{e: e.to_string()}
+    }
+}
+
+impl SqliteStore {
+    /*
+    opens the database at path, creating it (and the table) if need be
+    */
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteStore, MarkovErr> {
+        let conn = Connection::open(path)?;
+        // every increment is its own transaction, and without these each one waits on a full sync
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        SqliteStore::with_connection(conn)
+    }
+
+    pub fn in_memory() -> Result<SqliteStore, MarkovErr> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<SqliteStore, MarkovErr> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS edges (
+                a TEXT NOT NULL,
+                b TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                PRIMARY KEY (a, b)
+            ) WITHOUT ROWID",
+            []
+        )?;
+        Ok(SqliteStore { conn })
+    }
+}
+
+impl ChainStore for SqliteStore {
+    fn get(&self, a: &str, b: &str) -> Result<u64, MarkovErr> {
+        let count: Option<i64> = self.conn
+            .prepare_cached("SELECT count FROM edges WHERE a = ?1 AND b = ?2")?
+            .query_row(params![a, b], |row| row.get(0))
+            .optional()?;
+        Ok(count.unwrap_or(0) as u64)
+    }
+
+    fn increment(&mut self, a: &str, b: &str, by: u64) -> Result<(), MarkovErr> {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO edges (a, b, count) VALUES (?1, ?2, ?3)
+                ON CONFLICT (a, b) DO UPDATE SET count = count + excluded.count"
+            )?
+            .execute(params![a, b, by as i64])?;
+        Ok(())
+    }
+
+    fn successors(&self, a: &str) -> Result<Vec<(String, u64)>, MarkovErr> {
+        let mut statement = self.conn.prepare_cached("SELECT b, count FROM edges WHERE a = ?1")?;
+        let rows = statement.query_map(params![a], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+        rows.map(|row| row.map_err(MarkovErr::from)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StoredChain;
+    use TrainConfig;
+
+    #[test]
+    fn test_sqlite_store() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        store.increment("a", "b", 2).unwrap();
+        store.increment("a", "b", 1).unwrap();
+        store.increment("a", "c", 1).unwrap();
+        assert_eq!(store.get("a", "b"), Ok(3));
+        assert_eq!(store.get("b", "a"), Ok(0));
+        let mut next = store.successors("a").unwrap();
+        next.sort();
+        assert_eq!(next, vec![("b".to_string(), 3), ("c".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = ::std::env::temp_dir().join(format!("markov-test-{}.sqlite", ::std::process::id()));
+        {
+            let mut chain = StoredChain::with_store(SqliteStore::open(&path).unwrap(), TrainConfig::default());
+            chain.train("hello there bob").unwrap();
+        }
+        let chain = StoredChain::with_store(SqliteStore::open(&path).unwrap(), TrainConfig::default());
+        let out = chain.generate("hello", 3);
+        drop(chain);
+        for suffix in &["", "-wal", "-shm"] {
+            let _ = ::std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+        let expected: Vec<String> = ["hello", "there", "bob"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(out, Ok(expected));
+    }
+}
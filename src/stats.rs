@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::mem::size_of;
+
+use Chain;
+
+/*
+a summary of a trained chain, for checking that it came out the way you expected
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    // how many different words we've seen
+    pub vocab: usize,
+    // how many different pairs we've seen
+    pub edges: usize,
+    // the words with the most different neighbours (before or after them), most first
+    pub hubs: Vec<(String, usize)>,
+    // how unpredictable the next word is, in bits, averaged over every step of training. 0 means
+    // every word only ever led one place.
+    pub mean_entropy: f64,
+    // the most unpredictable any one word's next word is, in bits
+    pub max_entropy: f64,
+    // how many words only ever led to one other word
+    pub deterministic: usize,
+    // roughly how many bytes the chain takes up in memory
+    pub memory: usize
+}
+
+// what we guess a map spends on each entry besides the key and value themselves
+const ENTRY_OVERHEAD: usize = 16;
+
+impl Chain {
+    /*
+    counts up the stats for this chain, listing the top hubs
+    */
+    pub fn stats(&self, top: usize) -> Stats {
+        let mut degree: HashMap<&String, usize> = HashMap::new();
+        for (a, b) in self.edges.keys() {
+            *degree.entry(a).or_insert(0) += 1;
+            *degree.entry(b).or_insert(0) += 1;
+        }
+        let mut hubs: Vec<(String, usize)> = degree.into_iter().map(|(w, n)| (w.clone(), n)).collect();
+        hubs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hubs.truncate(top);
+
+        let mut total = 0.0;
+        let mut weighted = 0.0;
+        let mut max_entropy: f64 = 0.0;
+        let mut deterministic = 0;
+        for (w, &count) in &self.nodes {
+            let successors = self.successors(w);
+            if successors.len() == 1 {
+                deterministic += 1;
+            }
+            let entropy = entropy(&successors);
+            max_entropy = max_entropy.max(entropy);
+            weighted += entropy * count as f64;
+            total += count as f64;
+        }
+
+        Stats {
+            vocab: self.vocab().len(),
+            edges: self.edges.len(),
+            hubs,
+            mean_entropy: if total > 0.0 { weighted / total } else { 0.0 },
+            max_entropy,
+            deterministic,
+            memory: self.memory()
+        }
+    }
+
+    /*
+    a rough count of the bytes behind every map (sources included), the corpus and the reverse
+    chain. rough because we can only guess how much the maps themselves spend on bookkeeping.
+    */
+    fn memory(&self) -> usize {
+        let words = |counts: &BTreeMap<String, i32>| -> usize {
+            counts.keys().map(|w| w.len() + size_of::<(String, i32)>() + ENTRY_OVERHEAD).sum()
+        };
+        let edges: usize = self.edges.keys()
+            .map(|(a, b)| a.len() + b.len() + size_of::<((String, String), i32)>() + ENTRY_OVERHEAD)
+            .sum();
+        let surfaces: usize = self.surfaces.iter()
+            .map(|(w, forms)| w.len() + size_of::<(String, BTreeMap<String, i32>)>() + ENTRY_OVERHEAD + words(forms))
+            .sum();
+        let sources: usize = self.sources.iter()
+            .map(|((a, b), from)| {
+                a.len() + b.len() + size_of::<((String, String), BTreeSet<String>)>() + ENTRY_OVERHEAD
+                    + from.iter().map(|source| source.len() + size_of::<String>() + ENTRY_OVERHEAD).sum::<usize>()
+            })
+            .sum();
+        let corpus: usize = self.corpus.as_ref().map_or(0, |corpus| {
+            corpus.texts().iter()
+                .map(|text| size_of::<Vec<String>>() + text.iter().map(|w| w.len() + size_of::<String>()).sum::<usize>())
+                .sum()
+        });
+        let known: usize = self.known.as_ref().map_or(0, |known| known.iter().map(|w| w.len() + size_of::<String>() + ENTRY_OVERHEAD).sum());
+        let reverse = self.reverse.as_ref().map_or(0, |reverse| reverse.memory());
+
+        size_of::<Chain>() + words(&self.nodes) + words(&self.incoming) + words(&self.starts) + words(&self.ends)
+            + edges + surfaces + sources + corpus + known + reverse
+    }
+}
+
+/*
+the entropy in bits of picking one of these words in proportion to its weight
+*/
+fn entropy(choices: &[(&String, f32)]) -> f64 {
+    let total: f64 = choices.iter().map(|&(_, weight)| weight as f64).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    -choices.iter()
+        .map(|&(_, weight)| weight as f64 / total)
+        .filter(|&p| p > 0.0)
+        .map(|p| p * p.log2())
+        .sum::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mut chain = Chain::new();
+        chain.train("the cat sat on the mat").unwrap();
+        let stats = chain.stats(1);
+        assert_eq!(stats.vocab, 5);
+        assert_eq!(stats.edges, 5);
+        assert_eq!(stats.hubs, vec![("the".to_string(), 3)]);
+        // "the" goes to cat or mat, half and half, and everything else only goes one place
+        assert_eq!(stats.max_entropy, 1.0);
+        assert_eq!(stats.mean_entropy, 2.0 / 5.0);
+        assert_eq!(stats.deterministic, 3);
+        assert!(stats.memory > 0);
+        assert_eq!(Chain::new().stats(10).mean_entropy, 0.0);
+    }
+}
@@ -0,0 +1,198 @@
+/*
+pluggable storage for transition counts, for models too big to hold in memory.
+
+this is narrower than a Chain generic over its storage, on purpose. Chain always keeps its counts
+in its own BTreeMaps and reads them directly, so no ChainStore (MemoryStore, SqliteStore,
+SledStore) can back a Chain or anything built on one: GenConfig and its stops, teleports,
+penalties and hooks, saving and loading, journals, the analysis. all of that needs the whole model
+in memory. a ChainStore only backs a StoredChain, which trains and walks with generate(seed, len),
+and that's all: picking each word in proportion to its count, no further.
+*/
+use std::collections::HashMap;
+
+use rand::thread_rng;
+
+use {pick, Chain, MarkovErr, TrainConfig};
+
+/*
+somewhere to keep transition counts. Chain keeps its own in BTreeMaps and can't use one of these;
+this is for StoredChain, which can keep them anywhere (a database, say).
+*/
+pub trait ChainStore {
+    /*
+    how many times we've seen b follow a
+    */
+    fn get(&self, a: &str, b: &str) -> Result<u64, MarkovErr>;
+
+    fn increment(&mut self, a: &str, b: &str, by: u64) -> Result<(), MarkovErr>;
+
+    /*
+    every word we've seen follow a, with how many times we saw it. the order doesn't matter.
+    */
+    fn successors(&self, a: &str) -> Result<Vec<(String, u64)>, MarkovErr>;
+}
+
+/*
+a ChainStore in memory, keyed by the first word so successors doesn't have to look at every pair
+*/
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    edges: HashMap<String, HashMap<String, u64>>
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl ChainStore for MemoryStore {
+    fn get(&self, a: &str, b: &str) -> Result<u64, MarkovErr> {
+        Ok(self.edges.get(a).and_then(|next| next.get(b)).cloned().unwrap_or(0))
+    }
+
+    fn increment(&mut self, a: &str, b: &str, by: u64) -> Result<(), MarkovErr> {
+        *self.edges.entry(a.to_string()).or_default().entry(b.to_string()).or_insert(0) += by;
+        Ok(())
+    }
+
+    fn successors(&self, a: &str) -> Result<Vec<(String, u64)>, MarkovErr> {
+        Ok(self.edges.get(a)
+            .map(|next| next.iter().map(|(b, &count)| (b.clone(), count)).collect())
+            .unwrap_or_default())
+    }
+}
+
+/*
+a chain that keeps its counts in a ChainStore. it only knows about pairs, so it can train and walk
+but none of the extras Chain has (GenConfig, starts and ends, reverse, corpus index, analysis).
+*/
+pub struct StoredChain<S: ChainStore = MemoryStore> {
+    store: S,
+    // an empty chain with our config, which we only use to split text up the same way a Chain would
+    tokenizer: Chain
+}
+
+impl StoredChain<MemoryStore> {
+    pub fn new() -> StoredChain<MemoryStore> {
+        StoredChain::with_store(MemoryStore::new(), TrainConfig::default())
+    }
+}
+
+impl Default for StoredChain<MemoryStore> {
+    fn default() -> StoredChain<MemoryStore> {
+        StoredChain::new()
+    }
+}
+
+impl<S: ChainStore> StoredChain<S> {
+    /*
+    config works as it does for Chain, except for the options that need what StoredChain doesn't
+    keep (index_corpus, reverse)
+    */
+    pub fn with_store(store: S, config: TrainConfig) -> StoredChain<S> {
+        let tokenizer = Chain::with_config(TrainConfig { index_corpus: false, reverse: false, ..config });
+        StoredChain { store, tokenizer }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    pub fn train(&mut self, input: &str) -> Result<(), MarkovErr> {
+        let words = self.tokenizer.tokenize(input);
+        for (a, b, count) in pairs(&words, &self.tokenizer.config)? {
+            self.store.increment(a, b, count)?;
+        }
+        Ok(())
+    }
+
+    /*
+    walks len words on from seed (seed included), stopping early at a dead end. the store only
+    knows about pairs, so a seed with nowhere to go is NotSeen even if it ended a training text.
+    */
+    pub fn generate(&self, seed: &str, len: usize) -> Result<Vec<String>, MarkovErr> {
+        walk(self.tokenizer.tokenize(seed), seed, len, |w| self.store.successors(w))
+    }
+}
+
+/*
+the pairs a text's words make, and how much each counts for, the same way Chain::train counts them
+*/
+pub(crate) fn pairs<'a>(words: &'a [String], config: &TrainConfig) -> Result<Vec<(&'a String, &'a String, u64)>, MarkovErr> {
+    if words.is_empty() {
+        return Err(MarkovErr::EmptyCorpus);
+    }
+    let mut out = vec![];
+    let window = config.window.max(1);
+    for (i, a) in words.iter().enumerate() {
+        for (d, b) in words[i + 1..].iter().take(window).enumerate() {
+            out.push((a, b, (window - d) as u64));
+        }
+    }
+    if config.wrap_around {
+        out.push((&words[words.len() - 1], &words[0], window as u64));
+    }
+    out.retain(|&(a, b, _)| a != b || config.self_loops);
+    Ok(out)
+}
+
+/*
+walks len words on from prompt, picking each next word from what successors says
+*/
+pub(crate) fn walk<F>(mut out: Vec<String>, seed: &str, len: usize, successors: F) -> Result<Vec<String>, MarkovErr>
+    where F: Fn(&str) -> Result<Vec<(String, u64)>, MarkovErr>
+{
+    if out.is_empty() {
+        return Err(MarkovErr::NotSeen{w: seed.to_string()});
+    }
+    let mut rng = thread_rng();
+    while out.len() < len {
+        let successors = successors(&out[out.len() - 1])?;
+        if successors.is_empty() && out.len() == 1 {
+            return Err(MarkovErr::NotSeen{w: seed.to_string()});
+        }
+        let choices: Vec<(&String, f32)> = successors.iter().map(|(w, count)| (w, *count as f32)).collect();
+        match pick(&choices, &mut rng) {
+            Some(next) => out.push(next.clone()),
+            None => break
+        }
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store() {
+        let mut store = MemoryStore::new();
+        store.increment("a", "b", 2).unwrap();
+        store.increment("a", "b", 1).unwrap();
+        store.increment("a", "c", 1).unwrap();
+        assert_eq!(store.get("a", "b"), Ok(3));
+        assert_eq!(store.get("b", "a"), Ok(0));
+        let mut next = store.successors("a").unwrap();
+        next.sort();
+        assert_eq!(next, vec![("b".to_string(), 3), ("c".to_string(), 1)]);
+        assert_eq!(store.successors("z"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_stored_chain() {
+        let mut chain = StoredChain::new();
+        chain.train("Hello there, bob").unwrap();
+        assert_eq!(chain.store().get("hello", "there"), Ok(1));
+        let expected: Vec<String> = ["hello", "there", "bob"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(chain.generate("hello", 5), Ok(expected));
+        assert_eq!(chain.generate("alice", 5), Err(MarkovErr::NotSeen{w: "alice".to_string()}));
+        assert_eq!(chain.generate("!", 5), Err(MarkovErr::NotSeen{w: "!".to_string()}));
+        assert_eq!(chain.train("?!"), Err(MarkovErr::EmptyCorpus));
+    }
+}
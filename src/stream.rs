@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
+use std::slice;
+
+use rand::RngCore;
+
+use {rng, Chain, GenConfig, MarkovErr};
+
+/*
+the words of an endless walk, one at a time, from Chain::iter_from. it ends at the first dead end,
+or after the first error, and never yields anything again after that.
+*/
+pub struct Words<'a> {
+    chain: &'a Chain,
+    config: GenConfig,
+    rng: Box<dyn RngCore>,
+    // the seed's words, which we hand out before we start walking
+    pending: VecDeque<String>,
+    // the key we're currently on. None once we've finished.
+    last: Option<String>,
+    // an error to hand out before we finish, for a seed we can't walk from
+    err: Option<MarkovErr>
+}
+
+impl Chain {
+    /*
+    the words of a walk from seed (seed included), as they're generated, with no limit on how many.
+    take as many as you need. an unknown seed gives a single NotSeen.
+    */
+    pub fn iter_from(&self, seed: &str) -> Words<'_> {
+        let config = GenConfig { seed: Some(seed.to_string()), ..GenConfig::default() };
+        let prompt = self.tokenize(seed);
+        let last = prompt.last().cloned().filter(|w| self.contains(w));
+        let (pending, err) = match last {
+            Some(_) => (prompt.into_iter().collect(), None),
+            None => (VecDeque::new(), Some(MarkovErr::NotSeen{w: seed.to_string()}))
+        };
+        Words { chain: self, rng: rng(&config), config, pending, last, err }
+    }
+}
+
+impl<'a> Iterator for Words<'a> {
+    type Item = Result<String, MarkovErr>;
+
+    fn next(&mut self) -> Option<Result<String, MarkovErr>> {
+        if let Some(e) = self.err.take() {
+            return Some(Err(e));
+        }
+        if let Some(w) = self.pending.pop_front() {
+            return Some(Ok(self.surface(w)));
+        }
+        let last = self.last.take()?;
+        match self.chain.step(slice::from_ref(&last), &self.config, &mut self.rng) {
+            Ok(Some(next)) => {
+                self.last = Some(next.clone());
+                Some(Ok(self.surface(next)))
+            },
+            Ok(None) => None,
+            Err(e) => Some(Err(e))
+        }
+    }
+}
+
+impl<'a> FusedIterator for Words<'a> {}
+
+impl<'a> Words<'a> {
+    fn surface(&self, w: String) -> String {
+        self.chain.surface(vec![w]).pop().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iter_from() {
+        let mut chain = Chain::new();
+        chain.train("hello there bob").unwrap();
+        let words: Vec<_> = chain.iter_from("Hello there").collect();
+        let expected: Vec<_> = ["hello", "there", "bob"].iter().map(|w| Ok(w.to_string())).collect();
+        assert_eq!(words, expected);
+
+        let mut words = chain.iter_from("alice");
+        assert_eq!(words.next(), Some(Err(MarkovErr::NotSeen{w: "alice".to_string()})));
+        assert_eq!(words.next(), None);
+        assert_eq!(words.next(), None);
+    }
+
+    #[test]
+    fn test_iter_from_loops() {
+        let mut chain = Chain::new();
+        chain.train("a b a b").unwrap();
+        assert_eq!(chain.iter_from("a").take(100).filter(|w| w.is_ok()).count(), 100);
+    }
+}
@@ -0,0 +1,73 @@
+use {Chain, MarkovErr};
+
+impl Chain {
+    /*
+    trains on an SRT or WebVTT subtitle file, each cue its own text. returns how many cues that
+    was (see subtitle_cues).
+    */
+    pub fn train_subtitles(&mut self, text: &str) -> Result<usize, MarkovErr> {
+        let cues = subtitle_cues(text);
+        self.train_sequences(&cues)?;
+        Ok(cues.len())
+    }
+}
+
+/*
+the text of each cue in an SRT or WebVTT file, without the cue numbers, timestamps or formatting
+tags, and with a cue's lines joined by spaces. blocks without a timestamp (the WEBVTT header,
+NOTE and STYLE blocks) aren't cues, so they're left out.
+*/
+pub fn subtitle_cues(text: &str) -> Vec<String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text).replace("\r\n", "\n");
+    text.split("\n\n")
+        .filter_map(|block| {
+            let lines: Vec<&str> = block.lines().collect();
+            // everything up to the timing line is a cue number or identifier
+            let timing = lines.iter().position(|line| line.contains("-->"))?;
+            let cue = lines[timing + 1..].iter().map(|line| strip_tags(line)).collect::<Vec<String>>().join(" ");
+            let cue = cue.split_whitespace().collect::<Vec<&str>>().join(" ");
+            if cue.is_empty() { None } else { Some(cue) }
+        })
+        .collect()
+}
+
+// drops <i>-style tags (and VTT's <00:00:01.000> and <c.yellow>) and SRT's {\an8}-style ones
+fn strip_tags(line: &str) -> String {
+    let mut out = String::new();
+    let mut close = None;
+    for c in line.chars() {
+        match (close, c) {
+            (None, '<') => close = Some('>'),
+            (None, '{') => close = Some('}'),
+            (None, c) => out.push(c),
+            (Some(end), c) if c == end => close = None,
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srt() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>Hello there.</i>\r\n\r\n\
+            2\r\n00:00:03,000 --> 00:00:04,000\r\n{\\an8}General\r\nKenobi!\r\n\r\n";
+        assert_eq!(subtitle_cues(srt), vec!["Hello there.".to_string(), "General Kenobi!".to_string()]);
+    }
+
+    #[test]
+    fn test_vtt() {
+        let vtt = "WEBVTT\n\nNOTE made by hand\n\nintro\n00:01.000 --> 00:02.000 align:start\n\
+            <v Bob>we got <00:01.500><c.yellow>fun</c> and games\n";
+        assert_eq!(subtitle_cues(vtt), vec!["we got fun and games".to_string()]);
+
+        let mut chain = Chain::new();
+        assert_eq!(chain.train_subtitles(vtt), Ok(1));
+        assert!(chain.has_transition("fun", "and"));
+        assert!(!chain.contains("webvtt"));
+        assert_eq!(chain.train_subtitles("WEBVTT\n"), Err(MarkovErr::EmptyCorpus));
+    }
+}
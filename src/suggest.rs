@@ -0,0 +1,53 @@
+use {Chain, MarkovErr, UNK};
+
+impl Chain {
+    /*
+    the k likeliest words to come next after context, with the probability of each, likeliest
+    first: what a text box would offer to autocomplete with. only the context's last word
+    matters. ties go alphabetically. NotSeen if we've never seen that word (a word we've only
+    seen at the end of a text gets no suggestions), EmptyCorpus if context has no words at all.
+    */
+    pub fn suggest(&self, context: &str, k: usize) -> Result<Vec<(String, f64)>, MarkovErr> {
+        let words = self.tokenize(context);
+        let last = words.last().ok_or(MarkovErr::EmptyCorpus)?;
+        if !self.contains(last) {
+            return Err(MarkovErr::NotSeen{w: last.clone()});
+        }
+
+        let choices = self.successors(last);
+        let total: f64 = choices.iter().map(|&(_, weight)| weight as f64).sum();
+        let mut ranked: Vec<(&String, f64)> = choices.into_iter()
+            .filter(|&(w, _)| w != UNK)
+            .map(|(w, weight)| (w, weight as f64 / total))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        ranked.truncate(k);
+
+        let (keys, probabilities): (Vec<String>, Vec<f64>) = ranked.into_iter().map(|(w, p)| (w.clone(), p)).unzip();
+        Ok(self.surface(keys).into_iter().zip(probabilities).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Case, TrainConfig};
+
+    #[test]
+    fn test_suggest() {
+        let mut chain = Chain::new();
+        chain.train_sequences(["see you soon", "see you later", "see you soon then", "see the sea"]).unwrap();
+        assert_eq!(chain.suggest("I'll see", 5), Ok(vec![("you".to_string(), 0.75), ("the".to_string(), 0.25)]));
+        assert_eq!(chain.suggest("see you", 1), Ok(vec![("soon".to_string(), 2.0 / 3.0)]));
+        assert_eq!(chain.suggest("the sea", 3), Ok(vec![]));
+        assert_eq!(chain.suggest("hello", 3), Err(MarkovErr::NotSeen{w: "hello".to_string()}));
+        assert_eq!(chain.suggest("...", 3), Err(MarkovErr::EmptyCorpus));
+    }
+
+    #[test]
+    fn test_suggest_surfaces() {
+        let mut chain = Chain::with_config(TrainConfig { case: Case::FoldKeys, ..TrainConfig::default() });
+        chain.train("we met in Paris and Paris was lovely").unwrap();
+        assert_eq!(chain.suggest("in", 1), Ok(vec![("Paris".to_string(), 1.0)]));
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::Read;
+
+use csv::ReaderBuilder;
+
+use {Chain, MarkovErr};
+
+impl From<csv::Error> for MarkovErr {
+    fn from(e: csv::Error) -> MarkovErr {
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => MarkovErr::from(e),
+            _ => MarkovErr::Corrupt
+        }
+    }
+}
+
+impl Chain {
+    /*
+    trains on one column of a CSV with a header row, each row its own text, e.g. every
+    "review_text" in a dump of reviews. rows are read one at a time, so the file can be as big as
+    you like. returns how many rows had words in them; if none did, that's EmptyCorpus.
+    */
+    pub fn train_csv<R: Read>(&mut self, reader: R, column: &str) -> Result<usize, MarkovErr> {
+        let mut reader = ReaderBuilder::new().flexible(true).from_reader(reader);
+        let i = reader.headers()?.iter().position(|header| header == column)
+            .ok_or_else(|| MarkovErr::MissingColumn{c: column.to_string()})?;
+
+        let mut trained = 0;
+        for record in reader.records() {
+            match self.train(record?.get(i).unwrap_or("")) {
+                Ok(()) => trained += 1,
+                Err(MarkovErr::EmptyCorpus) => {},
+                Err(e) => return Err(e)
+            }
+        }
+        if trained > 0 { Ok(trained) } else { Err(MarkovErr::EmptyCorpus) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_train_csv() {
+        let csv = "id,review_text,stars\n\
+            1,\"Great food, friendly staff\",5\n\
+            2,\"said \"\"meh\"\" and left\",2\n\
+            3,,1\n\
+            4\n";
+        let mut chain = Chain::new();
+        assert_eq!(chain.train_csv(csv.as_bytes(), "review_text"), Ok(2));
+        assert!(chain.has_transition("food", "friendly"));
+        assert!(chain.has_transition("said", "meh"));
+        assert!(!chain.contains("5"));
+
+        let missing = chain.train_csv(csv.as_bytes(), "title");
+        assert_eq!(missing, Err(MarkovErr::MissingColumn{c: "title".to_string()}));
+        assert_eq!(chain.train_csv("a,b\n,1\n".as_bytes(), "a"), Err(MarkovErr::EmptyCorpus));
+    }
+}
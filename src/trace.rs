@@ -0,0 +1,106 @@
+use {rng, Chain, GenConfig, MarkovErr};
+
+/*
+why generation chose one of the words it did
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub word: String,
+    // the word before it, which is all a chain goes on
+    pub context: String,
+    // the chance word had of being picked, once the config's weightings and penalties were
+    // applied. if we teleported there, the chance following an edge would have had of landing
+    // there anyway, which is 0.0 unless it's one of context's successors.
+    pub probability: f64,
+    // whether we jumped to word rather than following an edge from context (see
+    // GenConfig::damping and GenConfig::epsilon)
+    pub teleported: bool,
+    // the likeliest candidates there were, with their chances, likeliest first. ties go
+    // alphabetically. word is one of them if it was likely enough.
+    pub alternatives: Vec<(String, f64)>
+}
+
+impl Chain {
+    /*
+    generate, but also explains each word it chose after the seed (or after the word it started
+    on, without one): what it followed, how likely it was, and the top alternatives it beat. for
+    working out why a chain came out with something strange.
+    */
+    pub fn generate_traced(&self, config: &GenConfig, top: usize) -> Result<(Vec<String>, Vec<TraceStep>), MarkovErr> {
+        let prompt = config.seed.as_ref().map(|seed| self.tokenize(seed));
+        // without a seed, the first word is wherever we started
+        let given = prompt.as_ref().map_or(1, Vec::len);
+        let mut jumps = vec![];
+        let out = self.generate_jumping(prompt, config, &mut rng(config), &mut jumps)?;
+
+        let mut steps = vec![];
+        for i in given.min(out.len())..out.len() {
+            let candidates = self.chances(&out[..i], config)?;
+            let probability = candidates.iter().find(|(w, _)| *w == out[i]).map_or(0.0, |&(_, p)| p);
+            let (alternatives, chances): (Vec<String>, Vec<f64>) = candidates.into_iter().take(top).unzip();
+            let mut words = self.surface(vec![out[i].clone(), out[i - 1].clone()]).into_iter();
+            steps.push(TraceStep {
+                word: words.next().unwrap_or_default(),
+                context: words.next().unwrap_or_default(),
+                probability,
+                teleported: jumps.contains(&i),
+                alternatives: self.surface(alternatives).into_iter().zip(chances).collect()
+            });
+        }
+        Ok((self.surface(out), steps))
+    }
+
+    /*
+    the chance each word has of coming next after out (keys), once the config's weightings and
+    penalties are applied, likeliest first and then alphabetically. empty at a dead end.
+    */
+    pub(crate) fn chances(&self, out: &[String], config: &GenConfig) -> Result<Vec<(String, f64)>, MarkovErr> {
+        Ok(self.weigh(out, config, |choices, allowed| {
+            let total: f32 = choices.iter().map(|&(_, weight)| weight).sum();
+            // step falls back to the raw weights the same way
+            let candidates = if total > 0.0 { choices } else { allowed };
+            let total: f64 = candidates.iter().map(|&(_, weight)| weight as f64).sum();
+            let mut ranked: Vec<(String, f64)> = candidates.iter()
+                .map(|&(w, weight)| (w.clone(), weight as f64 / total))
+                .collect();
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            Some(ranked)
+        })?.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Stop;
+
+    #[test]
+    fn test_generate_traced() {
+        let mut chain = Chain::new();
+        chain.see_weighted("the", "cat", 3);
+        chain.see("the", "dog");
+        chain.see("cat", "sat");
+        let config = GenConfig {
+            seed: Some("the".to_string()),
+            stop: Stop::Words(3),
+            bias: [("dog".to_string(), 0.0)].iter().cloned().collect(),
+            ..GenConfig::default()
+        };
+        let (out, steps) = chain.generate_traced(&config, 1).unwrap();
+        assert_eq!(out, vec!["the", "cat", "sat"]);
+        assert_eq!(steps, vec![
+            TraceStep { word: "cat".to_string(), context: "the".to_string(), probability: 1.0, teleported: false, alternatives: vec![("cat".to_string(), 1.0)] },
+            TraceStep { word: "sat".to_string(), context: "cat".to_string(), probability: 1.0, teleported: false, alternatives: vec![("sat".to_string(), 1.0)] }
+        ]);
+
+        let config = GenConfig { bias: Default::default(), stop: Stop::Words(2), ..config };
+        let (out, steps) = chain.generate_traced(&config, 5).unwrap();
+        assert_eq!(steps[0].alternatives, vec![("cat".to_string(), 0.75), ("dog".to_string(), 0.25)]);
+        assert_eq!(steps[0].probability, if out[1] == "cat" { 0.75 } else { 0.25 });
+
+        let config = GenConfig { damping: 0.0, seed: Some("cat".to_string()), ..config };
+        let (_, steps) = chain.generate_traced(&config, 5).unwrap();
+        assert!(steps[0].teleported);
+        assert_eq!(steps[0].probability, if steps[0].word == "sat" { 1.0 } else { 0.0 });
+    }
+}
@@ -0,0 +1,63 @@
+use std::borrow::Cow;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use UnicodeForm;
+
+/*
+puts text into the given normal form, first stripping combining marks if fold_diacritics is set.
+folding on its own composes what's left the NFC way. text comes back as-is if there's nothing to do.
+*/
+pub fn normalize(text: &str, form: Option<UnicodeForm>, fold_diacritics: bool) -> Cow<'_, str> {
+    if fold_diacritics {
+        // marks only come apart from the letters they're on once decomposed
+        let stripped: String = match form {
+            Some(UnicodeForm::Nfkc) => text.nfkd().filter(|&c| !is_combining_mark(c)).collect(),
+            Some(UnicodeForm::Nfc) | None => text.nfd().filter(|&c| !is_combining_mark(c)).collect()
+        };
+        return Cow::Owned(stripped.nfc().collect());
+    }
+    match form {
+        Some(UnicodeForm::Nfc) => Cow::Owned(text.nfc().collect()),
+        Some(UnicodeForm::Nfkc) => Cow::Owned(text.nfkc().collect()),
+        None => Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Chain, Hook, TrainConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("cafe\u{301}", Some(UnicodeForm::Nfc), false), "caf\u{e9}");
+        assert_eq!(normalize("\u{fb01}ne", Some(UnicodeForm::Nfc), false), "\u{fb01}ne");
+        assert_eq!(normalize("\u{fb01}ne", Some(UnicodeForm::Nfkc), false), "fine");
+        assert_eq!(normalize("na\u{ef}ve caf\u{e9}", None, true), "naive cafe");
+        assert_eq!(normalize("cafe\u{301}", None, false), "cafe\u{301}");
+    }
+
+    #[test]
+    fn test_train_unicode() {
+        let mut chain = Chain::with_config(TrainConfig {
+            keep_chars: Some(Hook(Arc::new(|c: char| c.is_alphabetic()))),
+            unicode: Some(UnicodeForm::Nfc),
+            ..TrainConfig::default()
+        });
+        chain.train("caf\u{e9} au lait").unwrap();
+        chain.train("cafe\u{301} noir").unwrap();
+        assert_eq!(chain.vocab(), vec!["au", "caf\u{e9}", "lait", "noir"]);
+
+        let mut chain = Chain::with_config(TrainConfig {
+            keep_chars: Some(Hook(Arc::new(|c: char| c.is_alphabetic()))),
+            fold_diacritics: true,
+            ..TrainConfig::default()
+        });
+        chain.train("Caf\u{e9} au lait").unwrap();
+        assert!(chain.has_transition("cafe", "au"));
+        assert!(chain.coverage("CAFE\u{301}").unknown.is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use {count_in, Chain, UNK};
+
+impl Chain {
+    /*
+    keeps the k most frequent words and lumps every other one together as UNK, which stops a huge
+    scraped corpus from keeping a long tail of typos and one-offs around. ties go to whichever word
+    comes first alphabetically. anything we train on afterwards that isn't one of the k words is
+    counted as UNK too, so capping after a first pass over a sample keeps memory bounded from then on.
+    */
+    pub fn cap_vocab(&mut self, k: usize) {
+        let mut ranked: Vec<(i32, String)> = self.vocab().into_iter()
+            .filter(|w| w != UNK)
+            .map(|w| (self.frequency(&w), w))
+            .collect();
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        let kept: HashSet<String> = ranked.into_iter().take(k).map(|(_, w)| w).collect();
+        self.fold_vocab(&kept);
+        self.known = Some(kept);
+    }
+
+    /*
+    the word we count w as, given the words we're keeping
+    */
+    pub(crate) fn in_vocab(&self, w: String) -> String {
+        match self.known {
+            Some(ref known) if !known.contains(&w) => UNK.to_string(),
+            _ => w
+        }
+    }
+
+    fn fold_vocab(&mut self, kept: &HashSet<String>) {
+        self.clear_cache();
+        let unk = |w: &String| if kept.contains(w) { w.clone() } else { UNK.to_string() };
+        self.nodes = fold(&self.nodes, &unk);
+        self.incoming = fold(&self.incoming, &unk);
+        self.starts = fold(&self.starts, &unk);
+        self.ends = fold(&self.ends, &unk);
+        self.edges = fold(&self.edges, &|(a, b): &(String, String)| (unk(a), unk(b)));
+        self.surfaces.retain(|w, _| kept.contains(w));
+        let mut sources: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+        for ((a, b), from) in &self.sources {
+            sources.entry((unk(a), unk(b))).or_default().extend(from.iter().cloned());
+        }
+        self.sources = sources;
+        if let Some(ref mut corpus) = self.corpus {
+            corpus.rename(&unk);
+        }
+        if let Some(ref mut reverse) = self.reverse {
+            reverse.fold_vocab(kept);
+        }
+    }
+}
+
+/*
+renames every key in counts, adding up the counts of keys that end up the same
+*/
+fn fold<K: Ord, F: Fn(&K) -> K>(counts: &BTreeMap<K, i32>, rename: &F) -> BTreeMap<K, i32> {
+    let mut out = BTreeMap::new();
+    for (k, &count) in counts {
+        count_in(&mut out, rename(k), count);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {GenConfig, Stop, TrainConfig};
+
+    fn capped() -> Chain {
+        let mut chain = Chain::with_config(TrainConfig { reverse: true, ..TrainConfig::default() });
+        chain.train("the cat sat on the mat").unwrap();
+        chain.train("the dog sat on the cat").unwrap();
+        chain.cap_vocab(4);
+        chain
+    }
+
+    #[test]
+    fn test_cap_vocab() {
+        let chain = capped();
+        assert_eq!(chain.vocab(), vec![UNK, "cat", "on", "sat", "the"]);
+        assert_eq!(chain.edges[&("the".to_string(), UNK.to_string())], 2);
+        assert_eq!(chain.nodes[UNK], 1);
+        assert_eq!(chain.starts["the"], 2);
+        assert_eq!(chain.ends[UNK], 1);
+        assert_eq!(chain.predecessors(UNK).unwrap(), vec![("the".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn test_train_after_cap() {
+        let mut chain = capped();
+        chain.train("the bird sat").unwrap();
+        assert_eq!(chain.edges[&("the".to_string(), UNK.to_string())], 3);
+        assert_eq!(chain.edges[&(UNK.to_string(), "sat".to_string())], 2);
+        assert!(!chain.contains("bird"));
+    }
+
+    #[test]
+    fn test_skip_unk() {
+        let chain = capped();
+        let config = GenConfig {
+            seed: Some("sat".to_string()),
+            stop: Stop::Words(6),
+            skip_unk: true,
+            ..GenConfig::default()
+        };
+        for _ in 0..20 {
+            let out = chain.generate(&config).unwrap();
+            assert_eq!(out, vec!["sat", "on", "the", "cat", "sat", "on"]);
+        }
+    }
+}